@@ -18,19 +18,21 @@ class Session:
         r = self.dll.set_alpn(self.hid, alpn.value.encode('utf-8'))
         if r == -1: raise Exception('set alpn error')
 
-    def set_timeout(self, connect: int = 3, read: int = 3, write: int = 3, handle: int = 30, connect_times: int = 3,
-                    handle_times: int = 3):
+    def set_timeout(self, connect: float = 3, read: float = 3, write: float = 3, handle: float = 30, connect_times: int = 3,
+                    handle_times: int = 3, tls_handshake: float = 3):
         """
-        :param connect: 连接超时,默认3s
+        :param connect: 连接超时,默认3s,可以是小数
         :param read: tcp读取超时,默认3s
         :param write: tcp写出超时,默认3s
-        :param handle: 发包处理超时,默认30s
+        :param handle: 总超时,包括重试和重定向,默认30s
         :param connect_times: 尝试连接次数,默认3次
         :param handle_times:尝试处理次数,默认3次
+        :param tls_handshake: tls握手超时,默认3s
         :return:
         """
         timeout = {
             'connect': connect,
+            'tls_handshake': tls_handshake,
             'read': read,
             'write': write,
             'handle': handle,
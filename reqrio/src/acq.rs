@@ -1,32 +1,56 @@
+use std::future::{poll_fn, Future};
+use std::io::{self, Cursor};
 use std::mem;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
 use crate::alpn::ALPN;
-use crate::coder::{HPackCoding, HackDecode};
-use crate::error::HlsResult;
+use crate::error::{HlsError, HlsResult, TimeoutPhase};
 use crate::ext::ReqExt;
 use crate::ext::{ReqGenExt, ReqPriExt};
-use crate::packet::{Frame, FrameFlag, FrameType, Header, HeaderKey, Method, Response};
-use crate::stream::{ConnParam, Proxy, Stream};
+use crate::packet::{BodyDecoder, H2Event, H2Fingerprint, Header, HeaderKey, Method, Response};
+use crate::stream::{ConnParam, H2Conn, H2Stream, Proxy, Stream, SystemProxy};
+use tokio::io::{AsyncRead, ReadBuf};
 use crate::timeout::Timeout;
-use crate::url::Url;
+use crate::url::{Protocol, Url};
 use crate::{Buffer, ReqCallback};
 use json::JsonValue;
 #[cfg(use_cls)]
 use reqtls::Fingerprint;
-use crate::body::BodyType;
+use crate::body::{BodyReader, BodyType, BODY_CHUNK};
+use crate::redirect::{Redirect, RedirectPolicy};
+use crate::retry::{ExponentialBackoff, Outcome, RetryPolicy};
+use crate::pool::{Pool, PoolConn, PoolKey};
+use crate::jar::CookieJar;
+use crate::resolve::{Resolver, SystemResolver};
+use crate::ws::{self, AcWs, WsCodec, WsConfig};
+use crate::sse::{self, Event, EventParser};
 
 pub struct AcReq {
     header: Header,
     url: Url,
-    hack_coder: HPackCoding,
+    //h2连接由后台任务持有，可以和其他AcReq共享
+    h2: Option<H2Conn>,
     stream: Stream,
     timeout: Timeout,
     callback: Option<ReqCallback>,
-    stream_id: u32,
     body: BodyType,
     alpn: ALPN,
     proxy: Proxy,
+    //按目标url选择代理，设置后覆盖proxy
+    system_proxy: Option<SystemProxy>,
     #[cfg(use_cls)]
     fingerprint: Fingerprint,
+    h2_fingerprint: H2Fingerprint,
+    pool: Option<Pool>,
+    cookie_jar: CookieJar,
+    pool_key: Option<PoolKey>,
+    reused: bool,
+    keep_alive: bool,
+    redirect: RedirectPolicy,
+    //本次请求已经跟随的重定向
+    redirects: Vec<Redirect>,
+    retry: Box<dyn RetryPolicy>,
+    resolver: Box<dyn Resolver>,
 }
 
 impl AcReq {
@@ -34,16 +58,26 @@ impl AcReq {
         AcReq {
             header: Header::new_req_h1(),
             url: Url::new(),
-            hack_coder: HPackCoding::new(),
+            h2: None,
             stream: Stream::unconnection(),
             timeout: Timeout::new(),
             callback: None,
-            stream_id: 0,
             alpn: ALPN::Http11,
             proxy: Proxy::Null,
+            system_proxy: None,
             #[cfg(use_cls)]
             fingerprint: Fingerprint::default(),
+            h2_fingerprint: H2Fingerprint::default(),
             body: BodyType::Text("".to_string()),
+            pool: None,
+            cookie_jar: CookieJar::new(),
+            pool_key: None,
+            reused: false,
+            keep_alive: true,
+            redirect: RedirectPolicy::None,
+            redirects: vec![],
+            retry: Box::new(ExponentialBackoff::default()),
+            resolver: Box::new(SystemResolver),
         }
     }
 
@@ -84,95 +118,154 @@ impl AcReq {
 
     pub async fn h1_io(&mut self, context: Vec<u8>) -> HlsResult<Response> {
         self.stream.async_write(context.as_slice()).await?;
-        let mut response = Response::new();
+        self.h1_read().await
+    }
+
+    ///分块发送请求体，长度未知时使用chunked
+    async fn h1_write_body(&mut self, mut body: BodyReader) -> HlsResult<()> {
+        let chunked = body.len().is_none();
+        let mut chunk = vec![0; BODY_CHUNK];
+        loop {
+            let len = body.async_read(&mut chunk).await?;
+            if chunked {
+                let mut data = format!("{:x}\r\n", len).into_bytes();
+                data.extend_from_slice(&chunk[..len]);
+                data.extend_from_slice(b"\r\n");
+                self.stream.async_write(&data).await?;
+            } else if len != 0 {
+                self.stream.async_write(&chunk[..len]).await?;
+            }
+            if len == 0 { return Ok(()); }
+        }
+    }
+
+    async fn h1_read(&mut self) -> HlsResult<Response> {
+        let mut response = Response::new_h1(self.header.method());
         let mut buffer = Buffer::with_capacity(16413);
-        let mut read_len = 0;
+        let mut decoder = None;
         loop {
             buffer.reset();
-            self.stream.async_read(&mut buffer).await?;
-            if self.handle_h1_res(&buffer, &mut response, &mut read_len)? { break; }
+            match self.stream.async_read(&mut buffer).await {
+                Ok(_) => if self.handle_h1_res(&buffer, &mut response, &mut decoder)? { break; },
+                //没有content-length和chunked的响应体读到连接关闭
+                Err(HlsError::PeerClosedConnection) if !response.header().is_empty() => {
+                    self.keep_alive = false;
+                    self.finish_h1_res(&mut response, &mut decoder)?;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
         }
         Ok(response)
     }
 
     async fn handle_io(&mut self) -> HlsResult<Response> {
-        let response = match self.stream.alpn() {
-            ALPN::Http20 => {
+        let response = match self.h2 {
+            Some(_) => {
                 let headers = self.gen_h2_header()?;
                 let body = self.gen_h2_body()?;
-                self.h2c_io(headers, body).await
+                self.h2c_body_io(headers, body).await
             }
-            _ => {
-                let context = self.gen_h1()?;
-                self.h1_io(context).await
+            None => match self.gen_h1()? {
+                (context, None) => self.h1_io(context).await,
+                (context, Some(body)) => {
+                    self.stream.async_write(&context).await?;
+                    self.h1_write_body(body).await?;
+                    self.h1_read().await
+                }
             }
         }?;
         self.update_cookie(&response);
-        self.callback = None;
-        if let ALPN::Http20 = self.stream.alpn() { self.stream_id += 2; }
+        if !response.header().keep_alive() { self.keep_alive = false; }
         Ok(response)
     }
 
+    ///发送请求并按重定向策略跟随重定向，整个过程不超过总超时
     pub async fn stream_io(&mut self) -> HlsResult<Response> {
-        for i in 0..self.timeout.handle_times() {
-            let res = tokio::time::timeout(self.timeout.handle(), self.handle_io()).await;
-            match &res {
-                Ok(res) => if let Err(e) = res && i != self.timeout.handle_times() - 1 {
-                    if e.to_string().to_lowercase().contains("close") {
-                        self.re_conn().await?;
-                    }
-                    println!("[AcReq] write/recv with error-{}, handle: {}/{}", e.to_string(), i + 2, self.timeout.handle_times());
-                    continue;
-                }
-                Err(_) => if i != self.timeout.handle_times() - 1 {
-                    println!("[AcReq] write/recv timeout, timeout: {:?}, handle: {}/{}", self.timeout.handle(), i + 2, self.timeout.handle_times());
-                    continue;
+        let deadline = self.timeout.start();
+        let res = tokio::time::timeout_at(deadline.into(), self.redirect_io()).await;
+        self.timeout.stop();
+        res.unwrap_or_else(|_| Err(self.deadline_exceeded()))
+    }
+
+    ///超过总超时时请求被中断，http/1.1的连接上可能还有未读完的响应
+    fn deadline_exceeded(&mut self) -> HlsError {
+        if self.h2.is_none() { self.keep_alive = false; }
+        HlsError::Timeout(TimeoutPhase::Handle)
+    }
+
+    async fn redirect_io(&mut self) -> HlsResult<Response> {
+        self.redirects.clear();
+        loop {
+            let mut response = self.retry_io().await?;
+            match self.next_redirect(response.header())? {
+                None => {
+                    response.set_history(mem::take(&mut self.redirects));
+                    self.callback = None;
+                    return Ok(response);
                 }
+                Some(redirect) => self.follow_redirect(redirect).await?,
             }
-            return match res {
-                Ok(res) => res,
-                Err(_) => Err(format!("handle timeout, handle:{}; timeout: {:?}", self.timeout.handle_times(), self.timeout.handle()).into())
+        }
+    }
+
+    async fn follow_redirect(&mut self, redirect: Redirect) -> HlsResult<()> {
+        let url = self.prepare_redirect(redirect);
+        //set_url会清空请求体，307/308需要保留
+        let body = mem::replace(&mut self.body, BodyType::Text("".to_string()));
+        self.set_url(url).await?;
+        self.body = body;
+        Ok(())
+    }
+
+    async fn retry_io(&mut self) -> HlsResult<Response> {
+        //上一个响应要求关闭连接
+        if !self.keep_alive { self.re_conn().await?; }
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let res = self.handle_io().await;
+            //读到了响应，复用的连接是可用的，之后的重试不需要因为复用而重连
+            if res.is_ok() { self.reused = false; }
+            let retry = match &res {
+                Ok(response) => self.next_retry(attempt, Outcome::Response(response.header())),
+                Err(e) => self.next_retry(attempt, Outcome::Error(e)),
             };
+            let Some(retry) = retry else { return res; };
+            tokio::time::sleep(retry.delay).await;
+            //连接池中的连接可能已被服务端关闭，未被处理的h2请求需要新的连接
+            if retry.reconnect || self.reused || !self.keep_alive { self.re_conn().await?; }
         }
-        Err("stream io error".into())
     }
 
     pub async fn re_conn(&mut self) -> HlsResult<()> {
-        self.hack_coder = HPackCoding::new();
-        self.stream_id = 0;
-        for i in 0..self.timeout.connect_times() {
+        self.h2 = None;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
             let param = ConnParam {
                 url: &self.url,
                 proxy: &self.proxy,
                 timeout: &self.timeout,
                 #[cfg(use_cls)]
                 fingerprint: &mut self.fingerprint,
-                alpn: &self.alpn,
+                //websocket只能使用http/1.1
+                alpn: if matches!(self.url.protocol(), Protocol::Ws | Protocol::Wss) { &ALPN::Http11 } else { &self.alpn },
+                resolver: self.resolver.as_ref(),
             };
             let res = tokio::time::timeout(self.timeout.connect(), self.stream.async_connect(param)).await;
-            match &res {
-                Ok(res) => if let Err(e) = res && i != self.timeout.handle_times() - 1 {
-                    println!("[AcReq] connect with error-{}, handle: {}/{}", e.to_string(), i + 2, self.timeout.handle_times());
-                    continue;
-                }
-                Err(e) => if i != self.timeout.handle_times() - 1 {
-                    println!("[AcReq] connect error, error: {:?}, handle: {}/{}", e.to_string(), i + 2, self.timeout.handle_times());
-                    continue;
-                }
+            let Err(e) = res.unwrap_or(Err(HlsError::Timeout(TimeoutPhase::Connect))) else { break; };
+            match self.next_connect_retry(attempt, &e) {
+                None => return Err(e),
+                Some(delay) => tokio::time::sleep(delay).await,
             }
-            return match res {
-                Ok(res) => match res {
-                    Ok(_) => {
-                        self.header.init_by_alpn(self.stream.alpn());
-                        if self.stream.alpn() == &ALPN::Http20 { self.handle_h2_setting().await?; }
-                        Ok(())
-                    }
-                    Err(e) => Err(e.into()),
-                },
-                Err(_) => Err(format!("connect timeout, handle:{}; timeout: {:?}", self.timeout.handle_times(), self.timeout.connect()).into())
-            };
         }
-        Err("[AcReq] connection error".into())
+        self.pool_key = Some(self.pool_key()?);
+        self.reused = false;
+        self.keep_alive = true;
+        self.header.init_by_alpn(self.stream.alpn());
+        if self.stream.alpn() == &ALPN::Http20 { self.handle_h2_setting().await?; }
+        Ok(())
     }
 
     pub async fn with_url(mut self, url: impl AsRef<str>) -> HlsResult<Self> {
@@ -189,16 +282,72 @@ impl AcReq {
     pub async fn set_url(&mut self, url: impl AsRef<str>) -> HlsResult<()> {
         let body = mem::replace(&mut self.body, BodyType::Text("".to_string()));
         drop(body);
-        let old_host = self.url.addr().host().to_string();
         self.url = Url::try_from(url.as_ref())?;
-        if self.url.addr().host() != old_host {
-            let host = self.url.addr().to_string().replace(":80", "").replace(":443", "");
+        if let Some(system) = &self.system_proxy { self.proxy = system.select(&self.url); }
+        let key = self.pool_key()?;
+        if self.pool_key.as_ref() != Some(&key) || !self.is_connected() {
+            let host = self.url.authority();
             self.header.set_host(host)?;
-            self.re_conn().await?;
+            self.release();
+            if let Some(h2) = self.pool.as_ref().map(|x| x.take_h2(&key)).transpose()?.flatten() {
+                self.reuse_h2(key, h2);
+                return Ok(());
+            }
+            match self.pool.as_ref().map(|x| x.take(&key)).transpose()?.flatten() {
+                None => self.re_conn().await?,
+                Some(conn) => self.reuse(key, conn),
+            }
         }
         Ok(())
     }
 
+    fn pool_key(&self) -> HlsResult<PoolKey> {
+        #[cfg(use_cls)]
+        let fingerprint = format!("{}-{}-{}", self.fingerprint.ja3()?, self.fingerprint.verify(), self.fingerprint.root_store().id());
+        #[cfg(not(use_cls))]
+        let fingerprint = String::new();
+        //h2指纹不同的连接不能共享
+        let fingerprint = format!("{}-{}", fingerprint, self.h2_fingerprint.akamai());
+        Ok(PoolKey::new(&self.url, &self.proxy, &self.alpn, fingerprint, false))
+    }
+
+    fn is_connected(&self) -> bool {
+        match &self.h2 {
+            None => self.stream.is_connected(),
+            Some(h2) => !h2.is_closed(),
+        }
+    }
+
+    fn reuse(&mut self, key: PoolKey, conn: PoolConn) {
+        self.stream = conn.stream;
+        self.pool_key = Some(key);
+        self.reused = true;
+        self.keep_alive = true;
+        self.header.init_by_alpn(self.stream.alpn());
+    }
+
+    fn reuse_h2(&mut self, key: PoolKey, h2: H2Conn) {
+        self.h2 = Some(h2);
+        self.pool_key = Some(key);
+        self.reused = true;
+        self.keep_alive = true;
+        self.header.init_by_alpn(&ALPN::Http20);
+    }
+
+    ///keep-alive的连接放回连接池，h2连接已在连接池中共享
+    fn release(&mut self) {
+        self.h2 = None;
+        let stream = mem::replace(&mut self.stream, Stream::unconnection());
+        if let (Some(pool), Some(key)) = (&self.pool, self.pool_key.take()) && self.keep_alive && stream.is_connected() {
+            let conn = PoolConn {
+                stream,
+                #[cfg(sync)]
+                h2: None,
+            };
+            let _ = pool.put(key, conn);
+        }
+    }
+
     pub async fn send_check(&mut self, method: Method) -> HlsResult<Response> {
         self.header.set_method(method);
         let response = self.stream_io().await?;
@@ -210,43 +359,204 @@ impl AcReq {
         let response = self.send_check(method).await?;
         self.check_res(response, k, v, e)
     }
+
+    ///发送请求，读取到响应头后返回，响应体通过AcResStream按需读取
+    /// * 总超时到读取到响应头为止，之后只受单次读取的超时限制
+    pub async fn send_stream(&mut self, method: Method) -> HlsResult<AcResStream<'_>> {
+        self.header.set_method(method);
+        let (response, body) = self.stream_head().await?;
+        AcResStream::new(self, response, body)
+    }
+
+    ///#### 打开server-sent events，读取到响应头后返回AcSse
+    /// * 使用当前的请求头、请求体、cookie jar、代理和tls指纹，请求头的Accept设置为text/event-stream，结束后恢复原来的请求头
+    /// * 状态码不是200或content-type不是text/event-stream时返回错误，204时没有事件
+    pub async fn event_source(&mut self, method: Method) -> HlsResult<AcSse<'_>> {
+        self.header.set_method(method);
+        let saved = sse::save_headers(&self.header);
+        let mut res = AcSse { req: self, body: None, parser: EventParser::new(), closed: false, saved };
+        res.connect().await?;
+        Ok(res)
+    }
+
+    async fn stream_head(&mut self) -> HlsResult<(Response, AcResBody)> {
+        let deadline = self.timeout.start();
+        let res = tokio::time::timeout_at(deadline.into(), self.redirect_stream_head()).await;
+        self.timeout.stop();
+        let (response, decoder, h2) = res.unwrap_or_else(|_| Err(self.deadline_exceeded()))?;
+        Ok((response, AcResBody::new(decoder, h2)))
+    }
+
+    async fn redirect_stream_head(&mut self) -> HlsResult<(Response, BodyDecoder, Option<H2Stream>)> {
+        self.redirects.clear();
+        loop {
+            let (mut response, decoder, h2) = self.retry_stream_head().await?;
+            match self.next_redirect(response.header())? {
+                None => {
+                    response.set_history(mem::take(&mut self.redirects));
+                    return Ok((response, decoder, h2));
+                }
+                Some(redirect) => {
+                    //不读取重定向的响应体，http/1.1的连接不再复用
+                    drop(AcResStream::new(self, response, AcResBody::new(decoder, h2))?);
+                    self.follow_redirect(redirect).await?;
+                }
+            }
+        }
+    }
+
+    async fn retry_stream_head(&mut self) -> HlsResult<(Response, BodyDecoder, Option<H2Stream>)> {
+        //上一个响应要求关闭连接
+        if !self.keep_alive { self.re_conn().await?; }
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let res = self.handle_stream_head().await;
+            if res.is_ok() { self.reused = false; }
+            let retry = match res {
+                Ok((response, decoder, h2)) => match self.next_retry(attempt, Outcome::Response(response.header())) {
+                    None => return Ok((response, decoder, h2)),
+                    Some(retry) => {
+                        //不读取需要重试的响应体，http/1.1的连接不再复用
+                        drop(AcResStream::new(self, response, AcResBody::new(decoder, h2))?);
+                        retry
+                    }
+                },
+                Err(e) => match self.next_retry(attempt, Outcome::Error(&e)) {
+                    None => return Err(e),
+                    Some(retry) => retry,
+                },
+            };
+            tokio::time::sleep(retry.delay).await;
+            if retry.reconnect || self.reused || !self.keep_alive { self.re_conn().await?; }
+        }
+    }
+
+    ///#### 升级为websocket，url必须是ws://或wss://
+    /// * 握手使用当前的请求头(cookie、origin、user-agent等)、cookie jar、代理和tls指纹，不跟随重定向
+    /// * 连接交给返回的AcWs，之后的请求会重新连接
+    pub async fn websocket(&mut self, config: WsConfig) -> HlsResult<AcWs> {
+        if !matches!(self.url.protocol(), Protocol::Ws | Protocol::Wss) { return Err("websocket url must be ws:// or wss://".into()); }
+        if !self.keep_alive || self.h2.is_some() || !self.stream.is_connected() || self.stream.alpn() != &ALPN::Http11 { self.re_conn().await?; }
+        let deadline = self.timeout.start();
+        let res = tokio::time::timeout_at(deadline.into(), self.ws_handshake(&config)).await;
+        self.timeout.stop();
+        //握手失败时连接的状态未知，不再复用
+        let stream = mem::replace(&mut self.stream, Stream::unconnection());
+        self.pool_key = None;
+        self.keep_alive = false;
+        let (header, protocol, codec) = res.unwrap_or(Err(HlsError::Timeout(TimeoutPhase::Handle)))?;
+        Ok(AcWs::new(stream, codec, header, protocol))
+    }
+
+    async fn ws_handshake(&mut self, config: &WsConfig) -> HlsResult<(Header, Option<String>, WsCodec)> {
+        let key = ws::client_key();
+        let manual = self.merge_jar_cookies();
+        let request = ws::handshake_request(&self.url, &self.header, &key, config);
+        self.header.set_cookies(manual);
+        self.stream.async_write(&request).await?;
+        let mut decoder = BodyDecoder::new_h1(&Method::GET);
+        let mut response = self.h1_read_header(&mut decoder).await?;
+        self.update_cookie(&response);
+        let (protocol, deflate) = ws::handshake_response(response.header(), &key, config)?;
+        let codec = WsCodec::new(config, deflate, decoder.take_upgraded());
+        Ok((mem::replace(response.header_mut(), Header::new_res()), protocol, codec))
+    }
+
+    async fn h1_read_header(&mut self, decoder: &mut BodyDecoder) -> HlsResult<Response> {
+        let mut response = Response::new();
+        let mut buffer = Buffer::with_capacity(16413);
+        loop {
+            buffer.reset();
+            self.stream.async_read(&mut buffer).await?;
+            let data = decoder.decode(buffer.filled())?;
+            if let Some(header) = decoder.take_header() {
+                *response.header_mut() = header;
+                //和响应头一起读到的响应体，已经解码
+                response.push_raw(data);
+                return Ok(response);
+            }
+        }
+    }
+
+    ///发送请求并读取响应头
+    async fn handle_stream_head(&mut self) -> HlsResult<(Response, BodyDecoder, Option<H2Stream>)> {
+        let (response, decoder, h2) = match self.h2 {
+            Some(_) => {
+                let headers = self.gen_h2_header()?;
+                let body = self.gen_h2_body()?;
+                let mut stream = self.h2c_open(headers, body).await?;
+                let mut response = Response::new();
+                let mut ended = false;
+                while response.header().is_empty() {
+                    let event = stream.recv(self.timeout.read()).await?;
+                    ended = self.handle_h2_event(event, &mut response, &mut None)?;
+                    if ended && response.header().is_empty() { return Err("h2 stream ended without headers".into()); }
+                }
+                let mut decoder = BodyDecoder::new_h2(response.header())?;
+                //没有响应体
+                if ended { decoder.finish()?; }
+                (response, decoder, Some(stream))
+            }
+            None => {
+                match self.gen_h1()? {
+                    (context, None) => self.stream.async_write(&context).await?,
+                    (context, Some(body)) => {
+                        self.stream.async_write(&context).await?;
+                        self.h1_write_body(body).await?;
+                    }
+                }
+                let mut decoder = BodyDecoder::new_h1(self.header.method());
+                let response = self.h1_read_header(&mut decoder).await?;
+                (response, decoder, None)
+            }
+        };
+        self.update_cookie(&response);
+        if !response.header().keep_alive() { self.keep_alive = false; }
+        Ok((response, decoder, h2))
+    }
 }
 
 impl AcReq {
+    ///启动h2连接的后台任务，有连接池时共享给其他AcReq
     pub async fn handle_h2_setting(&mut self) -> HlsResult<()> {
-        let mut handshake = "PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".as_bytes().to_vec();
-        let setting_frame = Frame::default_setting();
-        handshake.extend(setting_frame.to_bytes());
-        let update_frame = Frame::window_update();
-        handshake.extend(update_frame.to_bytes());
-        self.stream.async_write(&handshake).await?;
-        self.stream_id += 1;
+        let stream = mem::replace(&mut self.stream, Stream::unconnection());
+        let h2 = H2Conn::handshake(stream, self.h2_fingerprint.clone()).await?;
+        if let (Some(pool), Some(key)) = (&self.pool, &self.pool_key) { pool.put_h2(key.clone(), h2.clone())?; }
+        self.h2 = Some(h2);
         Ok(())
     }
 
     pub async fn h2c_io(&mut self, headers: Vec<HeaderKey>, body: Vec<u8>) -> HlsResult<Response> {
-        let hdr_bs = self.hack_coder.encode(headers)?;
-        let mut header_frame = Frame::new_header(hdr_bs, body.len(), self.stream_id);
-        header_frame.set_weight(146);
-        header_frame.add_flag(FrameFlag::Priority);
-        self.stream.async_write(header_frame.to_bytes().as_slice()).await?;
-        for body_frame in Frame::new_body(body, self.stream_id) {
-            self.stream.async_write(body_frame.to_bytes().as_slice()).await?;
+        let mut reader = BodyReader::new();
+        reader.push_bytes(body);
+        self.h2c_body_io(headers, reader).await
+    }
+
+    ///发送请求头和请求体，对端提前结束stream(如提前返回错误响应)时不再发送剩余的请求体
+    async fn h2c_open(&mut self, headers: Vec<HeaderKey>, mut body: BodyReader) -> HlsResult<H2Stream> {
+        let h2 = self.h2.as_ref().ok_or("h2 connection none")?;
+        match body.take_bytes() {
+            Some(bytes) => h2.request(headers, bytes, true).await,
+            None => {
+                let mut stream = h2.request(headers, vec![], false).await?;
+                let mut chunk = vec![0; BODY_CHUNK];
+                loop {
+                    let len = body.async_read(&mut chunk).await?;
+                    if !stream.send_data(chunk[..len].to_vec(), len == 0).await? || len == 0 { break; }
+                }
+                Ok(stream)
+            }
         }
+    }
+
+    async fn h2c_body_io(&mut self, headers: Vec<HeaderKey>, body: BodyReader) -> HlsResult<Response> {
+        let mut stream = self.h2c_open(headers, body).await?;
         let mut response = Response::new();
-        let mut buffer = Buffer::with_capacity(0xFFFF);
+        let mut decoder = None;
         loop {
-            self.stream.async_read(&mut buffer).await?;
-            while let Ok(frame) = Frame::from_bytes(&mut buffer) {
-                if frame.frame_type() == &FrameType::Settings && frame.flags().contains(&FrameFlag::ACK) {
-                    let mut end_frame = Frame::none_frame();
-                    end_frame.set_frame_type(FrameType::Settings);
-                    end_frame.set_flags(vec![FrameFlag::EndStream]);
-                    self.stream.async_write(end_frame.to_bytes().as_ref()).await?;
-                    continue;
-                }
-                if self.handle_h2_res(frame, &mut response)? { return Ok(response); };
-            }
+            let event = stream.recv(self.timeout.read()).await?;
+            if self.handle_h2_event(event, &mut response, &mut decoder)? { return Ok(response); }
         }
     }
 }
@@ -258,8 +568,24 @@ impl ReqPriExt for AcReq {
         &mut self.callback
     }
 
-    fn hack_decoder(&mut self) -> &mut HackDecode {
-        self.hack_coder.decoder()
+    fn h2_fingerprint(&self) -> &H2Fingerprint {
+        &self.h2_fingerprint
+    }
+
+    fn redirect_policy(&self) -> &RedirectPolicy {
+        &self.redirect
+    }
+
+    fn redirects(&self) -> &[Redirect] {
+        &self.redirects
+    }
+
+    fn redirects_mut(&mut self) -> &mut Vec<Redirect> {
+        &mut self.redirects
+    }
+
+    fn retry_policy(&self) -> &dyn RetryPolicy {
+        self.retry.as_ref()
     }
 }
 
@@ -297,9 +623,19 @@ impl ReqExt for AcReq {
     }
 
     fn set_proxy(&mut self, proxy: Proxy) {
+        self.system_proxy = None;
         self.proxy = proxy;
     }
 
+    fn set_system_proxy(&mut self, proxy: Option<SystemProxy>) {
+        self.proxy = proxy.as_ref().map(|x| x.select(&self.url)).unwrap_or(Proxy::Null);
+        self.system_proxy = proxy;
+    }
+
+    fn proxy(&self) -> &Proxy {
+        &self.proxy
+    }
+
     fn set_alpn(&mut self, alpn: ALPN) {
         self.alpn = alpn;
     }
@@ -312,8 +648,252 @@ impl ReqExt for AcReq {
     fn set_fingerprint(&mut self, fingerprint: Fingerprint) {
         self.fingerprint = fingerprint;
     }
+
+    fn set_h2_fingerprint(&mut self, fingerprint: H2Fingerprint) {
+        self.h2_fingerprint = fingerprint;
+    }
+
+    fn set_redirect(&mut self, policy: RedirectPolicy) {
+        self.redirect = policy;
+    }
+
+    fn set_retry(&mut self, policy: impl RetryPolicy + 'static) {
+        self.retry = Box::new(policy);
+    }
+
+    fn set_pool(&mut self, pool: Pool) {
+        self.pool = Some(pool);
+    }
+
+    fn set_resolver(&mut self, resolver: impl Resolver + 'static) {
+        self.resolver = Box::new(resolver);
+    }
+
+    fn set_cookie_jar(&mut self, jar: CookieJar) {
+        self.cookie_jar = jar;
+    }
+
+    fn cookie_jar(&self) -> &CookieJar {
+        &self.cookie_jar
+    }
+}
+
+impl Drop for AcReq {
+    fn drop(&mut self) {
+        self.release();
+    }
 }
 
 unsafe impl Send for AcReq {}
 
-unsafe impl Sync for AcReq {}
\ No newline at end of file
+unsafe impl Sync for AcReq {}
+
+type ResChunk<'a> = Pin<Box<dyn Future<Output=(AcResState<'a>, HlsResult<Option<Vec<u8>>>)> + 'a>>;
+
+struct AcResState<'a> {
+    req: &'a mut AcReq,
+    body: AcResBody,
+}
+
+impl AcResState<'_> {
+    //读取时转移所有权，AsyncRead可以跨poll保存读取中的future
+    async fn next_chunk(mut self) -> (Self, HlsResult<Option<Vec<u8>>>) {
+        let res = self.body.read_chunk(self.req).await;
+        (self, res)
+    }
+}
+
+impl Drop for AcResState<'_> {
+    fn drop(&mut self) {
+        self.body.abort(self.req);
+    }
+}
+
+///从连接按需读取响应体的状态
+struct AcResBody {
+    decoder: BodyDecoder,
+    buffer: Buffer,
+    h2: Option<H2Stream>,
+}
+
+impl AcResBody {
+    fn new(decoder: BodyDecoder, h2: Option<H2Stream>) -> AcResBody {
+        AcResBody { decoder, buffer: Buffer::with_capacity(16413), h2 }
+    }
+
+    async fn read_chunk(&mut self, req: &mut AcReq) -> HlsResult<Option<Vec<u8>>> {
+        while !self.decoder.is_ended() {
+            let data = match &mut self.h2 {
+                None => {
+                    self.buffer.reset();
+                    match req.stream.async_read(&mut self.buffer).await {
+                        Ok(_) => self.decoder.decode(self.buffer.filled())?,
+                        //没有content-length的响应体读到连接关闭
+                        Err(HlsError::PeerClosedConnection) => {
+                            req.keep_alive = false;
+                            self.decoder.finish()?
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Some(stream) => match stream.recv(req.timeout.read()).await? {
+                    H2Event::Data(_, data, ended) => {
+                        let mut data = self.decoder.decode(&data)?;
+                        if ended { data.extend(self.decoder.finish()?); }
+                        data
+                    }
+                    H2Event::Headers(_, _, true) => self.decoder.finish()?,
+                    H2Event::Reset(_, code) => return Err(HlsError::StreamReset(code)),
+                    _ => vec![],
+                }
+            };
+            if !data.is_empty() { return Ok(Some(data)); }
+        }
+        Ok(None)
+    }
+
+    ///响应体没有读完时http/1.1的连接不再复用，h2的stream在H2Stream drop时取消
+    fn abort(&self, req: &mut AcReq) {
+        if !self.decoder.is_ended() && self.h2.is_none() { req.keep_alive = false; }
+    }
+}
+
+///#### 流式响应体
+/// * 由`AcReq::send_stream`返回，响应体按需从连接读取，去掉chunked并解压后输出，内存占用不随响应体大小增长
+/// * 通过`next_chunk`或AsyncRead读取
+/// * 响应体读完前drop时，http/1.1的连接不再复用，h2的stream被取消
+pub struct AcResStream<'a> {
+    response: Response,
+    //读取中时被future持有
+    state: Option<AcResState<'a>>,
+    reading: Option<ResChunk<'a>>,
+    //已解码但还没有被AsyncRead读取的数据
+    data: Cursor<Vec<u8>>,
+}
+
+impl<'a> AcResStream<'a> {
+    fn new(req: &'a mut AcReq, mut response: Response, body: AcResBody) -> HlsResult<AcResStream<'a>> {
+        //和响应头一起读到并已解码的响应体
+        let data = response.raw_body().to_vec();
+        response.clear_raw();
+        let state = AcResState { req, body };
+        Ok(AcResStream { response, state: Some(state), reading: None, data: Cursor::new(data) })
+    }
+
+    pub fn header(&self) -> &Header { self.response.header() }
+
+    ///返回下一段解码后的响应体，None时响应体已读完
+    pub async fn next_chunk(&mut self) -> HlsResult<Option<Vec<u8>>> {
+        //先返回AsyncRead没有读完的数据
+        let pos = self.data.position() as usize;
+        if pos < self.data.get_ref().len() {
+            let data = mem::take(self.data.get_mut()).split_off(pos);
+            self.data.set_position(0);
+            return Ok(Some(data));
+        }
+        poll_fn(|cx| self.poll_chunk(cx)).await
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<HlsResult<Option<Vec<u8>>>> {
+        let reading = match &mut self.reading {
+            Some(reading) => reading,
+            None => match self.state.take() {
+                None => return Poll::Ready(Err("response stream error".into())),
+                Some(state) => self.reading.insert(Box::pin(state.next_chunk())),
+            }
+        };
+        let (state, res) = ready!(reading.as_mut().poll(cx));
+        self.reading = None;
+        self.state = Some(state);
+        Poll::Ready(res)
+    }
+}
+
+impl AsyncRead for AcResStream<'_> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.data.position() >= this.data.get_ref().len() as u64 {
+            match ready!(this.poll_chunk(cx))? {
+                None => return Poll::Ready(Ok(())),
+                Some(data) => this.data = Cursor::new(data),
+            }
+        }
+        let pos = this.data.position() as usize;
+        let len = buf.remaining().min(this.data.get_ref().len() - pos);
+        buf.put_slice(&this.data.get_ref()[pos..pos + len]);
+        this.data.set_position((pos + len) as u64);
+        Poll::Ready(Ok(()))
+    }
+}
+
+///#### server-sent events
+/// * 由`AcReq::event_source`返回，通过`next_event`读取事件
+/// * 连接断开或读取失败时，等待retry(默认3秒)后带上Last-Event-ID重新请求，重新请求失败时返回错误，之后继续读取会再次重连
+/// * 重连的响应状态码不是200或content-type不是text/event-stream时不再重连，204时结束
+pub struct AcSse<'a> {
+    req: &'a mut AcReq,
+    body: Option<AcResBody>,
+    parser: EventParser,
+    closed: bool,
+    //打开前的Accept/Cache-Control/Last-Event-ID，结束时恢复
+    saved: sse::SavedHeaders,
+}
+
+impl AcSse<'_> {
+    ///返回下一个事件，None时服务端已经结束
+    pub async fn next_event(&mut self) -> HlsResult<Option<Event>> {
+        loop {
+            if let Some(event) = self.parser.next() { return Ok(Some(event)); }
+            if self.closed { return Ok(None); }
+            let Some(body) = &mut self.body else {
+                tokio::time::sleep(self.parser.reconnect_time().unwrap_or(sse::RECONNECT_TIME)).await;
+                self.connect().await?;
+                continue;
+            };
+            match body.read_chunk(self.req).await {
+                Ok(Some(data)) => self.parser.feed(&data),
+                //连接断开，重连
+                Ok(None) | Err(_) => self.disconnect(),
+            }
+        }
+    }
+
+    ///重连时发送的Last-Event-ID
+    pub fn last_event_id(&self) -> &str {
+        self.parser.last_event_id()
+    }
+
+    async fn connect(&mut self) -> HlsResult<()> {
+        let header = &mut self.req.header;
+        header.set_accept("text/event-stream")?;
+        header.insert("Cache-Control", "no-cache")?;
+        match self.parser.last_event_id() {
+            "" => { header.remove("Last-Event-ID"); }
+            id => header.insert("Last-Event-ID", id)?,
+        }
+        let (mut response, body) = self.req.stream_head().await?;
+        let res = sse::check_response(&response);
+        if res.is_err() || response.header().status().status_num() == 204 {
+            self.closed = true;
+            body.abort(self.req);
+            return res;
+        }
+        //和响应头一起读到的响应体
+        self.parser.feed(response.raw_body());
+        response.clear_raw();
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(body) = self.body.take() { body.abort(self.req); }
+        self.parser.reset();
+    }
+}
+
+impl Drop for AcSse<'_> {
+    fn drop(&mut self) {
+        self.disconnect();
+        sse::restore_headers(&mut self.req.header, mem::take(&mut self.saved));
+    }
+}
@@ -1,5 +1,14 @@
 use json::JsonValue;
+use crate::error::HlsResult;
 use crate::file::HttpFile;
+use std::collections::VecDeque;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+#[cfg(aync)]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+//流式请求体每次读取的大小
+pub(crate) const BODY_CHUNK: usize = 16384;
 
 pub enum BodyType {
     Text(String),
@@ -7,6 +16,8 @@ pub enum BodyType {
     Files((JsonValue, Vec<HttpFile>)),
     WwwForm(JsonValue),
     Json(JsonValue),
+    ///流式请求体，读取后不能重发
+    Stream(Option<BodyReader>),
 }
 
 impl Drop for BodyType {
@@ -27,6 +38,171 @@ impl Drop for BodyType {
             }
             BodyType::WwwForm(v) => v.clear(),
             BodyType::Json(v) => v.clear(),
+            BodyType::Stream(v) => *v = None,
+        }
+    }
+}
+
+enum BodyPart {
+    Bytes(Cursor<Vec<u8>>),
+    //发送时才打开文件
+    File(PathBuf),
+    Reader(Box<dyn Read + Send>),
+    #[cfg(aync)]
+    AsyncReader(Box<dyn AsyncRead + Send + Unpin>),
+}
+
+///#### 请求体
+/// * 由内存数据、文件、Read/AsyncRead按顺序组成，发送时分块读取
+/// * len为None时长度未知，http/1.1使用chunked发送
+pub struct BodyReader {
+    parts: VecDeque<BodyPart>,
+    len: Option<u64>,
+    //已读取的长度，用于校验len
+    read: u64,
+}
+
+impl BodyReader {
+    pub(crate) fn new() -> BodyReader {
+        BodyReader { parts: VecDeque::new(), len: Some(0), read: 0 }
+    }
+
+    pub(crate) fn from_reader(reader: impl Read + Send + 'static, len: Option<u64>) -> BodyReader {
+        let mut res = BodyReader::new();
+        res.parts.push_back(BodyPart::Reader(Box::new(reader)));
+        res.len = len;
+        res
+    }
+
+    #[cfg(aync)]
+    pub(crate) fn from_async_reader(reader: impl AsyncRead + Send + Unpin + 'static, len: Option<u64>) -> BodyReader {
+        let mut res = BodyReader::new();
+        res.parts.push_back(BodyPart::AsyncReader(Box::new(reader)));
+        res.len = len;
+        res
+    }
+
+    pub(crate) fn push_bytes(&mut self, bytes: Vec<u8>) {
+        if bytes.is_empty() { return; }
+        self.len = self.len.map(|x| x + bytes.len() as u64);
+        match self.parts.back_mut() {
+            Some(BodyPart::Bytes(cursor)) => cursor.get_mut().extend(bytes),
+            _ => self.parts.push_back(BodyPart::Bytes(Cursor::new(bytes))),
+        }
+    }
+
+    pub(crate) fn push_file(&mut self, path: PathBuf, size: u64) {
+        self.len = self.len.map(|x| x + size);
+        self.parts.push_back(BodyPart::File(path));
+    }
+
+    pub(crate) fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    ///请求体全部在内存中时直接取出，不需要分块发送
+    pub(crate) fn take_bytes(&mut self) -> Option<Vec<u8>> {
+        match self.parts.len() {
+            0 => Some(vec![]),
+            1 => match self.parts.front() {
+                Some(BodyPart::Bytes(cursor)) if cursor.position() == 0 => match self.parts.pop_front() {
+                    Some(BodyPart::Bytes(cursor)) => Some(cursor.into_inner()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    ///读取结束(返回0)时校验长度
+    fn check_len(&mut self, len: usize) -> HlsResult<usize> {
+        self.read += len as u64;
+        match self.len {
+            Some(total) if self.read > total || (len == 0 && self.read != total) => {
+                Err(format!("request body length mismatch: expected {}, read {}", total, self.read).into())
+            }
+            _ => Ok(len)
         }
     }
-}
\ No newline at end of file
+
+    ///返回0时请求体已读完
+    #[cfg(sync)]
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> HlsResult<usize> {
+        while let Some(part) = self.parts.front_mut() {
+            let len = match part {
+                BodyPart::Bytes(cursor) => Read::read(cursor, buf)?,
+                BodyPart::File(path) => {
+                    *part = BodyPart::Reader(Box::new(std::fs::File::open(&path)?));
+                    continue;
+                }
+                BodyPart::Reader(reader) => Read::read(reader, buf)?,
+                #[cfg(aync)]
+                BodyPart::AsyncReader(_) => return Err("async request body can not be sent by ScReq".into()),
+            };
+            if len == 0 {
+                self.parts.pop_front();
+                continue;
+            }
+            return self.check_len(len);
+        }
+        self.check_len(0)
+    }
+
+    ///返回0时请求体已读完，Read会阻塞当前线程
+    #[cfg(aync)]
+    pub(crate) async fn async_read(&mut self, buf: &mut [u8]) -> HlsResult<usize> {
+        while let Some(part) = self.parts.front_mut() {
+            let len = match part {
+                BodyPart::Bytes(cursor) => Read::read(cursor, buf)?,
+                BodyPart::File(path) => {
+                    *part = BodyPart::AsyncReader(Box::new(tokio::fs::File::open(&path).await?));
+                    continue;
+                }
+                BodyPart::Reader(reader) => Read::read(reader, buf)?,
+                BodyPart::AsyncReader(reader) => reader.read(buf).await?,
+            };
+            if len == 0 {
+                self.parts.pop_front();
+                continue;
+            }
+            return self.check_len(len);
+        }
+        self.check_len(0)
+    }
+}
+
+#[cfg(all(test, sync))]
+mod tests {
+    use super::BodyReader;
+    use std::io::Cursor;
+
+    fn read_all(reader: &mut BodyReader) -> Result<Vec<u8>, String> {
+        let mut res = vec![];
+        let mut chunk = [0; 7];
+        loop {
+            let len = reader.read(&mut chunk).map_err(|e| e.to_string())?;
+            if len == 0 { return Ok(res); }
+            res.extend_from_slice(&chunk[..len]);
+        }
+    }
+
+    #[test]
+    fn test_body_reader() {
+        let path = std::env::temp_dir().join("reqrio_body_reader.txt");
+        std::fs::write(&path, b"file content").unwrap();
+        let mut reader = BodyReader::new();
+        reader.push_bytes(b"--head\r\n".to_vec());
+        reader.push_file(path.clone(), 12);
+        reader.push_bytes(b"\r\n--end".to_vec());
+        assert_eq!(reader.len(), Some(27));
+        assert!(reader.take_bytes().is_none());
+        assert_eq!(read_all(&mut reader).unwrap(), b"--head\r\nfile content\r\n--end");
+        std::fs::remove_file(path).unwrap();
+        //长度和声明的不一致
+        let mut reader = BodyReader::from_reader(Cursor::new(vec![1; 20]), Some(10));
+        assert!(read_all(&mut reader).unwrap_err().contains("length mismatch"));
+        let mut reader = BodyReader::from_reader(Cursor::new(vec![1; 20]), None);
+        assert_eq!(read_all(&mut reader).unwrap().len(), 20);
+    }
+}
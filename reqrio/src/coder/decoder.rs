@@ -0,0 +1,233 @@
+use crate::error::{HlsError, HlsResult};
+use flate2::write::{DeflateDecoder, GzDecoder};
+use std::io::Write;
+use std::mem;
+
+enum DecoderKind {
+    Identity,
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(DeflateDecoder<Vec<u8>>),
+    Br(Box<brotli::DecompressorWriter<Vec<u8>>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl DecoderKind {
+    fn new(encoding: &str) -> HlsResult<DecoderKind> {
+        Ok(match encoding {
+            "gzip" | "x-gzip" => DecoderKind::Gzip(GzDecoder::new(vec![])),
+            "deflate" => DecoderKind::Deflate(DeflateDecoder::new(vec![])),
+            "br" => DecoderKind::Br(Box::new(brotli::DecompressorWriter::new(vec![], 4096))),
+            "zstd" => DecoderKind::Zstd(zstd::stream::write::Decoder::new(vec![])?),
+            _ => DecoderKind::Identity,
+        })
+    }
+
+    fn decode(&mut self, data: &[u8]) -> HlsResult<Vec<u8>> {
+        let out = match self {
+            DecoderKind::Identity => return Ok(data.to_vec()),
+            DecoderKind::Gzip(d) => {
+                d.write_all(data)?;
+                d.flush()?;
+                d.get_mut()
+            }
+            DecoderKind::Deflate(d) => {
+                d.write_all(data)?;
+                d.flush()?;
+                d.get_mut()
+            }
+            DecoderKind::Br(d) => {
+                d.write_all(data)?;
+                d.flush()?;
+                d.get_mut()
+            }
+            DecoderKind::Zstd(d) => {
+                d.write_all(data)?;
+                d.flush()?;
+                d.get_mut()
+            }
+        };
+        Ok(mem::take(out))
+    }
+
+    fn finish(&mut self) -> HlsResult<Vec<u8>> {
+        let out = match self {
+            DecoderKind::Identity => return Ok(vec![]),
+            DecoderKind::Gzip(d) => {
+                d.try_finish()?;
+                d.get_mut()
+            }
+            DecoderKind::Deflate(d) => {
+                d.try_finish()?;
+                d.get_mut()
+            }
+            DecoderKind::Br(d) => {
+                d.close()?;
+                d.get_mut()
+            }
+            DecoderKind::Zstd(d) => {
+                d.flush()?;
+                d.get_mut()
+            }
+        };
+        Ok(mem::take(out))
+    }
+}
+
+///#### 增量解压
+/// * 每次写入收到的数据，返回已经可以解压出来的部分，不需要等待完整的响应体
+/// * 支持多层编码，例如`Content-Encoding: gzip, br`，按相反的顺序解压
+/// * 不支持的编码原样返回
+pub struct StreamDecoder {
+    //按解压顺序排列
+    kinds: Vec<DecoderKind>,
+    //没有写入过数据时finish直接返回空
+    written: bool,
+}
+
+impl StreamDecoder {
+    pub fn new(encoding: impl AsRef<str>) -> HlsResult<StreamDecoder> {
+        let kinds = encoding.as_ref().split(',').rev().map(|x| x.trim().to_lowercase())
+            .filter(|x| !x.is_empty() && x != "identity").map(|x| DecoderKind::new(&x)).collect::<HlsResult<_>>()?;
+        Ok(StreamDecoder { kinds, written: false })
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> HlsResult<Vec<u8>> {
+        if data.is_empty() { return Ok(vec![]); }
+        self.written = true;
+        let mut data = data.to_vec();
+        for kind in &mut self.kinds {
+            if data.is_empty() { break; }
+            data = kind.decode(&data).map_err(|e| HlsError::Decode(Box::new(e)))?;
+        }
+        Ok(data)
+    }
+
+    ///数据已全部写入，返回剩余的数据，压缩数据不完整时返回错误
+    pub fn finish(&mut self) -> HlsResult<Vec<u8>> {
+        if !self.written { return Ok(vec![]); }
+        let mut data = vec![];
+        for kind in &mut self.kinds {
+            //上一层剩余的数据写入后再结束这一层
+            let mut out = kind.decode(&data).map_err(|e| HlsError::Decode(Box::new(e)))?;
+            out.extend(kind.finish().map_err(|e| HlsError::Decode(Box::new(e)))?);
+            data = out;
+        }
+        Ok(data)
+    }
+}
+
+enum ChunkState {
+    //chunk大小所在的行
+    Size,
+    Data(usize),
+    //chunk数据后的\r\n
+    DataEnd,
+    Trailer,
+    Done,
+}
+
+///#### 增量解析chunked
+/// * 支持chunk扩展(`;`之后的部分)和trailer，数据可以在任意位置被截断
+pub struct ChunkDecoder {
+    state: ChunkState,
+    line: Vec<u8>,
+}
+
+impl Default for ChunkDecoder {
+    fn default() -> Self {
+        ChunkDecoder::new()
+    }
+}
+
+impl ChunkDecoder {
+    pub fn new() -> ChunkDecoder {
+        ChunkDecoder { state: ChunkState::Size, line: vec![] }
+    }
+
+    ///最后一个chunk和trailer已读完
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+
+    ///返回去掉chunked分块后的数据，结束之后的数据被忽略
+    pub fn decode(&mut self, mut data: &[u8]) -> HlsResult<Vec<u8>> {
+        let mut res = vec![];
+        while !data.is_empty() {
+            match self.state {
+                ChunkState::Size | ChunkState::DataEnd | ChunkState::Trailer => {
+                    let pos = data.iter().position(|x| *x == b'\n');
+                    self.line.extend_from_slice(&data[..pos.map(|x| x + 1).unwrap_or(data.len())]);
+                    let Some(pos) = pos else { break; };
+                    data = &data[pos + 1..];
+                    let line = mem::take(&mut self.line);
+                    let line = line.strip_suffix(b"\r\n").or(line.strip_suffix(b"\n")).unwrap_or(&line);
+                    self.state = match self.state {
+                        ChunkState::Size => {
+                            let size = String::from_utf8_lossy(line);
+                            let size = size.split(';').next().unwrap_or("").trim();
+                            match usize::from_str_radix(size, 16).or(Err(HlsError::Protocol(format!("chunk size error: {}", size))))? {
+                                0 => ChunkState::Trailer,
+                                len => ChunkState::Data(len),
+                            }
+                        }
+                        ChunkState::DataEnd if line.is_empty() => ChunkState::Size,
+                        ChunkState::DataEnd => return Err(HlsError::Protocol("chunk data end error".to_string())),
+                        ChunkState::Trailer if line.is_empty() => ChunkState::Done,
+                        _ => ChunkState::Trailer,
+                    };
+                }
+                ChunkState::Data(len) => {
+                    let len = len.min(data.len());
+                    res.extend_from_slice(&data[..len]);
+                    data = &data[len..];
+                    self.state = match self.state {
+                        ChunkState::Data(remain) if remain > len => ChunkState::Data(remain - len),
+                        _ => ChunkState::DataEnd,
+                    };
+                }
+                ChunkState::Done => break,
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChunkDecoder, StreamDecoder};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn test_stream_decode() {
+        let raw = b"4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: now\r\n\r\n";
+        let mut chunk = ChunkDecoder::new();
+        let mut res = vec![];
+        //逐字节输入
+        for b in raw { res.extend(chunk.decode(&[*b]).unwrap()); }
+        assert!(chunk.is_done());
+        assert_eq!(res, b"Wikipedia");
+
+        let text = "reqrio stream decoder ".repeat(1000);
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let mut decoder = StreamDecoder::new("gzip").unwrap();
+        let mut res = vec![];
+        for data in gzip.chunks(100) { res.extend(decoder.decode(data).unwrap()); }
+        res.extend(decoder.finish().unwrap());
+        assert_eq!(res, text.as_bytes());
+        let mut decoder = StreamDecoder::new("gzip").unwrap();
+        decoder.decode(&gzip[..gzip.len() / 2]).unwrap();
+        assert!(decoder.finish().is_err());
+        //先gzip再br
+        let mut br = vec![];
+        brotli::BrotliCompress(&mut gzip.as_slice(), &mut br, &Default::default()).unwrap();
+        let mut decoder = StreamDecoder::new("gzip, br").unwrap();
+        let mut res = vec![];
+        for data in br.chunks(7) { res.extend(decoder.decode(data).unwrap()); }
+        res.extend(decoder.finish().unwrap());
+        assert_eq!(res, text.as_bytes());
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+///IMF-fixdate，例如`Sun, 06 Nov 1994 08:49:37 GMT`
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let (_, value) = value.split_once(", ")?;
+    let parts = value.split(' ').collect::<Vec<_>>();
+    let [day, month, year, time, "GMT"] = parts[..] else { return None; };
+    let month = MONTHS.iter().position(|x| *x == month)? as u32 + 1;
+    let time = time.split(':').map(|x| x.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+    let [hour, minute, second] = time[..] else { return None; };
+    if hour > 23 || minute > 59 || second > 60 { return None; }
+    let seconds = unix_seconds(year.parse().ok()?, month, day.parse().ok()?, hour * 3600 + minute * 60 + second)?;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+///RFC 6265 5.1.1，兼容`Sun, 06-Nov-94 08:49:37 GMT`等格式
+pub(crate) fn parse_cookie_date(value: &str) -> Option<SystemTime> {
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    let tokens = value.split(|c: char| !(c.is_ascii_alphanumeric() || c == ':')).filter(|x| !x.is_empty());
+    for token in tokens {
+        let digits = token.bytes().take_while(|x| x.is_ascii_digit()).count();
+        if time.is_none() && token.contains(':') {
+            let parts = token.split(':').map(|x| x.parse::<u64>().ok()).collect::<Option<Vec<_>>>();
+            if let Some([hour, minute, second]) = parts.as_deref() {
+                time = Some((*hour, *minute, *second));
+                continue;
+            }
+        }
+        if day.is_none() && (1..=2).contains(&digits) {
+            day = token[..digits].parse::<u32>().ok();
+        } else if month.is_none() && token.len() >= 3 && let Some(pos) = MONTHS.iter().position(|x| token[..3].eq_ignore_ascii_case(x)) {
+            month = Some(pos as u32 + 1);
+        } else if year.is_none() && (2..=4).contains(&digits) {
+            year = token[..digits].parse::<i64>().ok();
+        }
+    }
+    let ((hour, minute, second), day, month, mut year) = (time?, day?, month?, year?);
+    if (70..=99).contains(&year) { year += 1900; } else if (0..=69).contains(&year) { year += 2000; }
+    if year < 1601 || hour > 23 || minute > 59 || second > 59 { return None; }
+    let seconds = unix_seconds(year, month, day, hour * 3600 + minute * 60 + second)?;
+    //1970年之前的日期视为已过期
+    match u64::try_from(seconds) {
+        Ok(seconds) => Some(UNIX_EPOCH + Duration::from_secs(seconds)),
+        Err(_) => Some(UNIX_EPOCH),
+    }
+}
+
+///格式化为IMF-fixdate
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let days = (seconds / 86400) as i64;
+    //1970-01-01是星期四
+    let weekday = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"][(days % 7) as usize];
+    let (year, month, day) = civil_from_days(days);
+    let seconds = seconds % 86400;
+    format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT", weekday, day, MONTHS[month as usize - 1], year, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+///日期加上当天的秒数，1970-01-01 00:00:00之后的秒数
+fn unix_seconds(year: i64, month: u32, day: u32, seconds: u64) -> Option<i64> {
+    days_from_civil(year, month, day)?.checked_mul(86400)?.checked_add(seconds as i64)
+}
+
+///days_from_civil的逆运算
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+///1970-01-01之后的天数
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) { return None; }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146097 + doe - 719468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(date.duration_since(UNIX_EPOCH).unwrap().as_secs(), 784111777);
+        assert_eq!(format_http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format_http_date(UNIX_EPOCH + Duration::from_secs(951782400)), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert!(parse_http_date("Sun, 06 Nov 1994 24:49:37 GMT").is_none());
+
+        assert_eq!(parse_cookie_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(date));
+        assert_eq!(parse_cookie_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(date));
+        assert_eq!(parse_cookie_date("Thu, 01 Jan 1960 00:00:00 GMT"), Some(UNIX_EPOCH));
+        assert!(parse_cookie_date("Nov 1994").is_none());
+    }
+}
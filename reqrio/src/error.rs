@@ -10,6 +10,8 @@ use std::string::FromUtf8Error;
 use std::sync::PoisonError;
 use httlib_hpack::{DecoderError, EncoderError};
 use json::JsonError;
+use crate::packet::H2ErrorCode;
+use crate::ws::CloseCode;
 #[cfg(feature = "rustls")]
 use rustls::pki_types::InvalidDnsNameError;
 #[cfg(aync)]
@@ -17,6 +19,77 @@ use tokio::time::error::Elapsed;
 #[cfg(use_cls)]
 use reqtls::RlsError;
 
+///超时发生的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    Connect,
+    TlsHandshake,
+    Read,
+    Write,
+    ///整个请求的处理时间
+    Handle,
+}
+
+impl Display for TimeoutPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeoutPhase::Connect => f.write_str("connect"),
+            TimeoutPhase::TlsHandshake => f.write_str("tls handshake"),
+            TimeoutPhase::Read => f.write_str("read"),
+            TimeoutPhase::Write => f.write_str("write"),
+            TimeoutPhase::Handle => f.write_str("handle"),
+        }
+    }
+}
+
+///#### tls错误
+/// * cls为reqtls的RlsError，std为rustls的Error，都可以取得对端发送的alert
+#[derive(Debug)]
+pub enum TlsError {
+    #[cfg(use_cls)]
+    Rls(RlsError),
+    #[cfg(feature = "rustls")]
+    Rustls(rustls::Error),
+}
+
+impl TlsError {
+    ///对端发送的alert的description，例如handshake_failure为40
+    pub fn alert(&self) -> Option<u8> {
+        match *self {
+            #[cfg(use_cls)]
+            TlsError::Rls(RlsError::Alert(_, description)) => Some(description),
+            #[cfg(feature = "rustls")]
+            TlsError::Rustls(rustls::Error::AlertReceived(description)) => Some(u8::from(description)),
+            #[cfg(any(use_cls, feature = "rustls"))]
+            _ => None,
+        }
+    }
+
+    fn inner(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            #[cfg(use_cls)]
+            TlsError::Rls(ref e) => Some(e),
+            #[cfg(feature = "rustls")]
+            TlsError::Rustls(ref e) => Some(e),
+        }
+    }
+}
+
+impl Display for TlsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.inner() {
+            Some(e) => write!(f, "{}", e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for TlsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner()?.source()
+    }
+}
+
 #[derive(Debug)]
 pub enum HlsError {
     NonePointer,
@@ -27,6 +100,106 @@ pub enum HlsError {
     EncrypterNone,
     // StdErr(Box<dyn Error>),
     Currently(String),
+    ///h2的stream被对端RST_STREAM
+    StreamReset(H2ErrorCode),
+    ///h2连接被对端GOAWAY，大于last_stream_id的stream没有被处理
+    GoAway(u32, H2ErrorCode),
+    ///对端违反h2协议(流量控制、帧格式、hpack等)，连接不能继续使用
+    H2Protocol(H2ErrorCode, String),
+    Io(io::Error),
+    ///域名解析失败(host, 原因)
+    Resolve(String, io::Error),
+    ///tcp连接失败(地址, 原因)
+    Connect(String, io::Error),
+    Timeout(TimeoutPhase),
+    ///代理握手失败(代理, 原因)
+    Proxy(String, Box<HlsError>),
+    Tls(TlsError),
+    ///http/1.1响应不符合协议
+    Protocol(String),
+    ///响应体解码(chunked之后的解压)失败
+    Decode(Box<HlsError>),
+    ///send_check时4xx、5xx的响应状态码，server-sent events不是200的响应状态码
+    Status(i32),
+    ///对端违反websocket协议，已经用关闭码关闭连接(关闭码, 原因)
+    WebSocket(CloseCode, String),
+    ///websocket已经关闭，不能继续收发
+    WebSocketClosed,
+}
+
+impl HlsError {
+    ///请求没有被服务端处理(GOAWAY之后的stream、REFUSED_STREAM)，可以重连后安全地重试
+    pub fn is_unprocessed(&self) -> bool {
+        matches!(self, HlsError::GoAway(_, _) | HlsError::StreamReset(H2ErrorCode::RefusedStream))
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            HlsError::Timeout(_) => true,
+            HlsError::Io(e) | HlsError::Connect(_, e) | HlsError::Resolve(_, e) => is_timeout_kind(e.kind()),
+            HlsError::Proxy(_, e) | HlsError::Decode(e) => e.is_timeout(),
+            _ => false,
+        }
+    }
+
+    ///超时、连接失败或被关闭、未被处理的h2请求、408/429/502/503/504等临时错误，可以重试
+    pub fn is_retryable(&self) -> bool {
+        if self.is_timeout() || self.is_unprocessed() { return true; }
+        match self {
+            HlsError::PeerClosedConnection | HlsError::Connect(_, _) => true,
+            HlsError::Io(e) => matches!(e.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted |
+                io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof | io::ErrorKind::Interrupted),
+            HlsError::Proxy(_, e) => e.is_retryable(),
+            HlsError::Status(status) => matches!(status, 408 | 429 | 502 | 503 | 504),
+            _ => false,
+        }
+    }
+
+    pub fn timeout_phase(&self) -> Option<TimeoutPhase> {
+        match self {
+            HlsError::Timeout(phase) => Some(*phase),
+            HlsError::Proxy(_, e) | HlsError::Decode(e) => e.timeout_phase(),
+            _ => None,
+        }
+    }
+
+    ///对端发送的tls alert的description
+    pub fn tls_alert(&self) -> Option<u8> {
+        match self {
+            HlsError::Tls(e) => e.alert(),
+            HlsError::Proxy(_, e) => e.tls_alert(),
+            _ => None,
+        }
+    }
+
+    ///把读写中的超时标记为对应阶段，其余错误不变
+    #[cfg(anys)]
+    pub(crate) fn phase(self, phase: TimeoutPhase) -> HlsError {
+        match self {
+            HlsError::Io(e) if is_timeout_kind(e.kind()) => HlsError::Timeout(phase),
+            e => e,
+        }
+    }
+
+    ///h2连接的错误需要分发给每个stream，io错误只保留kind和描述
+    #[cfg(aync)]
+    pub(crate) fn duplicate(&self) -> HlsError {
+        match self {
+            HlsError::PeerClosedConnection => HlsError::PeerClosedConnection,
+            HlsError::StreamReset(code) => HlsError::StreamReset(code.clone()),
+            HlsError::GoAway(last_stream_id, code) => HlsError::GoAway(*last_stream_id, code.clone()),
+            HlsError::H2Protocol(code, e) => HlsError::H2Protocol(code.clone(), e.clone()),
+            HlsError::Io(e) => HlsError::Io(io::Error::new(e.kind(), e.to_string())),
+            HlsError::Timeout(phase) => HlsError::Timeout(*phase),
+            HlsError::Protocol(e) => HlsError::Protocol(e.clone()),
+            e => HlsError::Currently(e.to_string()),
+        }
+    }
+}
+
+fn is_timeout_kind(kind: io::ErrorKind) -> bool {
+    //同步socket设置超时后，超时在部分平台上返回WouldBlock
+    matches!(kind, io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
 }
 
 impl From<&str> for HlsError {
@@ -63,6 +236,20 @@ impl Display for HlsError {
             HlsError::DecrypterNone => f.write_str("DecrypterNone"),
             HlsError::NonePointer => f.write_str("NonePointer"),
             HlsError::EncrypterNone => f.write_str("EncrypterNone"),
+            HlsError::StreamReset(code) => write!(f, "h2 stream reset by peer: {}", code),
+            HlsError::GoAway(last_stream_id, code) => write!(f, "h2 connection goaway: last_stream_id={}, {}", last_stream_id, code),
+            HlsError::H2Protocol(code, e) => write!(f, "h2 protocol error: {}, {}", code, e),
+            HlsError::Io(e) => write!(f, "{}", e),
+            HlsError::Resolve(host, e) => write!(f, "resolve {} error: {}", host, e),
+            HlsError::Connect(addr, e) => write!(f, "connect to {} error: {}", addr, e),
+            HlsError::Timeout(phase) => write!(f, "{} timeout", phase),
+            HlsError::Proxy(proxy, e) => write!(f, "proxy {} error: {}", proxy, e),
+            HlsError::Tls(e) => write!(f, "tls error: {}", e),
+            HlsError::Protocol(e) => write!(f, "http protocol error: {}", e),
+            HlsError::Decode(e) => write!(f, "decode response body error: {}", e),
+            HlsError::Status(status) => write!(f, "网络请求错误-{}", status),
+            HlsError::WebSocket(code, e) => write!(f, "websocket protocol error: {}, {}", code, e),
+            HlsError::WebSocketClosed => f.write_str("websocket closed"),
         }
     }
 }
@@ -75,7 +262,19 @@ impl From<TryFromSliceError> for HlsError {
 
 impl From<io::Error> for HlsError {
     fn from(value: io::Error) -> Self {
-        HlsError::Currently(value.to_string())
+        //Read/Write实现中转成io::Error的错误还原回来
+        if value.get_ref().is_some_and(|x| x.is::<HlsError>()) {
+            return *value.into_inner().unwrap().downcast::<HlsError>().unwrap();
+        }
+        #[cfg(use_cls)]
+        if value.get_ref().is_some_and(|x| x.is::<RlsError>()) {
+            return HlsError::from(*value.into_inner().unwrap().downcast::<RlsError>().unwrap());
+        }
+        #[cfg(feature = "rustls")]
+        if value.get_ref().is_some_and(|x| x.is::<rustls::Error>()) {
+            return HlsError::from(*value.into_inner().unwrap().downcast::<rustls::Error>().unwrap());
+        }
+        HlsError::Io(value)
     }
 }
 
@@ -90,7 +289,7 @@ impl From<InvalidDnsNameError> for HlsError {
 #[cfg(feature = "rustls")]
 impl From<rustls::Error> for HlsError {
     fn from(value: rustls::Error) -> Self {
-        HlsError::Currently(value.to_string())
+        HlsError::Tls(TlsError::Rustls(value))
     }
 }
 
@@ -102,13 +301,13 @@ impl From<Infallible> for HlsError {
 
 impl From<EncoderError> for HlsError {
     fn from(value: EncoderError) -> Self {
-        HlsError::Currently(value.to_string())
+        HlsError::H2Protocol(H2ErrorCode::CompressionError, value.to_string())
     }
 }
 
 impl From<DecoderError> for HlsError {
     fn from(value: DecoderError) -> Self {
-        HlsError::Currently(value.to_string())
+        HlsError::H2Protocol(H2ErrorCode::CompressionError, value.to_string())
     }
 }
 
@@ -133,20 +332,29 @@ impl From<Utf8Error> for HlsError {
 #[cfg(use_cls)]
 impl From<RlsError> for HlsError {
     fn from(value: RlsError) -> Self {
-        HlsError::Currently(value.to_string())
+        match value {
+            RlsError::Alert(1, 0) => HlsError::PeerClosedConnection,
+            //socket的io错误不属于tls
+            RlsError::StdError(e) if e.is::<io::Error>() => HlsError::from(*e.downcast::<io::Error>().unwrap()),
+            value => HlsError::Tls(TlsError::Rls(value)),
+        }
     }
 }
 
+///超时的阶段由调用方通过phase标记
 #[cfg(aync)]
 impl From<Elapsed> for HlsError {
     fn from(value: Elapsed) -> Self {
-        HlsError::Currently(value.to_string())
+        HlsError::Io(value.into())
     }
 }
 
 impl From<HlsError> for io::Error {
     fn from(err: HlsError) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, err.to_string())
+        match err {
+            HlsError::Io(e) => e,
+            err => io::Error::other(err),
+        }
     }
 }
 
@@ -168,9 +376,39 @@ impl From<AddrParseError> for HlsError {
 //     }
 // }
 
-impl Error for HlsError {}
-
-unsafe impl Send for HlsError {}
+impl Error for HlsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HlsError::Io(e) | HlsError::Resolve(_, e) | HlsError::Connect(_, e) => Some(e),
+            HlsError::Proxy(_, e) | HlsError::Decode(e) => Some(e.as_ref()),
+            HlsError::Tls(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 
 pub type HlsResult<T> = Result<T, HlsError>;
+
+#[cfg(test)]
+mod tests {
+    use super::{HlsError, TimeoutPhase};
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn test_error_kind() {
+        //经过Read/Write的io::Error后还原
+        let e = HlsError::from(io::Error::from(HlsError::Status(503)));
+        assert!(matches!(e, HlsError::Status(503)) && e.is_retryable());
+        assert!(!HlsError::Status(404).is_retryable());
+        let e = HlsError::from(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(matches!(e, HlsError::Io(_)) && e.is_timeout());
+        let e = HlsError::Proxy("socks5://127.0.0.1:1080".to_string(), Box::new(HlsError::Timeout(TimeoutPhase::Connect)));
+        assert_eq!(e.timeout_phase(), Some(TimeoutPhase::Connect));
+        assert!(e.is_retryable() && e.source().is_some());
+        let e = HlsError::Connect("127.0.0.1:1".to_string(), io::ErrorKind::ConnectionRefused.into());
+        assert!(e.is_retryable() && !e.is_timeout());
+        assert!(!HlsError::Protocol("chunk data end error".to_string()).is_retryable());
+    }
+}
@@ -1,14 +1,19 @@
-use crate::body::BodyType;
-use crate::error::HlsResult;
+use crate::body::{BodyReader, BodyType};
+use crate::error::{HlsError, HlsResult};
 use crate::file::HttpFile;
 use crate::packet::*;
+use crate::redirect::{Redirect, RedirectPolicy};
+use crate::retry::{Outcome, Retry, RetryPolicy};
 use crate::timeout::Timeout;
 use crate::url::Url;
-use crate::{coder, Buffer, Proxy, ReqCallback, ALPN};
+use crate::{coder, Buffer, CookieJar, Pool, Proxy, ReqCallback, Resolver, SystemProxy, ALPN};
 use json::JsonValue;
+use std::io::Read;
+use std::time::Duration;
+#[cfg(aync)]
+use tokio::io::AsyncRead;
 #[cfg(use_cls)]
 use reqtls::Fingerprint;
-use crate::coder::HackDecode;
 
 pub trait ReqExt: Sized {
     fn body_type(&self) -> &BodyType;
@@ -25,11 +30,23 @@ pub trait ReqExt: Sized {
     fn set_bytes(&mut self, bs: Vec<u8>) {
         *self.body_type_mut() = BodyType::Bytes(bs);
     }
-    /// * 文件上传示例
+
+    /// * 流式请求体，发送时分块读取，只能发送一次
+    /// * len为None时http/1.1使用chunked发送
+    fn set_reader(&mut self, reader: impl Read + Send + 'static, len: Option<u64>) {
+        *self.body_type_mut() = BodyType::Stream(Some(BodyReader::from_reader(reader, len)));
+    }
+
+    /// * 异步的流式请求体，仅AcReq可用
+    #[cfg(aync)]
+    fn set_async_reader(&mut self, reader: impl AsyncRead + Send + Unpin + 'static, len: Option<u64>) {
+        *self.body_type_mut() = BodyType::Stream(Some(BodyReader::from_async_reader(reader, len)));
+    }
+    /// * 文件上传示例，通过路径创建的文件上传时从磁盘分块读取
     /// ```rust
     /// let files=vec![]
-    /// files.push(HttpFile::new_fp("path/to/file1"));
-    /// files.push(HttpFile::new_fp("path/to/file1"));
+    /// files.push(HttpFile::new_with_fp("path/to/file1"));
+    /// files.push(HttpFile::new_with_fp("path/to/file1"));
     /// let data=json::object!{"key":"value"};
     /// req.set_files(data,files)
     /// ```
@@ -52,16 +69,56 @@ pub trait ReqExt: Sized {
     fn url(&self) -> &Url;
     fn url_mut(&mut self) -> &mut Url;
     fn set_proxy(&mut self, proxy: Proxy);
+    fn proxy(&self) -> &Proxy;
     fn with_proxy(mut self, proxy: Proxy) -> Self {
         self.set_proxy(proxy);
         self
     }
+    /// *按目标url选择代理(例如`SystemProxy::from_env`)，重定向时重新选择，None为不使用代理，必须在set_url/with_url前设置，
+    /// 之后调用set_proxy会取消
+    fn set_system_proxy(&mut self, proxy: Option<SystemProxy>);
+    fn with_system_proxy(mut self, proxy: SystemProxy) -> Self {
+        self.set_system_proxy(Some(proxy));
+        self
+    }
+    /// *共享连接池，必须在set_url/with_url前设置
+    fn set_pool(&mut self, pool: Pool);
+    fn with_pool(mut self, pool: Pool) -> Self {
+        self.set_pool(pool);
+        self
+    }
+    /// *域名解析器，默认为系统解析，必须在set_url/with_url前设置
+    fn set_resolver(&mut self, resolver: impl Resolver + 'static);
+    fn with_resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.set_resolver(resolver);
+        self
+    }
+    /// *cookie jar，默认每个请求单独使用一个，clone后共享
+    fn set_cookie_jar(&mut self, jar: CookieJar);
+    fn with_cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.set_cookie_jar(jar);
+        self
+    }
+    fn cookie_jar(&self) -> &CookieJar;
     /// *必须在建立tls连接（即：set_url/with_url）前设置, 否则需要调re_conn
     fn set_alpn(&mut self, alpn: ALPN);
     fn with_alpn(mut self, alpn: ALPN) -> Self {
         self.set_alpn(alpn);
         self
     }
+    /// *重定向策略，默认不跟随
+    fn set_redirect(&mut self, policy: RedirectPolicy);
+    fn with_redirect(mut self, policy: RedirectPolicy) -> Self {
+        self.set_redirect(policy);
+        self
+    }
+    /// *重试策略，默认ExponentialBackoff
+    fn set_retry(&mut self, policy: impl RetryPolicy + 'static);
+    fn with_retry(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.set_retry(policy);
+        self
+    }
+    /// *响应体去掉chunked并解压后分块交给回调，设置后response中不再保存响应体
     fn set_callback(&mut self, callback: impl FnMut(&[u8]) -> HlsResult<()> + 'static);
     #[cfg(use_cls)]
     fn set_fingerprint(&mut self, fingerprint: Fingerprint);
@@ -70,6 +127,12 @@ pub trait ReqExt: Sized {
         self.set_fingerprint(fingerprint);
         self
     }
+    /// *h2指纹(SETTINGS、WINDOW_UPDATE、PRIORITY、伪头部顺序)，必须在建立h2连接(即：set_url/with_url)前设置
+    fn set_h2_fingerprint(&mut self, fingerprint: H2Fingerprint);
+    fn with_h2_fingerprint(mut self, fingerprint: H2Fingerprint) -> Self {
+        self.set_h2_fingerprint(fingerprint);
+        self
+    }
     fn set_headers(&mut self, mut headers: Header, keep_cookie: bool) {
         if keep_cookie {
             let cks = self.header_mut().cookies().unwrap_or(&vec![]).clone();
@@ -118,51 +181,113 @@ pub trait ReqExt: Sized {
 
 pub(crate) trait ReqPriExt: ReqExt {
     fn callback(&mut self) -> &mut Option<ReqCallback>;
+    fn h2_fingerprint(&self) -> &H2Fingerprint;
+    fn redirect_policy(&self) -> &RedirectPolicy;
+    ///本次请求已经跟随的重定向
+    fn redirects(&self) -> &[Redirect];
+    fn redirects_mut(&mut self) -> &mut Vec<Redirect>;
+    fn retry_policy(&self) -> &dyn RetryPolicy;
 
-    fn hack_decoder(&mut self) -> &mut HackDecode;
+    ///按重试策略返回下一次重试，attempt为已经失败的次数
+    fn next_retry(&mut self, attempt: u32, outcome: Outcome<'_>) -> Option<Retry> {
+        if attempt >= self.timeout().handle_times().max(1) as u32 { return None; }
+        //已读取的流式请求体不能重发
+        if matches!(self.body_type(), BodyType::Stream(None)) { return None; }
+        //回调已经收到了响应体
+        if matches!(outcome, Outcome::Response(_)) && self.callback().is_some() { return None; }
+        let retry = self.retry_policy().retry(attempt, self.header().method(), outcome)?;
+        //等待之后已经超过总超时
+        if self.timeout().remaining().is_some_and(|x| x <= retry.delay) { return None; }
+        Some(retry)
+    }
 
-    fn handle_h1_res(&mut self, buffer: &Buffer, response: &mut Response, rd: &mut usize) -> HlsResult<bool> {
-        match self.callback() {
-            None => response.extend(&buffer),
-            Some(callback) => {
-                if response.header().is_empty() {
-                    response.extend(&buffer)?;
-                    if !response.header().is_empty() {
-                        callback(response.raw_body())?;
-                        *rd += response.raw_body().len();
-                        response.clear_raw();
-                    }
-                } else {
-                    callback(buffer.filled())?;
-                    *rd += buffer.filled().len();
-                }
-                if response.header().is_empty() { return Ok(false); }
-                match response.header().content_length() {
-                    None => Ok(buffer.filled().ends_with(&[48, 13, 10, 13, 10])),
-                    Some(len) => Ok(*rd >= len)
-                }
-            }
+    ///按重试策略返回重新连接前的等待时间
+    fn next_connect_retry(&self, attempt: u32, error: &HlsError) -> Option<Duration> {
+        if attempt >= self.timeout().connect_times().max(1) as u32 { return None; }
+        let delay = self.retry_policy().retry_connect(attempt, error)?;
+        if self.timeout().remaining().is_some_and(|x| x <= delay) { return None; }
+        Some(delay)
+    }
+
+    ///按重定向策略返回需要跟随的重定向
+    fn next_redirect(&self, header: &Header) -> HlsResult<Option<Redirect>> {
+        let status = header.status().status_num();
+        if !matches!(status, 301 | 302 | 303 | 307 | 308) { return Ok(None); }
+        let Some(location) = header.get("location").and_then(|x| x.as_string()) else { return Ok(None); };
+        //307/308需要重新发送请求体，流式请求体已经发送过
+        if matches!(status, 307 | 308) && matches!(self.body_type(), BodyType::Stream(None)) { return Ok(None); }
+        let redirect = Redirect::new(status, self.url().clone(), self.url().join(location)?);
+        Ok(self.redirect_policy().allow(&redirect, self.redirects()).then_some(redirect))
+    }
+
+    ///跟随重定向前修改请求方法和请求体，返回重定向的url
+    fn prepare_redirect(&mut self, redirect: Redirect) -> String {
+        let method = self.header().method();
+        let to_get = match redirect.status() {
+            303 => !matches!(method, Method::HEAD),
+            301 | 302 => matches!(method, Method::POST),
+            _ => false,
+        };
+        if to_get {
+            self.header_mut().set_method(Method::GET);
+            *self.body_type_mut() = BodyType::Text("".to_string());
+            self.header_mut().remove("content-type");
         }
+        if !redirect.is_same_origin() { self.header_mut().remove("authorization"); }
+        let url = redirect.to().to_string();
+        self.redirects_mut().push(redirect);
+        url
     }
 
-    fn handle_h2_res(&mut self, frame: Frame, response: &mut Response) -> HlsResult<bool> {
-        if frame.frame_type() == &FrameType::Goaway { return Err("Connection reset by peer".into()); }
-        match self.callback() {
-            None => response.extend_frame(frame, self.hack_decoder()),
-            Some(callback) => {
-                match frame.frame_type() {
-                    FrameType::Data => {
-                        callback(frame.payload())?;
-                        Ok(frame.is_end_frame())
-                    }
-                    FrameType::Headers => Ok(response.extend_frame(frame, self.hack_decoder())?),
-                    _ => Ok(false),
-                }
+    ///设置回调时响应体去掉chunked并解压后交给回调，不再保存到response
+    fn handle_h1_res(&mut self, buffer: &Buffer, response: &mut Response, decoder: &mut Option<BodyDecoder>) -> HlsResult<bool> {
+        if self.callback().is_none() { return response.extend(buffer); }
+        let decoder = decoder.get_or_insert_with(|| BodyDecoder::new_h1(self.header().method()));
+        let data = decoder.decode(buffer.filled())?;
+        if let Some(header) = decoder.take_header() { *response.header_mut() = header; }
+        //跟随的重定向的响应体不交给回调
+        if data.is_empty() || self.next_redirect(response.header())?.is_some() { return Ok(decoder.is_ended()); }
+        if let Some(callback) = self.callback() { callback(&data)?; }
+        Ok(decoder.is_ended())
+    }
+
+    ///http/1.1连接被关闭，读到连接关闭的响应体正常结束，否则返回错误
+    fn finish_h1_res(&mut self, response: &mut Response, decoder: &mut Option<BodyDecoder>) -> HlsResult<()> {
+        let Some(decoder) = decoder else { return response.finish(); };
+        let data = decoder.finish()?;
+        if data.is_empty() || self.next_redirect(response.header())?.is_some() { return Ok(()); }
+        if let Some(callback) = self.callback() { callback(&data)?; }
+        Ok(())
+    }
+
+    fn handle_h2_event(&mut self, event: H2Event, response: &mut Response, decoder: &mut Option<BodyDecoder>) -> HlsResult<bool> {
+        let (data, ended) = match event {
+            H2Event::Headers(_, header, ended) => {
+                //trailer不覆盖响应头
+                if response.header().is_empty() { *response.header_mut() = header; }
+                (vec![], ended)
             }
+            H2Event::Data(_, data, ended) => (data, ended),
+            H2Event::Reset(_, code) => return Err(HlsError::StreamReset(code)),
+            //连接级事件由调用方根据stream_id处理
+            H2Event::GoAway(_, _) => return Ok(false),
+        };
+        if self.callback().is_none() {
+            response.push_raw(data);
+            return Ok(ended);
         }
+        let decoder = match decoder {
+            Some(decoder) => decoder,
+            None => decoder.insert(BodyDecoder::new_h2(response.header())?),
+        };
+        let mut data = decoder.decode(&data)?;
+        if ended { data.extend(decoder.finish()?); }
+        if data.is_empty() || self.next_redirect(response.header())?.is_some() { return Ok(ended); }
+        if let Some(callback) = self.callback() { callback(&data)?; }
+        Ok(ended)
     }
 
-    fn format_file_body((data, files): &(JsonValue, Vec<HttpFile>), md5: &str) -> HlsResult<Vec<u8>> {
+    fn format_file_body((data, files): &(JsonValue, Vec<HttpFile>), md5: &str) -> HlsResult<BodyReader> {
         let mut body = vec![];
         for (k, v) in data.entries() {
             body.push(format!("--{}", md5));
@@ -171,60 +296,95 @@ pub(crate) trait ReqPriExt: ReqExt {
             body.push(v.dump());
             body.push("".to_string());
         };
-        let mut body = body.join("\r\n").into_bytes();
+        let mut reader = BodyReader::new();
+        reader.push_bytes(body.join("\r\n").into_bytes());
         for file in files {
-            body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n", md5, file.filed_name(), file.filename()).into_bytes());
+            let mut body = format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n", md5, file.filed_name(), file.filename()).into_bytes();
             if file.file_type() != "" {
                 body.extend(format!("Content-Type: {}\r\n", file.file_type()).into_bytes());
             }
             body.extend_from_slice(b"\r\n");
-            body.extend(file.raw_bytes());
+            match (file.raw_bytes(), file.path()) {
+                (Some(bytes), _) => body.extend(bytes),
+                (None, Some(path)) => {
+                    reader.push_bytes(body);
+                    body = vec![];
+                    reader.push_file(path.to_path_buf(), file.filesize() as u64);
+                }
+                (None, None) => {}
+            }
             body.append(&mut "\r\n".as_bytes().to_vec());
+            reader.push_bytes(body);
         }
-        body.append(&mut format!("--{}--\r\n", md5).as_bytes().to_vec());
-        Ok(body)
+        reader.push_bytes(format!("--{}--\r\n", md5).into_bytes());
+        Ok(reader)
     }
 
-    fn format_body(&mut self, md5: &str) -> HlsResult<Vec<u8>> {
-        match self.body_type() {
-            BodyType::Text(text) => Ok(text.as_bytes().to_vec()),
-            BodyType::Bytes(bytes) => Ok(bytes.to_vec()),
+    fn format_body(&mut self, md5: &str) -> HlsResult<BodyReader> {
+        let bytes = match self.body_type_mut() {
+            BodyType::Text(text) => text.as_bytes().to_vec(),
+            BodyType::Bytes(bytes) => bytes.to_vec(),
             BodyType::Files(fds) => {
                 // let md5 = "abcde12345abcdebbeeaaccafeacb454";
-                let body_bytes = Self::format_file_body(fds, md5)?;
-                Ok(body_bytes)
+                return Self::format_file_body(fds, md5);
             }
-            BodyType::WwwForm(form) => Ok(form.entries().map(|(k, v)| {
+            BodyType::WwwForm(form) => form.entries().map(|(k, v)| {
                 format!("{}={}", k, coder::url_encode(v.dump()))
-            }).collect::<Vec<_>>().join("&").into_bytes()),
-            BodyType::Json(jd) => Ok(jd.dump().into_bytes()),
-        }
+            }).collect::<Vec<_>>().join("&").into_bytes(),
+            BodyType::Json(jd) => jd.dump().into_bytes(),
+            BodyType::Stream(reader) => return reader.take().ok_or("request body stream has been sent".into()),
+        };
+        let mut reader = BodyReader::new();
+        reader.push_bytes(bytes);
+        Ok(reader)
     }
 
-    fn format_header(&mut self, md5: &str, body_len: usize) -> HlsResult<Vec<u8>> {
+    ///body_len为None时使用chunked
+    fn format_header(&mut self, md5: &str, body_len: Option<u64>) -> HlsResult<Vec<u8>> {
         if let BodyType::Files(_) = self.body_type() {
             self.header_mut().set_content_type(ContentType::File(md5.to_string()));
         }
-        let mut headers = self.header_mut().as_raw(body_len)?;
-        headers.insert(0, format!("{} {} HTTP/1.1", self.header().method(), self.url().uri()));
+        let mut headers = match body_len {
+            Some(len) => self.header_mut().as_raw(len as usize)?,
+            None => self.header_mut().as_raw_chunked()?,
+        };
+        //转发给http代理时请求行使用绝对地址，认证信息放在请求头中
+        match self.proxy().is_forward(self.url()) {
+            true => {
+                headers.insert(0, format!("{} {} HTTP/1.1", self.header().method(), self.url()));
+                if let Some(auth) = self.proxy().auth() { headers.push(format!("Proxy-Authorization: {}", auth.basic())); }
+            }
+            false => headers.insert(0, format!("{} {} HTTP/1.1", self.header().method(), self.url().uri())),
+        }
         headers.push("".to_string());
         headers.push("".to_string());
         Ok(headers.join("\r\n").into_bytes())
     }
 
+    ///响应的Set-Cookie保存到cookie jar
     #[cfg(anys)]
     fn update_cookie(&mut self, response: &Response) {
         for cookie in response.header().cookies().unwrap_or(&vec![]) {
-            if cookie.name() == "" && cookie.value() == "" { continue; }
-            self.header_mut().add_cookie(cookie.clone());
+            self.cookie_jar().store(self.url(), cookie);
         }
     }
 
+    ///在手动设置的cookie后加上cookie jar中匹配当前url的cookie，返回手动设置的cookie用于发送后恢复
+    fn merge_jar_cookies(&mut self) -> Vec<Cookie> {
+        let manual = self.header().cookies().cloned().unwrap_or_default();
+        let mut cookies = manual.clone();
+        for cookie in self.cookie_jar().cookies(self.url()) {
+            if cookies.iter().all(|x| x.name() != cookie.name()) { cookies.push(cookie); }
+        }
+        self.header_mut().set_cookies(cookies);
+        manual
+    }
+
     #[cfg(anys)]
     fn check_status(&self, response: &Response) -> HlsResult<()> {
         let status = response.header().status().status_num();
         match status {
-            400..600 => Err(format!("网络请求错误-{}", status).into()),
+            400..600 => Err(HlsError::Status(status)),
             _ => Ok(())
         }
     }
@@ -243,29 +403,48 @@ pub(crate) trait ReqPriExt: ReqExt {
 
 #[allow(private_bounds)]
 pub trait ReqGenExt: ReqPriExt {
-    fn gen_h1(&mut self) -> HlsResult<Vec<u8>> {
-        let host = self.url().addr().to_string().replace(":80", "").replace(":443", "");
+    ///请求体全部在内存中时和请求头一起返回，否则返回需要分块发送的请求体
+    fn gen_h1(&mut self) -> HlsResult<(Vec<u8>, Option<BodyReader>)> {
+        let host = self.url().authority();
         match self.header().host() {
             None => self.header_mut().set_host(host)?,
             Some(key_host) => if key_host.is_empty() || key_host != host { self.header_mut().set_host(host)? }
         }
         let md5 = "abcde12345abcdebbeeaaccafeacb454";
-        let body = self.format_body(md5)?;
-        let mut content = self.format_header(md5, body.len())?;
-        content.extend(body);
-        Ok(content)
+        let mut body = self.format_body(md5)?;
+        let manual = self.merge_jar_cookies();
+        let content = self.format_header(md5, body.len());
+        self.header_mut().set_cookies(manual);
+        let mut content = content?;
+        match body.take_bytes() {
+            None => Ok((content, Some(body))),
+            Some(bytes) => {
+                content.extend(bytes);
+                Ok((content, None))
+            }
+        }
     }
 
     fn gen_h2_header(&mut self) -> HlsResult<Vec<HeaderKey>> {
-        let mut headers = self.header().as_h2c()?;
-        headers.insert(1, HeaderKey::new(":authority".to_string(), HeaderValue::String(self.url().addr().to_string().replace(":80", "").replace(":443", ""))));
-        headers.insert(2, HeaderKey::new(":scheme".to_string(), HeaderValue::String("https".to_string())));
-        headers.insert(3, HeaderKey::new(":path".to_string(), HeaderValue::String(self.url().uri().to_string())));
-        Ok(headers)
+        let manual = self.merge_jar_cookies();
+        let headers = self.header().as_h2c();
+        self.header_mut().set_cookies(manual);
+        let mut headers = headers?;
+        let mut pseudo = vec![
+            headers.remove(0),
+            HeaderKey::new(":authority".to_string(), HeaderValue::String(self.url().authority())),
+            HeaderKey::new(":scheme".to_string(), HeaderValue::String("https".to_string())),
+            HeaderKey::new(":path".to_string(), HeaderValue::String(self.url().uri().to_string())),
+        ];
+        //伪头部按h2指纹排序
+        let order = self.h2_fingerprint().pseudo_header_order();
+        pseudo.sort_by_key(|x| order.iter().position(|o| *o == x.name()));
+        pseudo.extend(headers);
+        Ok(pseudo)
     }
 
 
-    fn gen_h2_body(&mut self) -> HlsResult<Vec<u8>> {
+    fn gen_h2_body(&mut self) -> HlsResult<BodyReader> {
         self.format_body("abcde12345abcdebbeeaaccafeacb454")
     }
 }
\ No newline at end of file
@@ -1,9 +1,15 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use crate::error::HlsResult;
 
+enum FileSource {
+    Bytes(Vec<u8>),
+    //上传时从磁盘分块读取，不读入内存
+    Path(PathBuf, u64),
+}
+
 pub struct HttpFile {
     filename: String,
-    bytes: Vec<u8>,
+    source: FileSource,
     filed_name: String,
     file_type: String,
 }
@@ -11,7 +17,7 @@ pub struct HttpFile {
 impl HttpFile {
     pub fn new(bytes: Vec<u8>) -> HttpFile {
         HttpFile {
-            bytes,
+            source: FileSource::Bytes(bytes),
             filename: format!("{}.png", "abbac323abe"),
             filed_name: "file".to_string(),
             file_type: "".to_string(),
@@ -20,7 +26,9 @@ impl HttpFile {
     pub fn new_with_fp(fp: impl AsRef<Path>) -> HlsResult<HttpFile> {
         let fp = fp.as_ref();
         let filename = fp.file_name().ok_or("path error")?.display().to_string();
-        let mut res = HttpFile::new(std::fs::read(fp)?);
+        let size = std::fs::metadata(fp)?.len();
+        let mut res = HttpFile::new(vec![]);
+        res.source = FileSource::Path(fp.to_path_buf(), size);
         res.set_filename(filename);
         Ok(res)
     }
@@ -33,9 +41,28 @@ impl HttpFile {
         self.filed_name = filed_name;
     }
 
-    pub fn filesize(&self) -> usize { self.bytes.len() }
+    pub fn filesize(&self) -> usize {
+        match &self.source {
+            FileSource::Bytes(bytes) => bytes.len(),
+            FileSource::Path(_, size) => *size as usize,
+        }
+    }
+
+    ///文件内容在内存中时返回，通过路径创建的返回None，使用path()
+    pub fn raw_bytes(&self) -> Option<&[u8]> {
+        match &self.source {
+            FileSource::Bytes(bytes) => Some(bytes.as_slice()),
+            FileSource::Path(_, _) => None,
+        }
+    }
 
-    pub fn raw_bytes(&self) -> &[u8] { self.bytes.as_slice() }
+    ///通过路径创建时返回文件路径，上传时从磁盘读取
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            FileSource::Bytes(_) => None,
+            FileSource::Path(path, _) => Some(path),
+        }
+    }
 
     pub fn set_file_type(&mut self, file_type: String) {
         self.file_type = file_type;
@@ -47,4 +74,4 @@ impl HttpFile {
 
     pub fn filename(&self) -> &str { &self.filename }
 
-}
\ No newline at end of file
+}
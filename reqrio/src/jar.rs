@@ -0,0 +1,320 @@
+use crate::error::HlsResult;
+use crate::packet::Cookie;
+use crate::date::{format_http_date, parse_cookie_date};
+use json::JsonValue;
+use crate::url::{Protocol, Url};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+struct StoredCookie {
+    //domain和path已经按规则补全
+    cookie: Cookie,
+    //没有Domain属性时只发送给设置它的host
+    host_only: bool,
+    //会话cookie为None
+    expires: Option<SystemTime>,
+    //同一路径长度的cookie按创建顺序发送
+    creation: u64,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|x| x <= now)
+    }
+
+    fn is_same(&self, other: &StoredCookie) -> bool {
+        self.cookie.name() == other.cookie.name() && self.cookie.domain() == other.cookie.domain() && self.cookie.path() == other.cookie.path()
+    }
+
+    ///导出的格式：可以发送给子域名的domain以`.`开头，过期时间为Expires
+    fn export(&self) -> Cookie {
+        let mut cookie = self.cookie.clone();
+        if !self.host_only { cookie.set_domain(format!(".{}", cookie.domain())); }
+        cookie.set_age(-1);
+        cookie.set_expires(self.expires.map(format_http_date).unwrap_or_default());
+        cookie
+    }
+
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        let domain = self.cookie.domain();
+        let domain_matched = match self.host_only {
+            true => host == domain,
+            false => domain_match(host, domain),
+        };
+        domain_matched && path_match(path, self.cookie.path()) && (secure || !self.cookie.secure())
+    }
+}
+
+#[derive(Default)]
+struct JarInner {
+    cookies: Vec<StoredCookie>,
+    next_creation: u64,
+}
+
+///#### cookie jar(RFC 6265)
+/// * 按响应的url保存Set-Cookie，处理Domain、Path、Secure、Max-Age、Expires，过期的cookie被删除
+/// * 请求时只发送匹配url(域名、路径、https)的cookie，路径长的在前
+/// * 多个ScReq/AcReq可以共享同一个cookie jar(clone后共享)
+/// * 没有公共后缀列表，只拒绝不含`.`的Domain；SameSite只保存，所有请求都视为同站请求
+/// * 可以导入导出Netscape的cookies.txt和json，在多次运行之间保存会话
+/// ```rust
+/// use reqrio::{CookieJar, ReqExt, ScReq};
+/// let jar = CookieJar::new();
+/// let mut req = ScReq::new().with_cookie_jar(jar.clone());
+/// jar.load_netscape(std::fs::read_to_string("cookies.txt").unwrap_or_default()).unwrap();
+/// std::fs::write("cookies.txt", jar.to_netscape()).unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    inner: Arc<Mutex<JarInner>>,
+}
+
+impl CookieJar {
+    pub fn new() -> CookieJar {
+        CookieJar::default()
+    }
+
+    ///保存url的响应中的Set-Cookie，不符合规则的cookie被忽略，返回是否保存
+    pub fn store(&self, url: &Url, cookie: &Cookie) -> bool {
+        match CookieJar::to_stored(url, cookie) {
+            None => false,
+            Some(stored) => self.add(stored),
+        }
+    }
+
+    ///直接添加cookie，domain必须设置，以`.`开头时也发送给子域名，path默认为`/`
+    pub fn insert(&self, mut cookie: Cookie) -> bool {
+        if cookie.name().is_empty() && cookie.value().is_empty() { return false; }
+        let domain = cookie.domain().to_lowercase();
+        let host_only = !domain.starts_with('.');
+        let domain = domain.trim_start_matches('.').to_string();
+        if domain.is_empty() { return false; }
+        cookie.set_domain(domain);
+        if !cookie.path().starts_with('/') { cookie.set_path("/".to_string()); }
+        let expires = CookieJar::expires(&cookie);
+        self.add(StoredCookie { cookie, host_only, expires, creation: 0 })
+    }
+
+    fn expires(cookie: &Cookie) -> Option<SystemTime> {
+        match cookie.age() {
+            -1 => parse_cookie_date(cookie.expires()),
+            //Max-Age<=0时立即过期
+            age if age <= 0 => Some(UNIX_EPOCH),
+            age => SystemTime::now().checked_add(Duration::from_secs(age as u64)),
+        }
+    }
+
+    fn add(&self, stored: StoredCookie) -> bool {
+        let Ok(mut inner) = self.inner.lock() else { return false; };
+        let now = SystemTime::now();
+        let creation = match inner.cookies.iter().position(|x| x.is_same(&stored)) {
+            //替换时保留原来的创建顺序
+            Some(pos) => inner.cookies.remove(pos).creation,
+            None => {
+                inner.next_creation += 1;
+                inner.next_creation
+            }
+        };
+        //过期时间已过的Set-Cookie用于删除cookie
+        if stored.is_expired(now) { return false; }
+        inner.cookies.push(StoredCookie { creation, ..stored });
+        true
+    }
+
+    fn to_stored(url: &Url, cookie: &Cookie) -> Option<StoredCookie> {
+        if cookie.name().is_empty() && cookie.value().is_empty() { return None; }
+        let host = url.addr().host().to_lowercase();
+        //http不能设置Secure的cookie(RFC 6265bis)
+        if cookie.secure() && !is_secure(url) { return None; }
+        let domain = cookie.domain().trim_start_matches('.').to_lowercase();
+        let host_only = domain.is_empty();
+        if !host_only && (!domain_match(&host, &domain) || !domain.contains('.')) { return None; }
+        let mut cookie = cookie.clone();
+        cookie.set_domain(if host_only { host } else { domain });
+        if !cookie.path().starts_with('/') { cookie.set_path(default_path(url.uri().path()).to_string()); }
+        let expires = CookieJar::expires(&cookie);
+        Some(StoredCookie { cookie, host_only, expires, creation: 0 })
+    }
+
+    ///匹配url的cookie，同时删除过期的cookie
+    pub fn cookies(&self, url: &Url) -> Vec<Cookie> {
+        let Ok(mut inner) = self.inner.lock() else { return vec![]; };
+        let now = SystemTime::now();
+        inner.cookies.retain(|x| !x.is_expired(now));
+        let host = url.addr().host().to_lowercase();
+        let path = match url.uri().path() {
+            "" => "/",
+            path => path,
+        };
+        let mut matched = inner.cookies.iter().filter(|x| x.matches(&host, path, is_secure(url))).collect::<Vec<_>>();
+        matched.sort_by_key(|x| (usize::MAX - x.cookie.path().len(), x.creation));
+        matched.into_iter().map(|x| x.cookie.clone()).collect()
+    }
+
+    ///所有未过期的cookie，格式和insert一致，开启serde时可以直接序列化
+    pub fn all(&self) -> Vec<Cookie> {
+        let Ok(mut inner) = self.inner.lock() else { return vec![]; };
+        let now = SystemTime::now();
+        inner.cookies.retain(|x| !x.is_expired(now));
+        inner.cookies.iter().map(|x| x.export()).collect()
+    }
+
+    ///cookie的数组，字段和`Cookie`的serde一致
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::from(self.all().into_iter().map(JsonValue::from).collect::<Vec<_>>())
+    }
+
+    ///加载to_json导出的cookie，返回添加的数量(已过期的不添加)
+    pub fn load_json(&self, value: &JsonValue) -> HlsResult<usize> {
+        if !value.is_array() { return Err("cookie json is not array".into()); }
+        let mut count = 0;
+        for cookie in value.members() {
+            if self.insert(Cookie::try_from(cookie)?) { count += 1; }
+        }
+        Ok(count)
+    }
+
+    ///Netscape的cookies.txt(curl、wget、浏览器插件导出的格式)
+    pub fn to_netscape(&self) -> String {
+        let Ok(mut inner) = self.inner.lock() else { return String::new(); };
+        let now = SystemTime::now();
+        inner.cookies.retain(|x| !x.is_expired(now));
+        let mut res = vec!["# Netscape HTTP Cookie File".to_string()];
+        for stored in &inner.cookies {
+            let cookie = &stored.cookie;
+            let domain = match (stored.host_only, cookie.http_only()) {
+                (true, true) => format!("#HttpOnly_{}", cookie.domain()),
+                (true, false) => cookie.domain().to_string(),
+                (false, true) => format!("#HttpOnly_.{}", cookie.domain()),
+                (false, false) => format!(".{}", cookie.domain()),
+            };
+            let expires = stored.expires.map(|x| x.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()).unwrap_or(0);
+            let flag = |x: bool| if x { "TRUE" } else { "FALSE" };
+            res.push([domain.as_str(), flag(!stored.host_only), cookie.path(), flag(cookie.secure()), &expires.to_string(), cookie.name(), cookie.value()].join("\t"));
+        }
+        res.join("\n") + "\n"
+    }
+
+    ///加载Netscape的cookies.txt，`#HttpOnly_`前缀表示HttpOnly，过期时间为0时是会话cookie
+    pub fn load_netscape(&self, text: impl AsRef<str>) -> HlsResult<usize> {
+        let mut count = 0;
+        for line in text.as_ref().lines().map(|x| x.trim_end_matches('\r')) {
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            if line.trim().is_empty() || line.starts_with('#') { continue; }
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let [domain, subdomains, path, secure, expires, name, ..] = fields[..] else { return Err(format!("invalid cookies.txt line: {}", line).into()); };
+            let mut cookie = Cookie::new_cookie(name, fields.get(6).unwrap_or(&""));
+            let domain = domain.trim_start_matches('.');
+            cookie.set_domain(if subdomains.eq_ignore_ascii_case("TRUE") { format!(".{}", domain) } else { domain.to_string() });
+            cookie.set_path(path.to_string());
+            cookie.set_secure(secure.eq_ignore_ascii_case("TRUE"));
+            cookie.set_http_only(http_only);
+            let expires = expires.parse::<u64>().map_err(|_| format!("invalid cookies.txt expires: {}", expires))?;
+            if expires != 0 { cookie.set_expires(format_http_date(UNIX_EPOCH + Duration::from_secs(expires))); }
+            if self.insert(cookie) { count += 1; }
+        }
+        Ok(count)
+    }
+
+    ///删除指定域名(不含子域名)下的cookie
+    pub fn remove_domain(&self, domain: impl AsRef<str>) {
+        let domain = domain.as_ref().trim_start_matches('.').to_lowercase();
+        if let Ok(mut inner) = self.inner.lock() { inner.cookies.retain(|x| x.cookie.domain() != domain); }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut inner) = self.inner.lock() { inner.cookies.clear(); }
+    }
+
+    ///包括已过期未清理的
+    pub fn len(&self) -> usize {
+        self.inner.lock().map(|x| x.cookies.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn is_secure(url: &Url) -> bool {
+    matches!(url.protocol(), Protocol::Https | Protocol::Wss)
+}
+
+///RFC 6265 5.1.3，ip只能完全相同
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain { return true; }
+    let is_ip = host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[');
+    !is_ip && host.ends_with(domain) && host[..host.len() - domain.len()].ends_with('.')
+}
+
+///RFC 6265 5.1.4
+fn default_path(path: &str) -> &str {
+    if !path.starts_with('/') { return "/"; }
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(pos) => &path[..pos],
+    }
+}
+
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    if path == cookie_path { return true; }
+    path.starts_with(cookie_path) && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::try_from(url).unwrap()
+    }
+
+    fn names(jar: &CookieJar, to: &str) -> Vec<String> {
+        jar.cookies(&url(to)).iter().map(|x| x.name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_cookie_jar() {
+        let jar = CookieJar::new();
+        let from = url("https://www.example.com/a/b");
+        for set_cookie in [
+            "host=1",
+            "sub=1; Domain=.example.com; Path=/",
+            "deep=1; path=/a/b/c",
+            "secure=1; secure",
+            "other=1; Domain=other.com",
+            "tld=1; Domain=com",
+            "gone=1; Max-Age=0",
+            "old=1; Expires=Sun, 06-Nov-94 08:49:37 GMT",
+        ] {
+            jar.store(&from, &Cookie::from_res(set_cookie).unwrap());
+        }
+        assert_eq!(names(&jar, "https://www.example.com/a/b/c/d"), ["deep", "host", "secure", "sub"]);
+        assert_eq!(names(&jar, "https://www.example.com/"), ["sub"]);
+        assert_eq!(names(&jar, "http://api.example.com/a/x"), ["sub"]);
+        assert!(names(&jar, "https://example.org/").is_empty());
+        //Max-Age=0删除已有的cookie
+        jar.store(&from, &Cookie::from_res("host=2; Max-Age=0").unwrap());
+        assert_eq!(names(&jar, "https://www.example.com/a/x"), ["secure", "sub"]);
+        assert!(!jar.store(&url("http://www.example.com/"), &Cookie::from_res("s=1; Secure").unwrap()));
+
+        //导出再导入
+        let text = jar.to_netscape();
+        assert!(text.contains(".example.com\tTRUE\t/\tFALSE\t0\tsub\t1"));
+        let copy = CookieJar::new();
+        assert_eq!(copy.load_netscape(&text).unwrap(), 3);
+        assert_eq!(names(&copy, "https://www.example.com/a/b/c"), ["deep", "secure", "sub"]);
+        let copy = CookieJar::new();
+        assert_eq!(copy.load_json(&jar.to_json()).unwrap(), 3);
+        assert_eq!(copy.to_netscape(), text);
+        //负数的age为已过期，不添加也不会删除其他cookie
+        let value = json::parse(r#"[{"name":"neg","value":"1","domain":"www.example.com","age":-5},{"name":"big","domain":".a.com","age":2147483647}]"#).unwrap();
+        assert_eq!(copy.load_json(&value).unwrap(), 1);
+        assert!(names(&copy, "https://www.example.com/").iter().all(|x| x != "neg"));
+        assert_eq!(copy.load_netscape("#HttpOnly_.a.com\tTRUE\t/\tTRUE\t4102444800\tk\tv\nb.com\tFALSE\t/\tFALSE\t1\told\tv").unwrap(), 1);
+        assert!(copy.all().iter().any(|x| x.http_only() && x.domain() == ".a.com" && x.expires() == "Fri, 01 Jan 2100 00:00:00 GMT"));
+    }
+}
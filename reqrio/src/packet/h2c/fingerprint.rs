@@ -0,0 +1,182 @@
+use super::frame::{Frame, FrameFlag, Setting};
+use crate::error::HlsResult;
+
+const PSEUDO_HEADERS: [&str; 4] = [":method", ":authority", ":scheme", ":path"];
+
+///#### h2优先级
+/// * weight为实际权重(1-256)，帧中的值为weight-1
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct H2Priority {
+    exclusive: bool,
+    depends_on: u32,
+    weight: u16,
+}
+
+impl H2Priority {
+    pub fn new(exclusive: bool, depends_on: u32, weight: u16) -> H2Priority {
+        H2Priority { exclusive, depends_on, weight: weight.clamp(1, 256) }
+    }
+
+    pub fn exclusive(&self) -> bool { self.exclusive }
+
+    pub fn depends_on(&self) -> u32 { self.depends_on }
+
+    pub fn weight(&self) -> u16 { self.weight }
+
+    fn apply(&self, frame: &mut Frame) {
+        frame.set_stream_dependency(self.depends_on, self.exclusive);
+        frame.set_weight((self.weight - 1) as u8);
+        frame.add_flag(FrameFlag::Priority);
+    }
+}
+
+///#### h2指纹
+/// * akamai格式: `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`，
+///   例如chrome: `1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p`
+/// * PRIORITY为0时不发送PRIORITY帧，否则为`stream_id:exclusive:depends_on:weight`，多个用`,`分隔
+/// * HEADERS帧中的优先级不在akamai格式中，通过`set_header_priority`设置
+#[derive(Clone, Debug)]
+pub struct H2Fingerprint {
+    settings: Vec<Setting>,
+    //连接前言中的WINDOW_UPDATE，0时不发送
+    window_update: u32,
+    //连接前言中的PRIORITY帧
+    priorities: Vec<(u32, H2Priority)>,
+    header_priority: Option<H2Priority>,
+    pseudo_header_order: Vec<&'static str>,
+}
+
+impl Default for H2Fingerprint {
+    fn default() -> Self {
+        H2Fingerprint {
+            settings: Setting::default(),
+            window_update: 0x00EF0001,
+            priorities: vec![],
+            header_priority: Some(H2Priority::new(true, 0, 147)),
+            pseudo_header_order: PSEUDO_HEADERS.to_vec(),
+        }
+    }
+}
+
+impl H2Fingerprint {
+    pub fn from_akamai(akamai: impl AsRef<str>) -> HlsResult<H2Fingerprint> {
+        let items = akamai.as_ref().trim().split('|').collect::<Vec<_>>();
+        if items.len() != 4 { return Err(format!("akamai fingerprint error: {}", akamai.as_ref()).into()); }
+        let settings = items[0].split(';').filter(|x| !x.is_empty()).map(|setting| {
+            let (k, v) = setting.split_once(':').ok_or(format!("akamai setting error: {}", setting))?;
+            Ok(Setting::new(k.trim().parse()?, v.trim().parse()?))
+        }).collect::<HlsResult<_>>()?;
+        let priorities = match items[2].trim() {
+            "0" | "" => vec![],
+            priorities => priorities.split(',').map(|priority| {
+                let values = priority.split(':').map(|x| x.trim().parse::<u32>()).collect::<Result<Vec<_>, _>>()?;
+                if values.len() != 4 || values[0] == 0 { return Err(format!("akamai priority error: {}", priority).into()); }
+                Ok((values[0], H2Priority::new(values[1] != 0, values[2], values[3] as u16)))
+            }).collect::<HlsResult<_>>()?,
+        };
+        let mut res = H2Fingerprint {
+            settings,
+            window_update: items[1].trim().parse()?,
+            priorities,
+            header_priority: H2Fingerprint::default().header_priority,
+            pseudo_header_order: vec![],
+        };
+        res.set_pseudo_header_order(items[3])?;
+        Ok(res)
+    }
+
+    pub fn akamai(&self) -> String {
+        let settings = self.settings.iter().map(|x| format!("{}:{}", x.id(), x.value())).collect::<Vec<_>>().join(";");
+        let priorities = match self.priorities.is_empty() {
+            true => "0".to_string(),
+            false => self.priorities.iter().map(|(id, x)| {
+                format!("{}:{}:{}:{}", id, x.exclusive as u8, x.depends_on, x.weight)
+            }).collect::<Vec<_>>().join(","),
+        };
+        let pseudo_header_order = self.pseudo_header_order.iter().map(|x| &x[1..2]).collect::<Vec<_>>().join(",");
+        format!("{}|{}|{}|{}", settings, self.window_update, priorities, pseudo_header_order)
+    }
+
+    ///SETTINGS按顺序发送: (id, value)
+    pub fn set_settings(&mut self, settings: Vec<(u16, u32)>) {
+        self.settings = settings.into_iter().map(|(k, v)| Setting::new(k, v)).collect();
+    }
+
+    pub fn window_update(&self) -> u32 { self.window_update }
+
+    pub fn set_window_update(&mut self, window_update: u32) { self.window_update = window_update & 0x7FFFFFFF; }
+
+    pub fn priorities(&self) -> &Vec<(u32, H2Priority)> { &self.priorities }
+
+    pub fn set_priorities(&mut self, priorities: Vec<(u32, H2Priority)>) { self.priorities = priorities; }
+
+    pub fn header_priority(&self) -> Option<&H2Priority> { self.header_priority.as_ref() }
+
+    ///None时HEADERS帧不带PRIORITY标志
+    pub fn set_header_priority(&mut self, priority: Option<H2Priority>) { self.header_priority = priority; }
+
+    pub fn pseudo_header_order(&self) -> &Vec<&'static str> { &self.pseudo_header_order }
+
+    ///akamai格式，例如: `m,a,s,p`，必须包含全部4个伪头部
+    pub fn set_pseudo_header_order(&mut self, order: impl AsRef<str>) -> HlsResult<()> {
+        let order = order.as_ref().split(',').map(|x| {
+            PSEUDO_HEADERS.iter().find(|p| p[1..2] == *x.trim()).cloned().ok_or(format!("akamai pseudo header error: {}", x))
+        }).collect::<Result<Vec<_>, _>>()?;
+        if order.len() != PSEUDO_HEADERS.len() || PSEUDO_HEADERS.iter().any(|x| !order.contains(x)) {
+            return Err(format!("akamai pseudo header order error: {:?}", order).into());
+        }
+        self.pseudo_header_order = order;
+        Ok(())
+    }
+
+    ///本端SETTINGS_INITIAL_WINDOW_SIZE
+    pub(crate) fn initial_window(&self) -> Option<u32> {
+        self.settings.iter().find_map(|x| match x {
+            Setting::SettingsInitialWindowSize(v) => Some(*v),
+            _ => None
+        })
+    }
+
+    ///连接前言中的帧: SETTINGS + WINDOW_UPDATE + PRIORITY
+    pub(crate) fn preface_frames(&self) -> Vec<Frame> {
+        let mut res = vec![Frame::new_settings(self.settings.clone())];
+        if self.window_update != 0 { res.push(Frame::new_window_update(0, self.window_update)); }
+        for (stream_id, priority) in &self.priorities {
+            res.push(Frame::new_priority(*stream_id, priority.exclusive, priority.depends_on, (priority.weight - 1) as u8));
+        }
+        res
+    }
+
+    ///PRIORITY帧使用过的stream_id之后的第一个客户端stream_id
+    pub(crate) fn first_stream_id(&self) -> u32 {
+        self.priorities.iter().map(|(id, _)| (*id + 1) | 1).max().unwrap_or(1)
+    }
+
+    pub(crate) fn apply_header_priority(&self, frame: &mut Frame) {
+        if let Some(priority) = &self.header_priority { priority.apply(frame); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::H2Fingerprint;
+
+    #[test]
+    fn test_akamai() {
+        let chrome = "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p";
+        let fingerprint = H2Fingerprint::from_akamai(chrome).unwrap();
+        assert_eq!(fingerprint.akamai(), chrome);
+        assert_eq!(fingerprint.initial_window(), Some(6291456));
+        assert_eq!(fingerprint.first_stream_id(), 1);
+        let firefox = "1:65536;4:131072;5:16384|12517377|3:0:0:201,5:0:0:101,7:0:0:1,9:0:7:1,11:0:3:1,13:0:0:241|m,p,a,s";
+        let fingerprint = H2Fingerprint::from_akamai(firefox).unwrap();
+        assert_eq!(fingerprint.akamai(), firefox);
+        assert_eq!(fingerprint.pseudo_header_order(), &vec![":method", ":path", ":authority", ":scheme"]);
+        assert_eq!(fingerprint.first_stream_id(), 15);
+        let frames = fingerprint.preface_frames();
+        assert_eq!(frames.len(), 8);
+        assert_eq!(frames[2].payload(), &vec![0, 0, 0, 0, 200]);
+        assert!(H2Fingerprint::from_akamai("1:65536|0|0|m,a,s").is_err());
+        assert!(H2Fingerprint::from_akamai("1:65536|0|m,a,s,p").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt::{Display, Formatter};
+
+///#### RST_STREAM/GOAWAY的错误码(RFC 9113 7)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum H2ErrorCode {
+    NoError,
+    ProtocolError,
+    InternalError,
+    FlowControlError,
+    SettingsTimeout,
+    StreamClosed,
+    FrameSizeError,
+    RefusedStream,
+    Cancel,
+    CompressionError,
+    ConnectError,
+    EnhanceYourCalm,
+    InadequateSecurity,
+    Http11Required,
+    Unknown(u32),
+}
+
+impl H2ErrorCode {
+    pub fn from_u32(code: u32) -> H2ErrorCode {
+        match code {
+            0x0 => H2ErrorCode::NoError,
+            0x1 => H2ErrorCode::ProtocolError,
+            0x2 => H2ErrorCode::InternalError,
+            0x3 => H2ErrorCode::FlowControlError,
+            0x4 => H2ErrorCode::SettingsTimeout,
+            0x5 => H2ErrorCode::StreamClosed,
+            0x6 => H2ErrorCode::FrameSizeError,
+            0x7 => H2ErrorCode::RefusedStream,
+            0x8 => H2ErrorCode::Cancel,
+            0x9 => H2ErrorCode::CompressionError,
+            0xa => H2ErrorCode::ConnectError,
+            0xb => H2ErrorCode::EnhanceYourCalm,
+            0xc => H2ErrorCode::InadequateSecurity,
+            0xd => H2ErrorCode::Http11Required,
+            _ => H2ErrorCode::Unknown(code),
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            H2ErrorCode::NoError => 0x0,
+            H2ErrorCode::ProtocolError => 0x1,
+            H2ErrorCode::InternalError => 0x2,
+            H2ErrorCode::FlowControlError => 0x3,
+            H2ErrorCode::SettingsTimeout => 0x4,
+            H2ErrorCode::StreamClosed => 0x5,
+            H2ErrorCode::FrameSizeError => 0x6,
+            H2ErrorCode::RefusedStream => 0x7,
+            H2ErrorCode::Cancel => 0x8,
+            H2ErrorCode::CompressionError => 0x9,
+            H2ErrorCode::ConnectError => 0xa,
+            H2ErrorCode::EnhanceYourCalm => 0xb,
+            H2ErrorCode::InadequateSecurity => 0xc,
+            H2ErrorCode::Http11Required => 0xd,
+            H2ErrorCode::Unknown(code) => *code,
+        }
+    }
+}
+
+impl Display for H2ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            H2ErrorCode::NoError => f.write_str("NO_ERROR"),
+            H2ErrorCode::ProtocolError => f.write_str("PROTOCOL_ERROR"),
+            H2ErrorCode::InternalError => f.write_str("INTERNAL_ERROR"),
+            H2ErrorCode::FlowControlError => f.write_str("FLOW_CONTROL_ERROR"),
+            H2ErrorCode::SettingsTimeout => f.write_str("SETTINGS_TIMEOUT"),
+            H2ErrorCode::StreamClosed => f.write_str("STREAM_CLOSED"),
+            H2ErrorCode::FrameSizeError => f.write_str("FRAME_SIZE_ERROR"),
+            H2ErrorCode::RefusedStream => f.write_str("REFUSED_STREAM"),
+            H2ErrorCode::Cancel => f.write_str("CANCEL"),
+            H2ErrorCode::CompressionError => f.write_str("COMPRESSION_ERROR"),
+            H2ErrorCode::ConnectError => f.write_str("CONNECT_ERROR"),
+            H2ErrorCode::EnhanceYourCalm => f.write_str("ENHANCE_YOUR_CALM"),
+            H2ErrorCode::InadequateSecurity => f.write_str("INADEQUATE_SECURITY"),
+            H2ErrorCode::Http11Required => f.write_str("HTTP_1_1_REQUIRED"),
+            H2ErrorCode::Unknown(code) => write!(f, "UNKNOWN({:#x})", code),
+        }
+    }
+}
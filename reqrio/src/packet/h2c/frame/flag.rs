@@ -1,3 +1,5 @@
+use super::typo::FrameType;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum FrameFlag {
     ACK,
@@ -8,13 +10,18 @@ pub enum FrameFlag {
 }
 
 impl FrameFlag {
-    pub fn from_u8(byte: u8) -> Vec<FrameFlag> {
+    ///0x1在SETTINGS/PING中是ACK，在其他帧中是END_STREAM
+    pub fn from_u8(frame_type: &FrameType, byte: u8) -> Vec<FrameFlag> {
         let mut res = vec![];
-        if byte & 1 == 1 { res.push(FrameFlag::EndStream); }
+        if byte & 1 == 1 {
+            match frame_type {
+                FrameType::Settings | FrameType::Ping => res.push(FrameFlag::ACK),
+                _ => res.push(FrameFlag::EndStream),
+            }
+        }
         if byte & 4 == 4 { res.push(FrameFlag::EndHeaders); }
         if byte & 8 == 8 { res.push(FrameFlag::Padded); }
         if byte & 32 == 32 { res.push(FrameFlag::Priority); }
-        if byte == 0 { res.push(FrameFlag::ACK); }
         res
     }
-}
\ No newline at end of file
+}
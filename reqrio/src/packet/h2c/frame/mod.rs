@@ -1,15 +1,16 @@
 pub use flag::FrameFlag;
 pub use typo::FrameType;
+pub use code::H2ErrorCode;
 
-use setting::Setting;
+pub use setting::Setting;
 mod setting;
 mod typo;
 mod flag;
+mod code;
 
 use std::fmt::{Debug, Display, Formatter};
-use std::ptr;
 use crate::Buffer;
-use crate::error::HlsResult;
+use crate::error::{HlsError, HlsResult};
 
 #[derive(Clone, Debug)]
 pub struct Frame {
@@ -46,40 +47,49 @@ impl Frame {
         }
     }
 
+    ///缓冲区中的数据不足一帧时返回Err，未知类型的帧直接丢弃(RFC 9113 4.1)
     pub fn from_bytes(buffer: &mut Buffer) -> HlsResult<Frame> {
-        if buffer.len() < 9 { return Err("byte not enough".into()); }
-        let len = u32::from_be_bytes([0, buffer[0], buffer[1], buffer[2]]) as usize;
-        let frame_type = FrameType::from_u8(buffer[3])?;
-        let flags = FrameFlag::from_u8(buffer[4]);
-        let mut stream_identifier = u32::from_be_bytes(buffer[5..9].try_into()?);
-        stream_identifier &= !2147483648;
-        if buffer.len() < 9 + len { return Err("byte not enough".into()); }
-        let (dependency, weight, payload_range) = if flags.contains(&FrameFlag::Priority) {
-            (u32::from_be_bytes(buffer[9..13].try_into()?), buffer[13], 14..9 + len)
-        } else {
-            (0, 0, 9..9 + len)
-        };
-        let mut payload: Vec<u8> = Vec::with_capacity(payload_range.end - payload_range.start);
-        unsafe {
-            let dst = payload.as_mut_ptr().add(0);
-            ptr::copy_nonoverlapping(buffer[payload_range].as_ptr(), dst, len);
-            payload.set_len(len);
+        loop {
+            if buffer.len() < 9 { return Err("byte not enough".into()); }
+            let len = u32::from_be_bytes([0, buffer[0], buffer[1], buffer[2]]) as usize;
+            if buffer.len() < 9 + len { return Err("byte not enough".into()); }
+            let frame = FrameType::from_u8(buffer[3]).ok().map(|x| Frame::parse(x, &buffer[..9 + len]));
+            buffer.copy_within(9 + len..buffer.len(), 0);
+            buffer.set_len(buffer.len() - len - 9);
+            if let Some(frame) = frame { return frame; }
         }
-        buffer.copy_within(9 + len..buffer.len(), 0);
-        buffer.set_len(buffer.len() - len - 9);
-
+    }
 
+    fn parse(frame_type: FrameType, bytes: &[u8]) -> HlsResult<Frame> {
+        let len = bytes.len() - 9;
+        let flags = FrameFlag::from_u8(&frame_type, bytes[4]);
+        let stream_identifier = u32::from_be_bytes(bytes[5..9].try_into()?) & 0x7FFFFFFF;
+        let mut payload = &bytes[9..];
+        //DATA/HEADERS的填充
+        if flags.contains(&FrameFlag::Padded) && (frame_type == FrameType::Data || frame_type == FrameType::Headers) {
+            let pad_len = payload.first().map(|x| *x as usize).unwrap_or(usize::MAX);
+            if pad_len >= payload.len() { return Err(HlsError::H2Protocol(H2ErrorCode::ProtocolError, "frame padding error".to_string())); }
+            payload = &payload[1..payload.len() - pad_len];
+        }
+        let (dependency, weight) = match frame_type {
+            FrameType::Headers if flags.contains(&FrameFlag::Priority) => {
+                if payload.len() < 5 { return Err(HlsError::H2Protocol(H2ErrorCode::FrameSizeError, "frame priority error".to_string())); }
+                let res = (u32::from_be_bytes(payload[..4].try_into()?), payload[4]);
+                payload = &payload[5..];
+                res
+            }
+            FrameType::Priority => {
+                if payload.len() != 5 { return Err(HlsError::H2Protocol(H2ErrorCode::FrameSizeError, "frame priority error".to_string())); }
+                (u32::from_be_bytes(payload[..4].try_into()?), payload[4])
+            }
+            _ => (0, 0)
+        };
         let mut settings = vec![];
         if frame_type == FrameType::Settings {
-            let mut cl = 0;
-            while cl < payload.len() {
-                let setting = Setting::from_bytes(&payload[cl..cl + 6])?;
-                settings.push(setting);
-                cl += 6;
+            for setting in payload.chunks_exact(6) {
+                settings.push(Setting::from_bytes(setting)?);
             }
         }
-
-
         Ok(Frame {
             len,
             frame_type,
@@ -87,22 +97,21 @@ impl Frame {
             stream_identifier,
             stream_dependency: dependency,
             weight,
-            payload,
+            payload: payload.to_vec(),
             settings,
         })
     }
 
-    pub fn to_bytes(mut self) -> Vec<u8> {
+    pub fn to_bytes(self) -> Vec<u8> {
         let mut res = (if self.flags.contains(&FrameFlag::Priority) { self.payload.len() + 5 } else { self.payload.len() } as u32).to_be_bytes()[1..].to_vec();
         res.push(self.frame_type.clone().to_u8());
         let mut flag = 0;
         let mut dep_bs = vec![];
-        if self.flags.contains(&FrameFlag::EndStream) { flag |= 1; }
+        if self.flags.contains(&FrameFlag::EndStream) || self.flags.contains(&FrameFlag::ACK) { flag |= 1; }
         if self.flags.contains(&FrameFlag::EndHeaders) { flag |= 4; }
         if self.flags.contains(&FrameFlag::Padded) { flag |= 8; }
         if self.flags.contains(&FrameFlag::Priority) {
             flag |= 32;
-            self.stream_dependency |= 2147483648;
             dep_bs = self.stream_dependency.to_be_bytes().to_vec();
 
             dep_bs.push(self.weight);
@@ -116,16 +125,119 @@ impl Frame {
     }
 
     pub fn window_update() -> Frame {
+        Frame::new_window_update(0, 0x00EF0001)
+    }
+
+    ///stream_id为0时是连接级的窗口
+    pub fn new_window_update(sid: u32, increment: u32) -> Frame {
         let mut frame = Frame::none_frame();
         frame.len = 4;
         frame.frame_type = FrameType::WindowUpdate;
+        frame.stream_identifier = sid;
+        frame.payload = (increment & 0x7FFFFFFF).to_be_bytes().to_vec();
+        frame
+    }
+
+    pub fn new_data(payload: Vec<u8>, sid: u32, end_stream: bool) -> Frame {
+        let mut frame = Frame::none_frame();
+        frame.len = payload.len();
+        frame.stream_identifier = sid;
+        frame.payload = payload;
+        if end_stream { frame.flags.push(FrameFlag::EndStream); }
+        frame
+    }
+
+    pub fn settings_ack() -> Frame {
+        let mut frame = Frame::none_frame();
+        frame.frame_type = FrameType::Settings;
         frame.flags.push(FrameFlag::ACK);
-        frame.payload = vec![0, 239, 0, 1];
         frame
     }
 
+    pub fn new_ping(payload: Vec<u8>, ack: bool) -> Frame {
+        let mut frame = Frame::none_frame();
+        frame.len = payload.len();
+        frame.frame_type = FrameType::Ping;
+        frame.payload = payload;
+        if ack { frame.flags.push(FrameFlag::ACK); }
+        frame
+    }
+
+    pub fn new_rst_stream(sid: u32, code: H2ErrorCode) -> Frame {
+        let mut frame = Frame::none_frame();
+        frame.len = 4;
+        frame.frame_type = FrameType::RstStream;
+        frame.stream_identifier = sid;
+        frame.payload = code.to_u32().to_be_bytes().to_vec();
+        frame
+    }
+
+    pub fn new_goaway(last_stream_id: u32, code: H2ErrorCode) -> Frame {
+        let mut frame = Frame::none_frame();
+        frame.len = 8;
+        frame.frame_type = FrameType::Goaway;
+        frame.payload = (last_stream_id & 0x7FFFFFFF).to_be_bytes().to_vec();
+        frame.payload.extend(code.to_u32().to_be_bytes());
+        frame
+    }
+
+    ///weight为帧中的值(实际权重-1)
+    pub fn new_priority(sid: u32, exclusive: bool, stream_dependency: u32, weight: u8) -> Frame {
+        let mut frame = Frame::none_frame();
+        frame.len = 5;
+        frame.frame_type = FrameType::Priority;
+        frame.stream_identifier = sid;
+        frame.set_stream_dependency(stream_dependency, exclusive);
+        frame.weight = weight;
+        frame.payload = frame.stream_dependency.to_be_bytes().to_vec();
+        frame.payload.push(weight);
+        frame
+    }
+
+    ///RST_STREAM/GOAWAY的错误码
+    pub fn error_code(&self) -> HlsResult<H2ErrorCode> {
+        let code = match self.frame_type {
+            FrameType::Goaway => self.payload.get(4..8),
+            _ => self.payload.get(..4),
+        }.ok_or("frame error code none")?;
+        Ok(H2ErrorCode::from_u32(u32::from_be_bytes(code.try_into()?)))
+    }
+
+    ///GOAWAY中对端最后处理的stream_id
+    pub fn last_stream_id(&self) -> HlsResult<u32> {
+        let last_stream_id = self.payload.get(..4).ok_or("goaway payload error")?;
+        Ok(u32::from_be_bytes(last_stream_id.try_into()?) & 0x7FFFFFFF)
+    }
+
+    ///HEADERS/PRIORITY中的依赖stream(不含exclusive位)
+    pub fn stream_dependency(&self) -> u32 {
+        self.stream_dependency & 0x7FFFFFFF
+    }
+
+    pub fn is_exclusive(&self) -> bool {
+        self.stream_dependency & 0x80000000 != 0
+    }
+
+    pub fn set_stream_dependency(&mut self, stream_dependency: u32, exclusive: bool) {
+        self.stream_dependency = stream_dependency & 0x7FFFFFFF;
+        if exclusive { self.stream_dependency |= 0x80000000; }
+    }
+
+    pub fn weight(&self) -> u8 {
+        self.weight
+    }
+
+    ///WINDOW_UPDATE的窗口增量
+    pub fn window_increment(&self) -> HlsResult<u32> {
+        let increment = self.payload.get(..4).ok_or("window update payload error")?;
+        Ok(u32::from_be_bytes(increment.try_into()?) & 0x7FFFFFFF)
+    }
+
     pub fn default_setting() -> Frame {
-        let settings = Setting::default();
+        Frame::new_settings(Setting::default())
+    }
+
+    pub fn new_settings(settings: Vec<Setting>) -> Frame {
         let mut payload = vec![];
         for setting in &settings {
             payload.extend(setting.to_bytes());
@@ -133,7 +245,7 @@ impl Frame {
         Frame {
             len: payload.len(),
             frame_type: FrameType::Settings,
-            flags: vec![FrameFlag::ACK],
+            flags: vec![],
             stream_identifier: 0,
             stream_dependency: 0,
             weight: 0,
@@ -189,6 +301,8 @@ impl Frame {
         &self.frame_type
     }
 
+    pub fn settings(&self) -> &Vec<Setting> { &self.settings }
+
     pub fn payload(&self) -> &Vec<u8> { &self.payload }
     pub fn to_payload(self) -> Vec<u8> { self.payload }
 
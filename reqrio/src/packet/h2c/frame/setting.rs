@@ -8,20 +8,50 @@ pub enum Setting {
     SettingsInitialWindowSize(u32),
     SettingsMaxFrameSize(u32),
     SettingsMaxHeaderListSize(u32),
+    ///未知的设置需要忽略(RFC 9113 6.5.2)
+    Unknown(u16, u32),
 }
 
 impl Setting {
     pub(crate) fn from_bytes(context: &[u8]) -> HlsResult<Setting> {
         let k = u16::from_be_bytes([context[0], context[1]]);
-        Ok(match k {
-            0x1 => Setting::SettingsHeaderTableSize(u32::from_be_bytes(context[2..6].try_into()?)),
-            0x2 => Setting::SettingsEnablePush(u32::from_be_bytes(context[2..6].try_into()?)),
-            0x3 => Setting::SettingsMaxConcurrentStreams(u32::from_be_bytes(context[2..6].try_into()?)),
-            0x4 => Setting::SettingsInitialWindowSize(u32::from_be_bytes(context[2..6].try_into()?)),
-            0x5 => Setting::SettingsMaxFrameSize(u32::from_be_bytes(context[2..6].try_into()?)),
-            0x6 => Setting::SettingsMaxHeaderListSize(u32::from_be_bytes(context[2..6].try_into()?)),
-            _ => return Err(format!("frame byte error: {:?}", context).into()),
-        })
+        Ok(Setting::new(k, u32::from_be_bytes(context[2..6].try_into()?)))
+    }
+
+    pub fn new(id: u16, value: u32) -> Setting {
+        match id {
+            0x1 => Setting::SettingsHeaderTableSize(value),
+            0x2 => Setting::SettingsEnablePush(value),
+            0x3 => Setting::SettingsMaxConcurrentStreams(value),
+            0x4 => Setting::SettingsInitialWindowSize(value),
+            0x5 => Setting::SettingsMaxFrameSize(value),
+            0x6 => Setting::SettingsMaxHeaderListSize(value),
+            _ => Setting::Unknown(id, value),
+        }
+    }
+
+    pub fn id(&self) -> u16 {
+        match self {
+            Setting::SettingsHeaderTableSize(_) => 0x1,
+            Setting::SettingsEnablePush(_) => 0x2,
+            Setting::SettingsMaxConcurrentStreams(_) => 0x3,
+            Setting::SettingsInitialWindowSize(_) => 0x4,
+            Setting::SettingsMaxFrameSize(_) => 0x5,
+            Setting::SettingsMaxHeaderListSize(_) => 0x6,
+            Setting::Unknown(id, _) => *id,
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        match self {
+            Setting::SettingsHeaderTableSize(v) => *v,
+            Setting::SettingsEnablePush(v) => *v,
+            Setting::SettingsMaxConcurrentStreams(v) => *v,
+            Setting::SettingsInitialWindowSize(v) => *v,
+            Setting::SettingsMaxFrameSize(v) => *v,
+            Setting::SettingsMaxHeaderListSize(v) => *v,
+            Setting::Unknown(_, v) => *v,
+        }
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -56,6 +86,11 @@ impl Setting {
                 res.extend(v.to_be_bytes());
                 res
             }
+            Setting::Unknown(k, v) => {
+                let mut res = k.to_be_bytes().to_vec();
+                res.extend(v.to_be_bytes());
+                res
+            }
         }
     }
 
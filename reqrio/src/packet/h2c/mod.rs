@@ -1,3 +1,7 @@
+pub use fingerprint::{H2Fingerprint, H2Priority};
 pub use frame::*;
+pub use session::{H2Event, H2Session};
 
-mod frame;
\ No newline at end of file
+mod fingerprint;
+mod frame;
+mod session;
@@ -0,0 +1,459 @@
+use super::fingerprint::H2Fingerprint;
+use super::frame::{Frame, FrameFlag, FrameType, H2ErrorCode, Setting};
+use crate::coder::HPackCoding;
+use crate::error::{HlsError, HlsResult};
+use crate::packet::{Header, HeaderKey};
+use std::collections::{BTreeMap, VecDeque};
+use std::mem;
+
+//RFC 9113 6.9.2
+const DEFAULT_WINDOW: i64 = 65535;
+const MAX_WINDOW: i64 = 0x7FFFFFFF;
+
+///#### h2响应事件
+pub enum H2Event {
+    ///响应头或trailer(1xx已忽略)，bool为END_STREAM
+    Headers(u32, Header, bool),
+    Data(u32, Vec<u8>, bool),
+    ///stream被对端RST_STREAM
+    Reset(u32, H2ErrorCode),
+    ///连接级: 对端GOAWAY，last_stream_id之后的stream没有被处理，也不能再新建stream
+    GoAway(u32, H2ErrorCode),
+}
+
+impl H2Event {
+    pub fn stream_id(&self) -> u32 {
+        match self {
+            H2Event::Headers(id, _, _) => *id,
+            H2Event::Data(id, _, _) => *id,
+            H2Event::Reset(id, _) => *id,
+            H2Event::GoAway(_, _) => 0,
+        }
+    }
+
+    pub fn is_end(&self) -> bool {
+        match self {
+            H2Event::Headers(_, _, end) => *end,
+            H2Event::Data(_, _, end) => *end,
+            H2Event::Reset(_, _) => true,
+            H2Event::GoAway(_, _) => false,
+        }
+    }
+}
+
+///单个stream的流量控制状态
+struct StreamState {
+    send_window: i64,
+    recv_window: i64,
+    //已处理但还没有WINDOW_UPDATE的字节数
+    recv_released: i64,
+    //因窗口不足还没有发送的请求体
+    pending: VecDeque<u8>,
+    //请求体是否已经全部交给session
+    body_ended: bool,
+    //请求体是否已经全部发送(END_STREAM)
+    local_ended: bool,
+    remote_ended: bool,
+}
+
+///#### h2连接状态(不含io)
+/// * 分配stream_id、编码请求，hpack是连接级的，必须按发送/接收顺序编解码
+/// * 接收的帧转为按stream_id区分的H2Event，需要回复的帧写入output，由调用方写出
+/// * 流量控制: 请求体按连接和stream的发送窗口分帧发送，窗口不足时等对端WINDOW_UPDATE；
+///   响应体由调用方处理后通过release_capacity归还接收窗口，消耗过半时发送WINDOW_UPDATE
+pub struct H2Session {
+    coder: HPackCoding,
+    next_stream_id: u32,
+    //对端SETTINGS_MAX_CONCURRENT_STREAMS，未设置时按100
+    max_concurrent_streams: u32,
+    //对端SETTINGS_INITIAL_WINDOW_SIZE
+    peer_initial_window: i64,
+    //对端SETTINGS_MAX_FRAME_SIZE
+    peer_max_frame_size: usize,
+    //本端SETTINGS_INITIAL_WINDOW_SIZE
+    local_initial_window: i64,
+    send_window: i64,
+    recv_window: i64,
+    //连接级接收窗口的大小(初始窗口+连接前言中的WINDOW_UPDATE)
+    local_conn_window: i64,
+    recv_released: i64,
+    streams: BTreeMap<u32, StreamState>,
+    //未接收完的头部块(HEADERS+CONTINUATION): stream_id, END_STREAM, 头部块
+    header_block: Option<(u32, bool, Vec<u8>)>,
+    //收到的GOAWAY: last_stream_id, 错误码
+    goaway: Option<(u32, H2ErrorCode)>,
+    fingerprint: H2Fingerprint,
+    output: Vec<u8>,
+}
+
+impl Default for H2Session {
+    fn default() -> Self {
+        H2Session::new()
+    }
+}
+
+impl H2Session {
+    pub fn new() -> H2Session {
+        H2Session::with_fingerprint(H2Fingerprint::default())
+    }
+
+    ///连接前言、HEADERS优先级按h2指纹发送
+    pub fn with_fingerprint(fingerprint: H2Fingerprint) -> H2Session {
+        let local_initial_window = fingerprint.initial_window().map(|x| x as i64).unwrap_or(DEFAULT_WINDOW);
+        let local_conn_window = fingerprint.window_update() as i64 + DEFAULT_WINDOW;
+        H2Session {
+            coder: HPackCoding::new(),
+            next_stream_id: fingerprint.first_stream_id(),
+            max_concurrent_streams: 100,
+            peer_initial_window: DEFAULT_WINDOW,
+            peer_max_frame_size: 16384,
+            local_initial_window,
+            send_window: DEFAULT_WINDOW,
+            recv_window: local_conn_window,
+            local_conn_window,
+            recv_released: 0,
+            streams: BTreeMap::new(),
+            header_block: None,
+            goaway: None,
+            fingerprint,
+            output: vec![],
+        }
+    }
+
+    ///连接前言: PRI + SETTINGS + WINDOW_UPDATE + PRIORITY
+    pub fn preface(&self) -> Vec<u8> {
+        let mut handshake = "PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".as_bytes().to_vec();
+        for frame in self.fingerprint.preface_frames() {
+            handshake.extend(frame.to_bytes());
+        }
+        handshake
+    }
+
+    pub fn fingerprint(&self) -> &H2Fingerprint {
+        &self.fingerprint
+    }
+
+    pub fn max_concurrent_streams(&self) -> u32 {
+        self.max_concurrent_streams
+    }
+
+    ///收到GOAWAY后不能再新建stream
+    pub fn is_going_away(&self) -> bool {
+        self.goaway.is_some()
+    }
+
+    ///编码请求，HEADERS和窗口允许的DATA帧写入output，返回stream_id
+    /// * end_stream为false时请求体还没有结束，后续通过send_data发送
+    pub fn open_stream(&mut self, headers: Vec<HeaderKey>, body: Vec<u8>, end_stream: bool) -> HlsResult<u32> {
+        if let Some((last_stream_id, code)) = &self.goaway { return Err(HlsError::GoAway(*last_stream_id, code.clone())); }
+        if self.next_stream_id > i32::MAX as u32 { return Err("h2 stream id exhausted".into()); }
+        let stream_id = self.next_stream_id;
+        let hdr_bs = self.coder.encode(headers)?;
+        let mut header_frame = Frame::new_header(hdr_bs, body.len(), stream_id);
+        if !end_stream { header_frame.set_flags(vec![FrameFlag::EndHeaders]); }
+        self.fingerprint.apply_header_priority(&mut header_frame);
+        self.output.extend(header_frame.to_bytes());
+        self.next_stream_id += 2;
+        self.streams.insert(stream_id, StreamState {
+            send_window: self.peer_initial_window,
+            recv_window: self.local_initial_window,
+            recv_released: 0,
+            body_ended: end_stream,
+            local_ended: end_stream && body.is_empty(),
+            pending: VecDeque::from(body),
+            remote_ended: false,
+        });
+        self.flush_pending();
+        Ok(stream_id)
+    }
+
+    ///继续发送请求体，需要等pending_len为0后再发送下一块，避免请求体堆积在内存中
+    pub fn send_data(&mut self, stream_id: u32, data: Vec<u8>, end_stream: bool) -> HlsResult<()> {
+        let stream = self.streams.get_mut(&stream_id).filter(|x| !x.body_ended).ok_or("h2 stream closed")?;
+        stream.pending.extend(data);
+        stream.body_ended = end_stream;
+        self.flush_pending();
+        Ok(())
+    }
+
+    ///因窗口不足还没有发送的请求体长度
+    pub fn pending_len(&self, stream_id: u32) -> usize {
+        self.streams.get(&stream_id).map(|x| x.pending.len()).unwrap_or(0)
+    }
+
+    ///对端已经结束或重置stream，不需要再发送请求体
+    pub fn is_remote_ended(&self, stream_id: u32) -> bool {
+        self.streams.get(&stream_id).map(|x| x.remote_ended).unwrap_or(true)
+    }
+
+    ///按发送窗口发送等待中的请求体
+    fn flush_pending(&mut self) {
+        for (stream_id, stream) in self.streams.iter_mut() {
+            while !stream.local_ended {
+                let window = self.send_window.min(stream.send_window).max(0) as usize;
+                let len = window.min(self.peer_max_frame_size).min(stream.pending.len());
+                //最后一个空的DATA帧不受窗口限制
+                let end_stream = stream.body_ended && len == stream.pending.len();
+                if len == 0 && !end_stream { break; }
+                let payload = stream.pending.drain(..len).collect::<Vec<_>>();
+                stream.local_ended = end_stream;
+                self.send_window -= len as i64;
+                stream.send_window -= len as i64;
+                self.output.extend(Frame::new_data(payload, *stream_id, stream.local_ended).to_bytes());
+            }
+        }
+        self.remove_closed();
+    }
+
+    fn remove_closed(&mut self) {
+        self.streams.retain(|_, x| !(x.local_ended && x.remote_ended));
+    }
+
+    ///取消stream(RST_STREAM)
+    pub fn reset_stream(&mut self, stream_id: u32, code: H2ErrorCode) {
+        if self.streams.remove(&stream_id).is_some() {
+            self.output.extend(Frame::new_rst_stream(stream_id, code).to_bytes());
+        }
+    }
+
+    ///响应体处理完后归还接收窗口
+    pub fn release_capacity(&mut self, stream_id: u32, len: usize) {
+        let len = len as i64;
+        self.recv_released += len;
+        if self.recv_released >= self.local_conn_window / 2 {
+            self.output.extend(Frame::new_window_update(0, self.recv_released as u32).to_bytes());
+            self.recv_window += self.recv_released;
+            self.recv_released = 0;
+        }
+        //已结束的stream不需要stream级的WINDOW_UPDATE
+        if let Some(stream) = self.streams.get_mut(&stream_id) && !stream.remote_ended {
+            stream.recv_released += len;
+            if stream.recv_released >= self.local_initial_window / 2 {
+                self.output.extend(Frame::new_window_update(stream_id, stream.recv_released as u32).to_bytes());
+                stream.recv_window += stream.recv_released;
+                stream.recv_released = 0;
+            }
+        }
+    }
+
+    ///#### 处理接收的帧，连接级的帧返回None
+    /// * 连接错误(H2Protocol)时在输出中加入GOAWAY，调用方需要先写出take_output再关闭连接
+    pub fn recv_frame(&mut self, frame: Frame) -> HlsResult<Option<H2Event>> {
+        let res = self.handle_frame(frame);
+        if let Err(HlsError::H2Protocol(code, _)) = &res && self.goaway.is_none() {
+            //客户端没有处理对端发起的stream，last_stream_id为0
+            self.output.extend(Frame::new_goaway(0, code.clone()).to_bytes());
+            self.goaway = Some((0, code.clone()));
+        }
+        res
+    }
+
+    fn handle_frame(&mut self, frame: Frame) -> HlsResult<Option<H2Event>> {
+        //头部块没有结束时只能收到同一个stream的CONTINUATION(RFC 9113 6.10)
+        if let Some((stream_id, _, _)) = &self.header_block && (frame.frame_type() != &FrameType::Continuation || frame.stream_identifier() != *stream_id) {
+            return Err(HlsError::H2Protocol(H2ErrorCode::ProtocolError, "expected CONTINUATION frame".to_string()));
+        }
+        match frame.frame_type() {
+            FrameType::Settings => {
+                //对端对本端SETTINGS的ACK不需要处理
+                if !frame.flags().contains(&FrameFlag::ACK) {
+                    for setting in frame.settings() {
+                        match setting {
+                            Setting::SettingsMaxConcurrentStreams(v) => self.max_concurrent_streams = *v,
+                            Setting::SettingsInitialWindowSize(v) => {
+                                if *v as i64 > MAX_WINDOW { return Err(HlsError::H2Protocol(H2ErrorCode::FlowControlError, "initial window size too large".to_string())); }
+                                //已有stream的发送窗口按差值调整
+                                let delta = *v as i64 - self.peer_initial_window;
+                                self.streams.values_mut().for_each(|x| x.send_window += delta);
+                                self.peer_initial_window = *v as i64;
+                            }
+                            Setting::SettingsMaxFrameSize(v) => self.peer_max_frame_size = *v as usize,
+                            _ => {}
+                        }
+                    }
+                    self.output.extend(Frame::settings_ack().to_bytes());
+                    self.flush_pending();
+                }
+                Ok(None)
+            }
+            FrameType::WindowUpdate => {
+                let increment = frame.window_increment()? as i64;
+                let window = match frame.stream_identifier() {
+                    0 => &mut self.send_window,
+                    stream_id => match self.streams.get_mut(&stream_id) {
+                        None => return Ok(None),
+                        Some(stream) => &mut stream.send_window,
+                    }
+                };
+                *window += increment;
+                if *window > MAX_WINDOW { return Err(HlsError::H2Protocol(H2ErrorCode::FlowControlError, "window overflow".to_string())); }
+                self.flush_pending();
+                Ok(None)
+            }
+            FrameType::Headers => {
+                let block = (frame.stream_identifier(), frame.flags().contains(&FrameFlag::EndStream), vec![]);
+                self.header_block = Some(block);
+                self.recv_header_block(frame)
+            }
+            FrameType::Continuation => self.recv_header_block(frame),
+            FrameType::Data => {
+                let stream_id = frame.stream_identifier();
+                let ended = frame.is_end_frame();
+                let len = frame.len() as i64;
+                self.recv_window -= len;
+                if self.recv_window < 0 { return Err(HlsError::H2Protocol(H2ErrorCode::FlowControlError, "connection window exceeded".to_string())); }
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.recv_window -= len;
+                    if stream.recv_window < 0 { return Err(HlsError::H2Protocol(H2ErrorCode::FlowControlError, "stream window exceeded".to_string())); }
+                    stream.remote_ended |= ended;
+                }
+                let payload = frame.to_payload();
+                //填充部分直接归还
+                self.release_capacity(stream_id, len as usize - payload.len());
+                self.remove_closed();
+                Ok(Some(H2Event::Data(stream_id, payload, ended)))
+            }
+            FrameType::Ping => {
+                if !frame.flags().contains(&FrameFlag::ACK) {
+                    self.output.extend(Frame::new_ping(frame.to_payload(), true).to_bytes());
+                }
+                Ok(None)
+            }
+            FrameType::RstStream => {
+                let stream_id = frame.stream_identifier();
+                self.streams.remove(&stream_id);
+                Ok(Some(H2Event::Reset(stream_id, frame.error_code()?)))
+            }
+            FrameType::Goaway => {
+                let last_stream_id = frame.last_stream_id()?;
+                let code = frame.error_code()?;
+                self.streams.retain(|id, _| *id <= last_stream_id);
+                self.goaway = Some((last_stream_id, code.clone()));
+                Ok(Some(H2Event::GoAway(last_stream_id, code)))
+            }
+            //客户端不需要按优先级调度响应
+            FrameType::Priority => Ok(None),
+            _ => Ok(None),
+        }
+    }
+
+    fn recv_header_block(&mut self, frame: Frame) -> HlsResult<Option<H2Event>> {
+        let end_headers = frame.flags().contains(&FrameFlag::EndHeaders);
+        match self.header_block.as_mut() {
+            Some((stream_id, _, block)) if *stream_id == frame.stream_identifier() => block.extend(frame.to_payload()),
+            _ => return Err(HlsError::H2Protocol(H2ErrorCode::ProtocolError, "header block stream id mismatch".to_string())),
+        }
+        if !end_headers { return Ok(None); }
+        let (stream_id, ended, mut block) = self.header_block.take().ok_or("h2 header block none")?;
+        let header = Header::parse_h2(self.coder.decode(&mut block)?)?;
+        if (100..200).contains(&header.status().status_num()) { return Ok(None); }
+        if ended && let Some(stream) = self.streams.get_mut(&stream_id) {
+            stream.remote_ended = true;
+            self.remove_closed();
+        }
+        Ok(Some(H2Event::Headers(stream_id, header, ended)))
+    }
+
+    ///需要写出的帧(HEADERS、DATA、SETTINGS ACK、WINDOW_UPDATE等)
+    pub fn take_output(&mut self) -> Vec<u8> {
+        mem::take(&mut self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::HlsError;
+    use crate::packet::{Frame, FrameType, H2ErrorCode, H2Event, H2Session};
+    use crate::Buffer;
+
+    fn frames(output: Vec<u8>) -> Vec<Frame> {
+        let mut buffer = Buffer::with_capacity(output.len());
+        buffer.unfilled_mut()[..output.len()].copy_from_slice(&output);
+        buffer.set_len(output.len());
+        let mut res = vec![];
+        while let Ok(frame) = Frame::from_bytes(&mut buffer) { res.push(frame); }
+        res
+    }
+
+    #[test]
+    fn test_flow_control() {
+        let mut session = H2Session::new();
+        //请求体超过默认窗口65535时等待WINDOW_UPDATE
+        let stream_id = session.open_stream(vec![], vec![1; 100000], true).unwrap();
+        let sent = frames(session.take_output());
+        assert_eq!(sent[0].frame_type(), &FrameType::Headers);
+        assert_eq!(sent[1..].iter().map(|x| x.len()).sum::<usize>(), 65535);
+        assert!(sent[1..].iter().all(|x| x.len() <= 16384 && !x.is_end_frame()));
+        session.recv_frame(Frame::new_window_update(stream_id, 50000)).unwrap();
+        assert!(session.take_output().is_empty());
+        session.recv_frame(Frame::new_window_update(0, 50000)).unwrap();
+        let sent = frames(session.take_output());
+        assert_eq!(sent.iter().map(|x| x.len()).sum::<usize>(), 100000 - 65535);
+        assert!(sent.last().unwrap().is_end_frame());
+        //响应体消耗过半后发送WINDOW_UPDATE
+        for _ in 0..500 {
+            let frame = frames(Frame::new_data(vec![0; 16384], stream_id, false).to_bytes()).remove(0);
+            session.recv_frame(frame).unwrap();
+            session.release_capacity(stream_id, 16384);
+        }
+        let updates = frames(session.take_output());
+        assert!(updates.iter().any(|x| x.frame_id() == 0));
+        assert!(updates.iter().any(|x| x.frame_id() == stream_id));
+        assert!(updates.iter().all(|x| x.frame_type() == &FrameType::WindowUpdate));
+    }
+
+    #[test]
+    fn test_control_frames() {
+        let mut session = H2Session::new();
+        let stream_id = session.open_stream(vec![], vec![], true).unwrap();
+        session.take_output();
+        let raw = [
+            //SETTINGS: MAX_CONCURRENT_STREAMS=2 + 未知设置
+            vec![0, 0, 12, 4, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0x99, 0, 0, 0, 1],
+            //未知帧类型直接跳过
+            vec![0, 0, 2, 0xEE, 0, 0, 0, 0, 0, 1, 2],
+            //PING
+            vec![0, 0, 8, 6, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8],
+            //带padding的DATA
+            vec![0, 0, 5, 0, 0x8, 0, 0, 0, 1, 2, b'o', b'k', 0, 0],
+            //RST_STREAM(REFUSED_STREAM)
+            vec![0, 0, 4, 3, 0, 0, 0, 0, 1, 0, 0, 0, 7],
+        ].concat();
+        let received = frames(raw);
+        assert_eq!(received.len(), 4);
+        let mut events = vec![];
+        let mut output = vec![];
+        for frame in received {
+            events.push(session.recv_frame(frame).unwrap());
+            output.extend(frames(session.take_output()));
+        }
+        assert_eq!(session.max_concurrent_streams(), 2);
+        assert!(output[0].frame_type() == &FrameType::Settings && output[0].len() == 0);
+        assert!(output[1].frame_type() == &FrameType::Ping && output[1].payload() == &vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(matches!(&events[2], Some(H2Event::Data(1, data, false)) if data == b"ok"));
+        assert!(matches!(events[3], Some(H2Event::Reset(1, H2ErrorCode::RefusedStream))));
+        //GOAWAY后不能再打开新的stream
+        let goaway = frames(Frame::new_goaway(stream_id, H2ErrorCode::NoError).to_bytes()).remove(0);
+        assert!(matches!(session.recv_frame(goaway).unwrap(), Some(H2Event::GoAway(1, H2ErrorCode::NoError))));
+        let error = session.open_stream(vec![], vec![], true).unwrap_err();
+        assert!(matches!(error, HlsError::GoAway(1, _)) && error.is_unprocessed());
+    }
+
+    #[test]
+    fn test_header_block_interleaved() {
+        let mut session = H2Session::new();
+        session.open_stream(vec![], vec![], true).unwrap();
+        session.open_stream(vec![], vec![], true).unwrap();
+        session.take_output();
+        //stream 1的HEADERS没有END_HEADERS，之后收到stream 3的HEADERS
+        let received = frames([vec![0, 0, 1, 1, 0, 0, 0, 0, 1, 0x88], vec![0, 0, 1, 1, 4, 0, 0, 0, 3, 0x88]].concat());
+        let mut received = received.into_iter();
+        assert!(session.recv_frame(received.next().unwrap()).unwrap().is_none());
+        let res = session.recv_frame(received.next().unwrap());
+        assert!(matches!(res, Err(HlsError::H2Protocol(H2ErrorCode::ProtocolError, _))));
+        let output = frames(session.take_output());
+        assert!(output.len() == 1 && output[0].frame_type() == &FrameType::Goaway);
+        assert!(matches!(output[0].error_code().unwrap(), H2ErrorCode::ProtocolError));
+        assert!(session.open_stream(vec![], vec![], true).is_err());
+    }
+}
@@ -1,6 +1,5 @@
-#[cfg(feature = "export")]
 use json::JsonValue;
-use crate::error::HlsResult;
+use crate::error::{HlsError, HlsResult};
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug)]
@@ -45,7 +44,8 @@ impl Cookie {
             "httponly" => self.http_only = true,
             "secure" => self.secure = true,
             "path" => self.path = v,
-            "max-age" => self.age = v.parse().unwrap_or(-1),
+            //小于等于0时立即过期，-1表示没有设置
+            "max-age" => if let Ok(age) = v.parse::<i64>() { self.age = age.clamp(0, i32::MAX as i64) as i32 },
             "domain" => self.domain = v,
             "expires" => self.expires = v,
             "samesite" => self.same_site = v,
@@ -61,22 +61,26 @@ impl Cookie {
         let mut res = vec![];
         let ck = ck.as_ref().replace("; ", ";");
         for cookie in ck.split(";") {
-            let mut items = cookie.split("=");
-            let mut cookie = Cookie::new();
-            cookie.name = items.next().ok_or("cooke name not found")?.to_string();
-            cookie.value = items.next().unwrap_or("").to_string();
-            res.push(cookie);
+            //值中可以有=
+            let (name, value) = cookie.split_once("=").unwrap_or((cookie, ""));
+            res.push(Cookie::new_cookie(name, value));
         }
         Ok(res)
     }
+
+    ///第一个`name=value`之后是属性(RFC 6265 5.2)
     pub fn from_res(ck: impl AsRef<str>) -> HlsResult<Cookie> {
-        let mut cookie = Cookie::new();
-        let ck = ck.as_ref().replace("; ", ";");
-        for item in ck.split(";").filter(|x| x != &"") {
-            let mut items = item.split("=");
-            let name = items.next().ok_or("cooke name not found")?;
-            let value = items.next().unwrap_or("");
-            cookie.insert(name, value.to_string());
+        let mut items = ck.as_ref().split(";");
+        let first = items.next().unwrap_or("");
+        //没有=时名字为空(RFC 6265bis)
+        let (name, value) = first.split_once("=").unwrap_or(("", first));
+        let mut cookie = Cookie::new_cookie(name.trim(), value.trim());
+        for item in items.map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let (name, value) = item.split_once("=").unwrap_or((item, ""));
+            match name.trim().to_lowercase().as_str() {
+                "httponly" | "secure" | "path" | "max-age" | "domain" | "expires" | "samesite" | "icpsp" => cookie.insert(name.trim(), value.trim().to_string()),
+                _ => {}
+            }
         }
         Ok(cookie)
     }
@@ -114,15 +118,43 @@ impl Cookie {
     pub fn set_expires(&mut self, expires: String) {
         self.expires = expires;
     }
+    pub fn set_secure(&mut self, secure: bool) {
+        self.secure = secure;
+    }
+    pub fn set_same_site(&mut self, same_site: String) {
+        self.same_site = same_site;
+    }
     pub fn value(&self) -> &str {
         &self.value
     }
     pub fn name(&self) -> &str {
         &self.name
     }
+    ///Max-Age，没有设置时为-1
+    pub fn age(&self) -> i32 {
+        self.age
+    }
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+    pub fn expires(&self) -> &str {
+        &self.expires
+    }
+    pub fn same_site(&self) -> &str {
+        &self.same_site
+    }
 }
 
-#[cfg(feature = "export")]
+///和serde的字段名一致
 impl From<Cookie> for JsonValue {
     fn from(cookie: Cookie) -> Self {
         json::object! {
@@ -140,6 +172,28 @@ impl From<Cookie> for JsonValue {
     }
 }
 
+///name以外的字段可以省略
+impl TryFrom<&JsonValue> for Cookie {
+    type Error = HlsError;
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let string = |k: &str| value[k].as_str().unwrap_or("").to_string();
+        let boolean = |k: &str| value[k].as_bool().unwrap_or(false);
+        Ok(Cookie {
+            name: value["name"].as_str()?.to_string(),
+            value: string("value"),
+            //-1为没有设置，其他负数和Set-Cookie的Max-Age一样视为0
+            age: value["age"].as_i32().map(|x| if x < -1 { 0 } else { x }).unwrap_or(-1),
+            domain: string("domain"),
+            path: string("path"),
+            http_only: boolean("http_only"),
+            secure: boolean("secure"),
+            expires: string("expires"),
+            same_site: string("same_site"),
+            icpsp: boolean("icpsp"),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::packet::http::cookie::Cookie;
@@ -12,6 +12,13 @@ pub enum Method {
     TRACH,
 }
 
+impl Method {
+    ///重复发送和发送一次的效果相同(RFC 9110 9.2.2)
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Method::POST | Method::CONNECT)
+    }
+}
+
 impl Display for Method {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -116,11 +116,19 @@ impl Header {
     }
 
     pub fn as_raw(&mut self, body_len: usize) -> HlsResult<Vec<String>> {
+        self.remove("transfer-encoding");
         self.set_content_length(body_len)?;
         Ok(self.raw())
     }
 
-    fn raw(&self) -> Vec<String> {
+    ///长度未知的请求体使用chunked
+    pub fn as_raw_chunked(&mut self) -> HlsResult<Vec<String>> {
+        self.remove("content-length");
+        self.insert("transfer-encoding", "chunked")?;
+        Ok(self.raw())
+    }
+
+    pub(crate) fn raw(&self) -> Vec<String> {
         let mut res = vec![];
         for key in &self.keys {
             if key.value().to_string() == "" { continue; }
@@ -152,7 +160,7 @@ impl Header {
         let res = res.into_iter().filter_map(|x| {
             if x.value().to_string() == "" {
                 None
-            } else if x.name_lower() == "connection" || x.name_lower() == "host" || x.name_lower() == "content-length" {
+            } else if x.name_lower() == "connection" || x.name_lower() == "host" || x.name_lower() == "content-length" || x.name_lower() == "transfer-encoding" {
                 None
             } else {
                 Some(x)
@@ -298,6 +306,16 @@ impl Header {
 
     pub fn is_empty(&self) -> bool { self.agreement == "" }
 
+    ///响应后连接是否可以复用: HTTP/1.0需要显式的keep-alive，其余版本默认复用
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.get("connection").and_then(|x| x.as_string()).map(|x| x.to_lowercase());
+        match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => self.agreement != "HTTP/1.0"
+        }
+    }
+
     pub fn content_encoding(&self) -> Option<&str> {
         self.get("content-encoding")?.as_string()
     }
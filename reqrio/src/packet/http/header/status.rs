@@ -13,6 +13,7 @@ pub enum HttpStatus {
     PartialContent = 206,
     Move = 301,
     Found = 302,
+    SeeOther = 303,
     NotModified = 304,
     TemporaryRedirect = 307,
     PermanentRedirect = 308,
@@ -20,6 +21,7 @@ pub enum HttpStatus {
     Unauthorized = 401,
     Forbidden = 403,
     NotFound = 404,
+    RequestTimeout = 408,
     ReqTooLarge = 413,
     Teapot = 418,
     TooManyRequests = 429,
@@ -49,6 +51,7 @@ impl TryFrom<i32> for HttpStatus {
             206 => Ok(HttpStatus::PartialContent),
             301 => Ok(HttpStatus::Move),
             302 => Ok(HttpStatus::Found),
+            303 => Ok(HttpStatus::SeeOther),
             304 => Ok(HttpStatus::NotModified),
             307 => Ok(HttpStatus::TemporaryRedirect),
             308 => Ok(HttpStatus::PermanentRedirect),
@@ -56,6 +59,7 @@ impl TryFrom<i32> for HttpStatus {
             401 => Ok(HttpStatus::Unauthorized),
             403 => Ok(HttpStatus::Forbidden),
             404 => Ok(HttpStatus::NotFound),
+            408 => Ok(HttpStatus::RequestTimeout),
             413 => Ok(HttpStatus::ReqTooLarge),
             418 => Ok(HttpStatus::Teapot),
             429 => Ok(HttpStatus::TooManyRequests),
@@ -82,6 +86,7 @@ impl Display for HttpStatus {
             HttpStatus::PartialContent => write!(f, "Partial Content"),
             HttpStatus::Move => f.write_str("Move"),
             HttpStatus::Found => f.write_str("Found"),
+            HttpStatus::SeeOther => f.write_str("See Other"),
             HttpStatus::NotModified => f.write_str("Not Modified"),
             HttpStatus::TemporaryRedirect => f.write_str("Temporary Redirect"),
             HttpStatus::PermanentRedirect => f.write_str("Permanent Redirect"),
@@ -89,6 +94,7 @@ impl Display for HttpStatus {
             HttpStatus::Unauthorized => f.write_str("Unauthorized"),
             HttpStatus::Forbidden => f.write_str("Forbidden"),
             HttpStatus::NotFound => f.write_str("Not Found"),
+            HttpStatus::RequestTimeout => f.write_str("Request Timeout"),
             HttpStatus::ReqTooLarge => f.write_str("Request Too Large"),
             HttpStatus::Teapot => f.write_str("Teapot"),
             HttpStatus::TooManyRequests => f.write_str("Too Many Requests"),
@@ -1,5 +1,7 @@
 pub use header::*;
 pub use response::{Response, Body};
+#[cfg(anys)]
+pub(crate) use response::BodyDecoder;
 pub use content_type::*;
 pub use cookie::Cookie;
 
@@ -7,4 +9,5 @@ pub use cookie::Cookie;
 mod header;
 mod content_type;
 mod cookie;
-mod response;
\ No newline at end of file
+mod response;
+mod parser;
\ No newline at end of file
@@ -0,0 +1,183 @@
+use crate::coder::ChunkDecoder;
+use crate::error::{HlsError, HlsResult};
+use crate::packet::{Header, Method};
+use std::mem;
+
+///响应头的最大长度，超过时不再等待\r\n\r\n
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+enum H1State {
+    //状态行和响应头，1xx的中间响应之后回到这里
+    Head,
+    Chunked(ChunkDecoder),
+    Length(usize),
+    //没有content-length和chunked，读到连接关闭
+    Close,
+    Done,
+}
+
+///#### http/1.1响应增量解析
+/// * 数据可以在任意位置被截断，ScReq和AcReq共用
+/// * 跳过1xx的中间响应(101除外)，HEAD请求、1xx、204、304没有响应体，101之后的数据属于升级后的协议
+/// * 响应体按chunked(支持扩展和trailer)、content-length、连接关闭的顺序确定分帧
+pub(crate) struct H1Parser {
+    head_request: bool,
+    state: H1State,
+    head: Vec<u8>,
+    header: Option<Header>,
+    //101之后读到的数据
+    upgraded: Option<Vec<u8>>,
+}
+
+impl H1Parser {
+    pub(crate) fn new(method: &Method) -> H1Parser {
+        H1Parser {
+            head_request: matches!(method, Method::HEAD),
+            state: H1State::Head,
+            head: vec![],
+            header: None,
+            upgraded: None,
+        }
+    }
+
+    #[cfg(anys)]
+    pub(crate) fn header(&self) -> Option<&Header> { self.header.as_ref() }
+
+    ///取出最终的响应头，只会返回一次
+    pub(crate) fn take_header(&mut self) -> Option<Header> { self.header.take() }
+
+    ///取出101之后读到的数据，不是101时为空
+    #[cfg(anys)]
+    pub(crate) fn take_upgraded(&mut self) -> Vec<u8> {
+        self.upgraded.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        matches!(self.state, H1State::Done)
+    }
+
+    ///返回去掉分帧后的响应体，响应结束之后的数据被忽略(101之后的数据通过take_upgraded取出)
+    pub(crate) fn feed(&mut self, data: &[u8]) -> HlsResult<Vec<u8>> {
+        match &mut self.state {
+            H1State::Head => {
+                //\r\n\r\n可能被截断在上次的数据末尾
+                let start = self.head.len().saturating_sub(3);
+                self.head.extend_from_slice(data);
+                let pos = self.head[start..].windows(4).position(|w| w == b"\r\n\r\n");
+                if pos.map(|x| start + x).unwrap_or(self.head.len()) > MAX_HEAD_SIZE { return Err(HlsError::InvalidHeadSize); }
+                let Some(pos) = pos else { return Ok(vec![]); };
+                let rest = self.head.split_off(start + pos + 4);
+                let mut head = mem::take(&mut self.head);
+                head.truncate(head.len() - 4);
+                self.parse_head(head)?;
+                //剩下的是下一个响应头或响应体
+                match rest.is_empty() {
+                    true => Ok(vec![]),
+                    false => self.feed(&rest),
+                }
+            }
+            H1State::Chunked(chunk) => {
+                let res = chunk.decode(data)?;
+                if chunk.is_done() { self.state = H1State::Done; }
+                Ok(res)
+            }
+            H1State::Length(remain) => {
+                let len = data.len().min(*remain);
+                *remain -= len;
+                if *remain == 0 { self.state = H1State::Done; }
+                Ok(data[..len].to_vec())
+            }
+            H1State::Close => Ok(data.to_vec()),
+            H1State::Done => {
+                if let Some(upgraded) = &mut self.upgraded { upgraded.extend_from_slice(data); }
+                Ok(vec![])
+            }
+        }
+    }
+
+    ///连接已关闭，只有读到连接关闭的响应体可以正常结束
+    pub(crate) fn close(&mut self) -> HlsResult<()> {
+        match self.state {
+            H1State::Close | H1State::Done => {
+                self.state = H1State::Done;
+                Ok(())
+            }
+            H1State::Head => Err(HlsError::PeerClosedConnection),
+            _ => Err(HlsError::Protocol("response body incomplete".to_string())),
+        }
+    }
+
+    fn parse_head(&mut self, head: Vec<u8>) -> HlsResult<()> {
+        let head = String::from_utf8(head).map_err(|e| HlsError::Protocol(e.to_string()))?;
+        //100 Continue、103 Early Hints等中间响应，继续读取最终的响应
+        let status = head.split(' ').nth(1).and_then(|x| x.trim().parse::<i32>().ok()).unwrap_or(0);
+        if (100..200).contains(&status) && status != 101 { return Ok(()); }
+        let header = Header::try_from(head).map_err(|e| HlsError::Protocol(e.to_string()))?;
+        let encoding = header.get("transfer-encoding").and_then(|x| x.as_string()).map(|x| x.to_lowercase());
+        //chunked必须是最后一个传输编码，否则只能读到连接关闭
+        let chunked = encoding.as_ref().map(|x| x.rsplit(',').next().unwrap_or("").trim() == "chunked");
+        self.state = match (chunked, header.content_length()) {
+            _ if self.head_request || status == 101 || status == 204 || status == 304 => H1State::Done,
+            (Some(true), _) => H1State::Chunked(ChunkDecoder::new()),
+            (Some(false), _) => H1State::Close,
+            (None, Some(0)) => H1State::Done,
+            (None, Some(len)) => H1State::Length(len),
+            (None, None) => H1State::Close,
+        };
+        if status == 101 { self.upgraded = Some(vec![]); }
+        self.header = Some(header);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::H1Parser;
+    use crate::error::HlsError;
+    use crate::packet::Method;
+
+    fn parse(method: Method, raw: &[u8], step: usize) -> (H1Parser, Vec<u8>) {
+        let mut parser = H1Parser::new(&method);
+        let mut body = vec![];
+        for data in raw.chunks(step) { body.extend(parser.feed(data).unwrap()); }
+        (parser, body)
+    }
+
+    #[test]
+    fn test_h1_parser() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: now\r\n\r\nHTTP/1.1";
+        for step in [1, 3, raw.len()] {
+            let (mut parser, body) = parse(Method::GET, raw, step);
+            assert!(parser.is_done());
+            assert_eq!(body, b"Wikipedia");
+            assert_eq!(parser.take_header().unwrap().status().status_num(), 200);
+        }
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n";
+        let (parser, body) = parse(Method::HEAD, raw, 4);
+        assert!(parser.is_done() && body.is_empty());
+        let (parser, _) = parse(Method::GET, b"HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n", 7);
+        assert!(parser.is_done());
+        let (parser, body) = parse(Method::GET, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello world", 2);
+        assert!(parser.is_done());
+        assert_eq!(body, b"hello");
+        //读到连接关闭
+        let (mut parser, body) = parse(Method::GET, b"HTTP/1.1 200 OK\r\nConnection: keep-alive\r\n\r\nhello", 5);
+        assert!(!parser.is_done());
+        assert!(parser.close().is_ok());
+        assert_eq!(body, b"hello");
+        let (mut parser, _) = parse(Method::GET, b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello", 5);
+        assert!(parser.close().is_err());
+        //101之后的数据属于升级后的协议
+        #[cfg(anys)]
+        for step in [1, 3, 100] {
+            let (mut parser, body) = parse(Method::GET, b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n\x81\x02hi", step);
+            assert!(parser.is_done() && body.is_empty());
+            assert_eq!(parser.take_upgraded(), b"\x81\x02hi");
+        }
+        //响应头超过长度限制
+        let mut parser = H1Parser::new(&Method::GET);
+        assert!(parser.feed(b"HTTP/1.1 200 OK\r\n").unwrap().is_empty());
+        let res = (0..10000).map(|_| parser.feed(b"X-Pad: 0\r\n")).find(|x| x.is_err());
+        assert!(matches!(res, Some(Err(HlsError::InvalidHeadSize))));
+    }
+}
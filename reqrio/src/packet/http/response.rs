@@ -1,11 +1,13 @@
 use crate::buffer::Buffer;
-use crate::coder::HackDecode;
+use crate::coder::{HackDecode, StreamDecoder};
 use crate::error::HlsResult;
 use crate::packet::h2c::{FrameFlag, FrameType};
-use crate::packet::{Frame, Header};
-use crate::{coder, HeaderValue};
+use crate::packet::{Frame, Header, Method};
+use crate::HeaderValue;
+use crate::redirect::Redirect;
+use super::parser::H1Parser;
 use json::JsonValue;
-use std::{mem, ptr};
+use std::mem;
 pub enum Body {
     Raw(Vec<u8>),
     Decoded(Vec<u8>),
@@ -25,17 +27,9 @@ impl Body {
 
     fn decompress(&mut self, encoding: Option<&HeaderValue>) -> HlsResult<()> {
         if let Body::Raw(raw) = self {
-            let decoded = if let Some(encoding) = encoding {
-                match encoding.as_string().unwrap_or("") {
-                    "gzip" => coder::gzip_decode(mem::take(raw))?,
-                    "deflate" => coder::deflate_decode(mem::take(raw))?,
-                    "br" => coder::br_decode(mem::take(raw))?,
-                    "zstd" => coder::zstd_decode(mem::take(raw))?,
-                    _ => mem::take(raw),
-                }
-            } else {
-                mem::take(raw)
-            };
+            let mut decoder = StreamDecoder::new(encoding.and_then(|x| x.as_string()).unwrap_or(""))?;
+            let mut decoded = decoder.decode(&mem::take(raw))?;
+            decoded.extend(decoder.finish()?);
             *self = Body::Decoded(decoded);
         }
         Ok(())
@@ -93,59 +87,116 @@ impl Body {
     }
 }
 
+///#### 响应体增量解码
+/// * http/1.1由H1Parser解析响应头并去掉分帧，再按content-encoding解压，每次只处理新收到的数据
+/// * h2由END_STREAM结束，只需要解压
+#[cfg(anys)]
+pub(crate) struct BodyDecoder {
+    //h2时为None
+    parser: Option<H1Parser>,
+    //http/1.1在响应头解析完成后创建
+    decoder: Option<StreamDecoder>,
+    ended: bool,
+}
+
+#[cfg(anys)]
+impl BodyDecoder {
+    pub(crate) fn new_h1(method: &Method) -> BodyDecoder {
+        BodyDecoder { parser: Some(H1Parser::new(method)), decoder: None, ended: false }
+    }
+
+    pub(crate) fn new_h2(header: &Header) -> HlsResult<BodyDecoder> {
+        Ok(BodyDecoder { parser: None, decoder: Some(BodyDecoder::new_decoder(header)?), ended: false })
+    }
+
+    fn new_decoder(header: &Header) -> HlsResult<StreamDecoder> {
+        StreamDecoder::new(header.get("content-encoding").and_then(|x| x.as_string()).unwrap_or(""))
+    }
+
+    pub(crate) fn is_ended(&self) -> bool { self.ended }
+
+    ///http/1.1的101之后读到的数据
+    pub(crate) fn take_upgraded(&mut self) -> Vec<u8> {
+        self.parser.as_mut().map(|x| x.take_upgraded()).unwrap_or_default()
+    }
+
+    ///http/1.1的最终响应头，只会返回一次
+    pub(crate) fn take_header(&mut self) -> Option<Header> {
+        self.parser.as_mut()?.take_header()
+    }
+
+    ///返回解码后的数据，分帧结束时同时结束解压
+    pub(crate) fn decode(&mut self, data: &[u8]) -> HlsResult<Vec<u8>> {
+        if self.ended { return Ok(vec![]); }
+        let (data, ended) = match &mut self.parser {
+            Some(parser) => {
+                let data = parser.feed(data)?;
+                if self.decoder.is_none() && let Some(header) = parser.header() {
+                    self.decoder = Some(BodyDecoder::new_decoder(header)?);
+                }
+                (data, parser.is_done())
+            }
+            None => (data.to_vec(), false),
+        };
+        let Some(decoder) = &mut self.decoder else { return Ok(vec![]); };
+        let mut res = decoder.decode(&data)?;
+        if ended {
+            self.ended = true;
+            res.extend(decoder.finish()?);
+        }
+        Ok(res)
+    }
+
+    ///连接关闭或收到END_STREAM，http/1.1除读到连接关闭的响应体外不完整时返回错误
+    pub(crate) fn finish(&mut self) -> HlsResult<Vec<u8>> {
+        if self.ended { return Ok(vec![]); }
+        if let Some(parser) = &mut self.parser { parser.close()?; }
+        self.ended = true;
+        match &mut self.decoder {
+            Some(decoder) => decoder.finish(),
+            None => Ok(vec![]),
+        }
+    }
+}
+
 pub struct Response {
     header: Header,
     body: Body,
     raw: Vec<u8>,
     frames: Vec<Frame>,
+    //http/1.1的响应解析
+    parser: H1Parser,
+    history: Vec<Redirect>,
 }
 
 impl Response {
     pub fn new() -> Response {
+        Response::new_h1(&Method::GET)
+    }
+
+    ///HEAD请求的响应没有响应体，需要知道请求方法
+    pub fn new_h1(method: &Method) -> Response {
         Response {
             header: Header::new_res(),
             body: Body::Raw(Vec::new()),
             raw: Vec::new(),
             frames: vec![],
+            parser: H1Parser::new(method),
+            history: vec![],
         }
     }
 
-    fn check_status(&self) -> Option<bool> {
-        let chucked = self.header.get("transfer-encoding");
-        if let Some(chucked) = chucked {
-            if chucked.as_string()? != "chunked" {
-                println!("have transfer-encoding, but unknow-{}", chucked.as_string()?);
-                return None;
-            }
-            if self.raw.ends_with(&[48, 13, 10, 13, 10]) { return Some(true); }
-            None
-        } else {
-            let len = self.header.content_length().unwrap_or(0);
-            if self.raw.len() >= len { Some(true) } else { None }
-        }
+    ///写入http/1.1的响应数据，raw中保存去掉chunked后的响应体，返回响应是否已完整
+    pub fn extend(&mut self, buffer: &Buffer) -> HlsResult<bool> {
+        let body = self.parser.feed(buffer.filled())?;
+        if let Some(header) = self.parser.take_header() { self.header = header; }
+        self.raw.extend(body);
+        Ok(self.parser.is_done())
     }
 
-    pub fn extend(&mut self, buffer: &Buffer) -> HlsResult<bool> {
-        self.raw.reserve(buffer.len());
-        unsafe {
-            let dst = self.raw.as_mut_ptr().add(self.raw.len());
-            ptr::copy_nonoverlapping(buffer.filled().as_ptr(), dst, buffer.len());
-            self.raw.set_len(self.raw.len() + buffer.len());
-        }
-        match self.header.is_empty() {
-            true => {
-                let pos = self.raw.windows(4).position(|w| w == b"\r\n\r\n");
-                if let Some(pos) = pos {
-                    let hdr_bs = self.raw.drain(..pos).collect();
-                    let hdr_str = String::from_utf8(hdr_bs)?;
-                    // println!("{}", hdr_str);
-                    self.header = Header::try_from(hdr_str)?;
-                    self.raw.drain(..4);
-                    Ok(self.check_status().unwrap_or(false))
-                } else { Ok(false) }
-            }
-            false => Ok(self.check_status().unwrap_or(false))
-        }
+    ///http/1.1连接已关闭，没有content-length和chunked的响应体到此结束，其余情况不完整时返回错误
+    pub fn finish(&mut self) -> HlsResult<()> {
+        self.parser.close()
     }
 
     pub fn extend_frame(&mut self, frame: Frame, hpack_coding: &mut HackDecode) -> HlsResult<bool> {
@@ -178,6 +229,12 @@ impl Response {
 
     pub fn header_mut(&mut self) -> &mut Header { &mut self.header }
 
+    ///跟随过的重定向，按先后顺序
+    pub fn history(&self) -> &[Redirect] { &self.history }
+
+    #[cfg(anys)]
+    pub(crate) fn set_history(&mut self, history: Vec<Redirect>) { self.history = history; }
+
     pub fn raw_body(&self) -> &[u8] { &self.raw }
 
     pub fn raw_string(&self) -> String {
@@ -190,12 +247,7 @@ impl Response {
 
     pub fn decode_body(&mut self) -> HlsResult<&mut Body> {
         if !self.body.is_raw() { return Ok(&mut self.body); }
-        let chucked = self.header.get("transfer-encoding");
-        if let Some(chucked) = chucked && chucked.as_string().unwrap_or("") == "chunked" {
-            self.body.extend(coder::chunk_decode(mem::take(&mut self.raw))?);
-        } else {
-            self.body.extend(mem::take(&mut self.raw));
-        }
+        self.body.extend(mem::take(&mut self.raw));
         let encoding = self.header.get("content-encoding");
         self.body.decompress(encoding)?;
         Ok(&mut self.body)
@@ -0,0 +1,246 @@
+use crate::error::HlsResult;
+#[cfg(sync)]
+use crate::packet::H2Session;
+#[cfg(aync)]
+use crate::stream::H2Conn;
+use crate::stream::{Proxy, Stream};
+use crate::url::{Addr, Protocol, Url};
+use crate::ALPN;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+///#### 连接池的key
+/// * 协议、域名、端口、代理、ALPN、指纹(ja3+是否校验证书+根证书)都一致时才复用连接
+/// * 同步和异步的连接不能互相复用
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PoolKey {
+    protocol: Protocol,
+    addr: Addr,
+    proxy: String,
+    alpn: ALPN,
+    fingerprint: String,
+    sync: bool,
+}
+
+impl PoolKey {
+    pub fn new(url: &Url, proxy: &Proxy, alpn: &ALPN, fingerprint: String, sync: bool) -> PoolKey {
+        PoolKey {
+            protocol: url.protocol().clone(),
+            addr: url.addr().clone(),
+            proxy: proxy.to_string(),
+            alpn: alpn.clone(),
+            fingerprint,
+            sync,
+        }
+    }
+}
+
+///独占的连接及其h2状态(hpack动态表、stream_id)
+pub(crate) struct PoolConn {
+    pub stream: Stream,
+    #[cfg(sync)]
+    pub h2: Option<H2Session>,
+}
+
+struct IdleConn {
+    conn: PoolConn,
+    idle_at: Instant,
+}
+
+struct PoolInner {
+    idle: HashMap<PoolKey, Vec<IdleConn>>,
+    //异步的h2连接可以多路复用，取出时不移除
+    #[cfg(aync)]
+    h2: HashMap<PoolKey, (H2Conn, Instant)>,
+    max_idle_per_host: usize,
+    max_idle: usize,
+    idle_timeout: Duration,
+}
+
+impl PoolInner {
+    fn idle_count(&self) -> usize {
+        self.idle.values().map(|x| x.len()).sum()
+    }
+
+    fn remove_expired(&mut self) {
+        let idle_timeout = self.idle_timeout;
+        self.idle.retain(|_, conns| {
+            conns.retain(|x| x.idle_at.elapsed() < idle_timeout);
+            !conns.is_empty()
+        });
+        #[cfg(aync)]
+        self.h2.retain(|_, (conn, used_at)| !conn.is_closed() && used_at.elapsed() < idle_timeout);
+    }
+
+    ///超出总数限制时移除最早空闲的连接
+    fn remove_oldest(&mut self) {
+        let oldest = self.idle.iter().filter_map(|(k, v)| v.first().map(|x| (k.clone(), x.idle_at))).min_by_key(|(_, t)| *t);
+        if let Some((key, _)) = oldest && let Some(conns) = self.idle.get_mut(&key) {
+            conns.remove(0);
+            if conns.is_empty() { self.idle.remove(&key); }
+        }
+    }
+}
+
+///#### 连接池
+/// * 多个ScReq/AcReq可以共享同一个连接池(clone后共享)，切换域名时优先复用空闲的keep-alive连接
+/// * 请求结束(切换域名或drop)后连接放回连接池
+/// * AcReq的h2连接由多个AcReq同时使用(多路复用)
+/// ```rust,no_run
+/// use reqrio::{Pool, ReqExt, ScReq};
+/// let pool = Pool::new().with_max_idle_per_host(4);
+/// let mut req = ScReq::new().with_pool(pool.clone());
+/// req.set_url("https://www.baidu.com").unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Mutex<PoolInner>>,
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Pool::new()
+    }
+}
+
+impl Pool {
+    ///默认每个host最多8个空闲连接，总共最多64个，空闲90秒后关闭
+    pub fn new() -> Pool {
+        Pool {
+            inner: Arc::new(Mutex::new(PoolInner {
+                idle: HashMap::new(),
+                #[cfg(aync)]
+                h2: HashMap::new(),
+                max_idle_per_host: 8,
+                max_idle: 64,
+                idle_timeout: Duration::from_secs(90),
+            }))
+        }
+    }
+
+    pub fn with_max_idle_per_host(self, max: usize) -> Self {
+        self.set_max_idle_per_host(max);
+        self
+    }
+
+    pub fn set_max_idle_per_host(&self, max: usize) {
+        if let Ok(mut inner) = self.inner.lock() { inner.max_idle_per_host = max; }
+    }
+
+    pub fn with_max_idle(self, max: usize) -> Self {
+        self.set_max_idle(max);
+        self
+    }
+
+    pub fn set_max_idle(&self, max: usize) {
+        if let Ok(mut inner) = self.inner.lock() { inner.max_idle = max; }
+    }
+
+    pub fn with_idle_timeout(self, timeout: Duration) -> Self {
+        self.set_idle_timeout(timeout);
+        self
+    }
+
+    pub fn set_idle_timeout(&self, timeout: Duration) {
+        if let Ok(mut inner) = self.inner.lock() { inner.idle_timeout = timeout; }
+    }
+
+    ///当前空闲连接数(包括已超时未清理的)，不包括共享的h2连接
+    pub fn idle_count(&self) -> usize {
+        self.inner.lock().map(|x| x.idle_count()).unwrap_or(0)
+    }
+
+    ///关闭所有空闲连接，共享的h2连接在使用者drop后关闭
+    pub fn clear(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.idle.clear();
+            #[cfg(aync)]
+            inner.h2.clear();
+        }
+    }
+
+    ///取出最近放回的空闲连接
+    pub(crate) fn take(&self, key: &PoolKey) -> HlsResult<Option<PoolConn>> {
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        inner.remove_expired();
+        let conn = inner.idle.get_mut(key).and_then(|x| x.pop());
+        if inner.idle.get(key).is_some_and(|x| x.is_empty()) { inner.idle.remove(key); }
+        Ok(conn.map(|x| x.conn))
+    }
+
+    pub(crate) fn put(&self, key: PoolKey, conn: PoolConn) -> HlsResult<()> {
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        inner.remove_expired();
+        if inner.max_idle_per_host == 0 || inner.max_idle == 0 { return Ok(()); }
+        let max_idle_per_host = inner.max_idle_per_host;
+        let conns = inner.idle.entry(key).or_default();
+        if conns.len() >= max_idle_per_host { conns.remove(0); }
+        conns.push(IdleConn { conn, idle_at: Instant::now() });
+        while inner.idle_count() > inner.max_idle {
+            inner.remove_oldest();
+        }
+        Ok(())
+    }
+
+    ///共享的h2连接
+    #[cfg(aync)]
+    pub(crate) fn take_h2(&self, key: &PoolKey) -> HlsResult<Option<H2Conn>> {
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        inner.remove_expired();
+        Ok(inner.h2.get_mut(key).map(|(conn, used_at)| {
+            *used_at = Instant::now();
+            conn.clone()
+        }))
+    }
+
+    #[cfg(aync)]
+    pub(crate) fn put_h2(&self, key: PoolKey, conn: H2Conn) -> HlsResult<()> {
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        inner.remove_expired();
+        if inner.max_idle_per_host == 0 || inner.max_idle == 0 { return Ok(()); }
+        inner.h2.insert(key, (conn, Instant::now()));
+        Ok(())
+    }
+}
+
+#[cfg(all(test, sync))]
+mod tests {
+    use crate::packet::H2Session;
+    use crate::pool::{Pool, PoolConn, PoolKey};
+    use crate::stream::{Proxy, Stream};
+    use crate::url::Url;
+    use crate::ALPN;
+    use std::time::Duration;
+
+    //opened个stream后的h2连接，用下一个stream_id区分
+    fn conn(opened: u32) -> PoolConn {
+        let mut h2 = H2Session::new();
+        for _ in 0..opened { h2.open_stream(vec![], vec![], true).unwrap(); }
+        PoolConn { stream: Stream::unconnection(), h2: Some(h2) }
+    }
+
+    fn next_stream_id(conn: PoolConn) -> u32 {
+        conn.h2.unwrap().open_stream(vec![], vec![], true).unwrap()
+    }
+
+    #[test]
+    fn test_pool() {
+        let pool = Pool::new().with_max_idle_per_host(2).with_max_idle(3);
+        let key1 = PoolKey::new(&Url::try_from("https://a.com/x").unwrap(), &Proxy::Null, &ALPN::Http11, "".to_string(), true);
+        let key2 = PoolKey::new(&Url::try_from("https://b.com/").unwrap(), &Proxy::Null, &ALPN::Http11, "".to_string(), true);
+        let key3 = PoolKey::new(&Url::try_from("https://a.com/y").unwrap(), &Proxy::Null, &ALPN::Http11, "".to_string(), false);
+        for i in 0..3 { pool.put(key1.clone(), conn(i)).unwrap(); }
+        assert_eq!(pool.idle_count(), 2);
+        pool.put(key2.clone(), conn(10)).unwrap();
+        pool.put(key2.clone(), conn(11)).unwrap();
+        assert_eq!(pool.idle_count(), 3);
+        assert!(pool.take(&key3).unwrap().is_none());
+        assert_eq!(next_stream_id(pool.take(&key1).unwrap().unwrap()), 5);
+        assert!(pool.take(&key1).unwrap().is_none());
+        assert_eq!(next_stream_id(pool.take(&key2).unwrap().unwrap()), 23);
+        pool.set_idle_timeout(Duration::ZERO);
+        assert!(pool.take(&key2).unwrap().is_none());
+        assert_eq!(pool.idle_count(), 0);
+    }
+}
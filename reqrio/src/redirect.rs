@@ -0,0 +1,59 @@
+use crate::url::Url;
+
+///#### 一次重定向
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    status: i32,
+    from: Url,
+    to: Url,
+}
+
+impl Redirect {
+    #[cfg(anys)]
+    pub(crate) fn new(status: i32, from: Url, to: Url) -> Redirect {
+        Redirect { status, from, to }
+    }
+
+    pub fn status(&self) -> i32 { self.status }
+
+    pub fn from(&self) -> &Url { &self.from }
+
+    ///已按from解析过的Location
+    pub fn to(&self) -> &Url { &self.to }
+
+    pub fn is_same_origin(&self) -> bool { self.from.is_same_origin(&self.to) }
+}
+
+pub type RedirectFn = Box<dyn Fn(&Redirect, &[Redirect]) -> bool>;
+
+///#### 重定向策略
+/// * 默认不跟随，3xx直接返回
+/// * 303以及POST的301/302改为GET并丢弃请求体，307/308保留请求方法和请求体
+/// * 跨域时去掉Authorization
+#[derive(Default)]
+pub enum RedirectPolicy {
+    #[default]
+    None,
+    ///最多跟随的次数
+    Limit(usize),
+    ///只跟随同源(协议、主机、端口都相同)的重定向，最多跟随的次数
+    SameOrigin(usize),
+    ///参数为即将跟随的重定向和之前的重定向，返回false时直接返回3xx响应
+    Custom(RedirectFn),
+}
+
+impl RedirectPolicy {
+    pub fn custom(f: impl Fn(&Redirect, &[Redirect]) -> bool + 'static) -> RedirectPolicy {
+        RedirectPolicy::Custom(Box::new(f))
+    }
+
+    #[cfg(anys)]
+    pub(crate) fn allow(&self, redirect: &Redirect, history: &[Redirect]) -> bool {
+        match self {
+            RedirectPolicy::None => false,
+            RedirectPolicy::Limit(max) => history.len() < *max,
+            RedirectPolicy::SameOrigin(max) => history.len() < *max && redirect.is_same_origin(),
+            RedirectPolicy::Custom(f) => f(redirect, history),
+        }
+    }
+}
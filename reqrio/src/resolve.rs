@@ -0,0 +1,470 @@
+use crate::coder;
+use crate::error::{HlsError, HlsResult};
+use crate::ext::ReqExt;
+use crate::retry::NoRetry;
+use crate::timeout::Timeout;
+use crate::Pool;
+#[cfg(sync)]
+use crate::ScReq;
+#[cfg(aync)]
+use crate::AcReq;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(aync)]
+use std::future::Future;
+#[cfg(aync)]
+use std::pin::Pin;
+
+///#### 异步解析返回的future
+#[cfg(aync)]
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output=io::Result<Lookup>> + 'a>>;
+
+///#### 域名解析的结果
+/// * ttl为None时表示解析器不知道有效期(例如系统解析)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lookup {
+    addrs: Vec<IpAddr>,
+    ttl: Option<Duration>,
+}
+
+impl Lookup {
+    pub fn new(addrs: Vec<IpAddr>, ttl: Option<Duration>) -> Lookup {
+        Lookup { addrs, ttl }
+    }
+
+    pub fn addrs(&self) -> &[IpAddr] { &self.addrs }
+
+    pub fn ttl(&self) -> Option<Duration> { self.ttl }
+}
+
+///#### 域名解析器
+/// * host不包含端口，url和代理中已经是ip的host不经过解析器
+/// * 返回的地址按地址族交替，通过Happy Eyeballs(RFC 8305)错开尝试连接，同一地址族保持返回的顺序
+/// * socks4和socks5代理在本地解析目标域名时也使用解析器
+pub trait Resolver: Send + Sync {
+    ///同步解析，timeout为剩余的连接超时
+    fn resolve(&self, host: &str, timeout: Duration) -> io::Result<Lookup>;
+
+    ///异步解析，默认调用同步的resolve(会阻塞当前线程)，耗时的解析器需要实现
+    #[cfg(aync)]
+    fn resolve_async<'a>(&'a self, host: &'a str, timeout: Duration) -> ResolveFuture<'a> {
+        let res = self.resolve(host, timeout);
+        Box::pin(async move { res })
+    }
+}
+
+///#### 系统解析(getaddrinfo)，默认的解析器
+/// * 同步解析没有超时，在单独的线程中解析
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, timeout: Duration) -> io::Result<Lookup> {
+        let (sender, receiver) = mpsc::channel();
+        let host = host.to_string();
+        thread::spawn(move || {
+            let res = (host.as_str(), 0).to_socket_addrs().map(|x| x.map(|x| x.ip()).collect::<Vec<_>>());
+            let _ = sender.send(res);
+        });
+        let addrs = receiver.recv_timeout(timeout).unwrap_or(Err(io::ErrorKind::TimedOut.into()))?;
+        Ok(Lookup::new(dedup(addrs), None))
+    }
+
+    #[cfg(aync)]
+    fn resolve_async<'a>(&'a self, host: &'a str, _: Duration) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host, 0)).await?.map(|x| x.ip()).collect();
+            Ok(Lookup::new(dedup(addrs), None))
+        })
+    }
+}
+
+///去掉重复的地址，保持顺序
+fn dedup(addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+    let mut res = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        if !res.contains(&addr) { res.push(addr); }
+    }
+    res
+}
+
+///#### 固定的域名和地址(类似curl的--resolve，但不区分端口)
+/// * 域名不区分大小写，没有设置的域名交给fallback，默认为系统解析
+/// ```rust
+/// use reqrio::{ReqExt, ScReq, StaticResolver};
+/// let resolver = StaticResolver::new().with_host("example.com", ["127.0.0.1".parse().unwrap()]);
+/// let mut req = ScReq::new().with_resolver(resolver);
+/// ```
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+    fallback: Option<Box<dyn Resolver>>,
+}
+
+impl Default for StaticResolver {
+    fn default() -> Self {
+        StaticResolver::new()
+    }
+}
+
+impl StaticResolver {
+    pub fn new() -> StaticResolver {
+        StaticResolver { hosts: HashMap::new(), fallback: Some(Box::new(SystemResolver)) }
+    }
+
+    ///设置域名的地址，已经设置过时覆盖
+    pub fn add_host(&mut self, host: impl AsRef<str>, addrs: impl IntoIterator<Item=IpAddr>) {
+        self.hosts.insert(host.as_ref().trim_end_matches('.').to_lowercase(), addrs.into_iter().collect());
+    }
+
+    pub fn with_host(mut self, host: impl AsRef<str>, addrs: impl IntoIterator<Item=IpAddr>) -> Self {
+        self.add_host(host, addrs);
+        self
+    }
+
+    ///没有设置的域名使用的解析器，None时返回NotFound
+    pub fn set_fallback(&mut self, fallback: Option<Box<dyn Resolver>>) {
+        self.fallback = fallback;
+    }
+
+    pub fn with_fallback(mut self, fallback: impl Resolver + 'static) -> Self {
+        self.set_fallback(Some(Box::new(fallback)));
+        self
+    }
+
+    fn get(&self, host: &str) -> Option<Lookup> {
+        let addrs = self.hosts.get(&host.trim_end_matches('.').to_lowercase())?;
+        Some(Lookup::new(addrs.clone(), None))
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str, timeout: Duration) -> io::Result<Lookup> {
+        if let Some(lookup) = self.get(host) { return Ok(lookup); }
+        match &self.fallback {
+            None => Err(io::ErrorKind::NotFound.into()),
+            Some(fallback) => fallback.resolve(host, timeout),
+        }
+    }
+
+    #[cfg(aync)]
+    fn resolve_async<'a>(&'a self, host: &'a str, timeout: Duration) -> ResolveFuture<'a> {
+        if let Some(lookup) = self.get(host) { return Box::pin(async move { Ok(lookup) }); }
+        match &self.fallback {
+            None => Box::pin(async { Err(io::ErrorKind::NotFound.into()) }),
+            Some(fallback) => fallback.resolve_async(host, timeout),
+        }
+    }
+}
+
+//域名->(地址，过期时间)
+type DnsCache = HashMap<String, (Vec<IpAddr>, Instant)>;
+
+///#### 缓存解析结果
+/// * 按解析器返回的ttl缓存，没有ttl时使用默认的ttl(60秒)，ttl为0和没有地址的结果不缓存
+/// * clone后共享缓存，多个ScReq/AcReq可以使用同一个缓存
+/// ```rust
+/// use reqrio::{CachedResolver, DohResolver, ReqExt, ScReq};
+/// let resolver = CachedResolver::new(DohResolver::new("https://1.1.1.1/dns-query"));
+/// let mut req = ScReq::new().with_resolver(resolver.clone());
+/// ```
+#[derive(Clone)]
+pub struct CachedResolver {
+    inner: Arc<dyn Resolver>,
+    cache: Arc<Mutex<DnsCache>>,
+    ttl: Duration,
+}
+
+impl CachedResolver {
+    pub fn new(inner: impl Resolver + 'static) -> CachedResolver {
+        CachedResolver {
+            inner: Arc::new(inner),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            ttl: Duration::from_secs(60),
+        }
+    }
+
+    ///解析器没有返回ttl时使用的ttl
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.cache.lock() { cache.clear(); }
+    }
+
+    ///没有过期的缓存，ttl为剩余的有效期
+    fn get(&self, host: &str) -> Option<Lookup> {
+        let cache = self.cache.lock().ok()?;
+        let (addrs, expires) = cache.get(&host.to_lowercase())?;
+        let ttl = expires.checked_duration_since(Instant::now()).filter(|x| !x.is_zero())?;
+        Some(Lookup::new(addrs.clone(), Some(ttl)))
+    }
+
+    fn put(&self, host: &str, lookup: &Lookup) {
+        let ttl = lookup.ttl().unwrap_or(self.ttl);
+        if ttl.is_zero() || lookup.addrs().is_empty() { return; }
+        let Ok(mut cache) = self.cache.lock() else { return; };
+        let now = Instant::now();
+        cache.retain(|_, (_, expires)| *expires > now);
+        cache.insert(host.to_lowercase(), (lookup.addrs().to_vec(), now + ttl));
+    }
+}
+
+impl Resolver for CachedResolver {
+    fn resolve(&self, host: &str, timeout: Duration) -> io::Result<Lookup> {
+        if let Some(lookup) = self.get(host) { return Ok(lookup); }
+        let lookup = self.inner.resolve(host, timeout)?;
+        self.put(host, &lookup);
+        Ok(lookup)
+    }
+
+    #[cfg(aync)]
+    fn resolve_async<'a>(&'a self, host: &'a str, timeout: Duration) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            if let Some(lookup) = self.get(host) { return Ok(lookup); }
+            let lookup = self.inner.resolve_async(host, timeout).await?;
+            self.put(host, &lookup);
+            Ok(lookup)
+        })
+    }
+}
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+///#### DNS over HTTPS(RFC 8484)
+/// * 通过reqrio发送GET请求(`?dns=`为base64url编码的DNS报文)，同步解析使用ScReq，异步解析使用AcReq
+/// * 分别查询AAAA和A记录，ipv6的地址在前，ttl为所有应答中最小的
+/// * DoH服务端自身的域名使用系统解析，可以直接使用ip(例如`https://1.1.1.1/dns-query`)
+/// * 查询之间通过内部的`Pool`复用连接
+pub struct DohResolver {
+    url: String,
+    pool: Pool,
+}
+
+impl DohResolver {
+    pub fn new(url: impl ToString) -> DohResolver {
+        DohResolver { url: url.to_string(), pool: Pool::new() }
+    }
+
+    fn query_url(&self, host: &str, qtype: u16) -> io::Result<String> {
+        let query = coder::base64_encode(dns_query(host, qtype)?);
+        let query = query.replace('+', "-").replace('/', "_");
+        let sep = if self.url.contains('?') { '&' } else { '?' };
+        Ok(format!("{}{}dns={}", self.url, sep, query.trim_end_matches('=')))
+    }
+
+    ///查询只尝试一次，各阶段超时都不超过剩余的连接超时
+    fn timeout(timeout: Duration) -> Timeout {
+        let mut res = Timeout::new();
+        res.set_connect(timeout);
+        res.set_tls_handshake(timeout);
+        res.set_read(timeout);
+        res.set_write(timeout);
+        res.set_handle(timeout);
+        res.set_connect_times(1);
+        res.set_handle_times(1);
+        res
+    }
+
+    #[cfg(sync)]
+    fn query_sync(&self, host: &str, qtype: u16, timeout: Duration) -> HlsResult<(Vec<IpAddr>, Option<u32>)> {
+        let mut req = ScReq::new().with_pool(self.pool.clone()).with_retry(NoRetry);
+        req.set_timeout(DohResolver::timeout(timeout));
+        req.set_url(self.query_url(host, qtype)?)?;
+        req.header_mut().set_accept("application/dns-message")?;
+        let response = req.get()?;
+        Ok(dns_answer(&doh_body(response)?, qtype)?)
+    }
+
+    #[cfg(aync)]
+    async fn query_async(&self, host: &str, qtype: u16, timeout: Duration) -> HlsResult<(Vec<IpAddr>, Option<u32>)> {
+        let mut req = AcReq::new().with_pool(self.pool.clone()).with_retry(NoRetry);
+        req.set_timeout(DohResolver::timeout(timeout));
+        req.set_url(self.query_url(host, qtype)?).await?;
+        req.header_mut().set_accept("application/dns-message")?;
+        let response = req.get().await?;
+        Ok(dns_answer(&doh_body(response)?, qtype)?)
+    }
+}
+
+impl Resolver for DohResolver {
+    #[cfg(sync)]
+    fn resolve(&self, host: &str, timeout: Duration) -> io::Result<Lookup> {
+        let deadline = Instant::now() + timeout;
+        let v6 = self.query_sync(host, TYPE_AAAA, timeout).map_err(doh_error)?;
+        let v4 = self.query_sync(host, TYPE_A, deadline.saturating_duration_since(Instant::now())).map_err(doh_error)?;
+        Ok(doh_lookup(v6, v4))
+    }
+
+    #[cfg(not(sync))]
+    fn resolve(&self, _: &str, _: Duration) -> io::Result<Lookup> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "sync dns over https not supported"))
+    }
+
+    #[cfg(aync)]
+    fn resolve_async<'a>(&'a self, host: &'a str, timeout: Duration) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let (v6, v4) = tokio::join!(self.query_async(host, TYPE_AAAA, timeout), self.query_async(host, TYPE_A, timeout));
+            Ok(doh_lookup(v6.map_err(doh_error)?, v4.map_err(doh_error)?))
+        })
+    }
+}
+
+fn doh_error(error: HlsError) -> io::Error {
+    match error {
+        HlsError::Io(e) => e,
+        e => io::Error::other(e.to_string()),
+    }
+}
+
+fn doh_body(mut response: crate::Response) -> HlsResult<Vec<u8>> {
+    let status = response.header().status().status_num();
+    if status != 200 { return Err(HlsError::Status(status)); }
+    Ok(response.decode_body()?.as_bytes()?.clone())
+}
+
+fn doh_lookup(v6: (Vec<IpAddr>, Option<u32>), v4: (Vec<IpAddr>, Option<u32>)) -> Lookup {
+    let ttl = match (v6.1, v4.1) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    Lookup::new([v6.0, v4.0].concat(), ttl.map(|x| Duration::from_secs(x as u64)))
+}
+
+///DNS查询报文(RFC 1035)，id为0便于http缓存(RFC 8484)，要求递归查询
+fn dns_query(host: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let mut query = vec![0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid domain: {}", host)));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&[0, 1]);
+    Ok(query)
+}
+
+///跳过报文中的域名(可能是压缩指针)，返回域名之后的位置
+fn skip_name(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            _ if len & 0xC0 == 0xC0 => return data.get(pos + 1).map(|_| pos + 2),
+            _ => pos += len + 1,
+        }
+    }
+}
+
+///应答中qtype的地址和所有记录(包括CNAME)中最小的ttl，域名不存在时没有地址
+fn dns_answer(data: &[u8], qtype: u16) -> io::Result<(Vec<IpAddr>, Option<u32>)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid dns message");
+    if data.len() < 12 || data[2] & 0x80 == 0 { return Err(invalid()); }
+    match data[3] & 0x0F {
+        0 | 3 => {}
+        rcode => return Err(io::Error::other(format!("dns response code: {}", rcode))),
+    }
+    let question = u16::from_be_bytes([data[4], data[5]]);
+    let answer = u16::from_be_bytes([data[6], data[7]]);
+    let mut pos = 12;
+    for _ in 0..question {
+        pos = skip_name(data, pos).ok_or_else(invalid)? + 4;
+    }
+    let mut addrs = vec![];
+    let mut ttl: Option<u32> = None;
+    for _ in 0..answer {
+        pos = skip_name(data, pos).ok_or_else(invalid)?;
+        let record = data.get(pos..pos + 10).ok_or_else(invalid)?;
+        let rtype = u16::from_be_bytes([record[0], record[1]]);
+        let rttl = u32::from_be_bytes([record[4], record[5], record[6], record[7]]);
+        let len = u16::from_be_bytes([record[8], record[9]]) as usize;
+        let rdata = data.get(pos + 10..pos + 10 + len).ok_or_else(invalid)?;
+        pos += 10 + len;
+        ttl = Some(ttl.map_or(rttl, |x| x.min(rttl)));
+        if rtype != qtype { continue; }
+        let addr = match rdata.len() {
+            4 => IpAddr::from([rdata[0], rdata[1], rdata[2], rdata[3]]),
+            16 => IpAddr::from(<[u8; 16]>::try_from(rdata).map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+        addrs.push(addr);
+    }
+    Ok((addrs, ttl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dns_answer, dns_query, CachedResolver, Lookup, Resolver, StaticResolver, TYPE_A};
+    use std::net::IpAddr;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct Counter(Arc<AtomicU32>, Option<Duration>);
+
+    impl Resolver for Counter {
+        fn resolve(&self, _: &str, _: Duration) -> std::io::Result<Lookup> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Lookup::new(vec!["10.0.0.1".parse().unwrap()], self.1))
+        }
+    }
+
+    #[test]
+    fn test_resolver() {
+        let timeout = Duration::from_secs(1);
+        let ip: IpAddr = "127.0.0.2".parse().unwrap();
+        let resolver = StaticResolver::new().with_host("Example.com.", [ip]);
+        assert_eq!(resolver.resolve("example.COM", timeout).unwrap().addrs(), &[ip]);
+        let resolver = StaticResolver::new().with_fallback(StaticResolver::new().with_host("b.com", [ip]));
+        assert_eq!(resolver.resolve("b.com", timeout).unwrap().addrs(), &[ip]);
+        let mut resolver = StaticResolver::new();
+        resolver.set_fallback(None);
+        assert!(resolver.resolve("b.com", timeout).is_err());
+
+        let count = Arc::new(AtomicU32::new(0));
+        let resolver = CachedResolver::new(Counter(count.clone(), Some(Duration::from_millis(100))));
+        resolver.resolve("a.com", timeout).unwrap();
+        assert!(resolver.clone().resolve("A.com", timeout).unwrap().ttl().unwrap() <= Duration::from_millis(100));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        std::thread::sleep(Duration::from_millis(110));
+        resolver.resolve("a.com", timeout).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        //ttl为0时不缓存
+        let resolver = CachedResolver::new(Counter(count.clone(), Some(Duration::ZERO)));
+        resolver.resolve("a.com", timeout).unwrap();
+        resolver.resolve("a.com", timeout).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_dns_message() {
+        let query = dns_query("www.a.com", TYPE_A).unwrap();
+        assert_eq!(&query[12..], b"\x03www\x01a\x03com\x00\x00\x01\x00\x01");
+        assert!(dns_query("a..com", TYPE_A).is_err());
+        //应答：CNAME(压缩指针指向问题中的域名) + A
+        let mut answer = vec![0, 0, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0];
+        answer.extend_from_slice(&query[12..]);
+        answer.extend_from_slice(&[0xC0, 12, 0, 5, 0, 1, 0, 0, 1, 0, 0, 4, 1, b'b', 0xC0, 18]);
+        answer.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4]);
+        let (addrs, ttl) = dns_answer(&answer, TYPE_A).unwrap();
+        assert_eq!(addrs, vec!["1.2.3.4".parse::<IpAddr>().unwrap()]);
+        assert_eq!(ttl, Some(60));
+        //NXDOMAIN没有地址，SERVFAIL为错误，截断的报文为错误
+        answer[3] = 0x83;
+        answer[7] = 0;
+        assert_eq!(dns_answer(&answer, TYPE_A).unwrap(), (vec![], None));
+        answer[3] = 0x82;
+        assert!(dns_answer(&answer, TYPE_A).is_err());
+        answer[3] = 0x80;
+        answer[7] = 2;
+        assert!(dns_answer(&answer[..answer.len() - 2], TYPE_A).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::date::parse_http_date;
+use crate::error::{HlsError, TimeoutPhase};
+use crate::packet::{Header, Method};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+///#### 一次请求的结果
+pub enum Outcome<'a> {
+    Error(&'a HlsError),
+    ///收到了响应头
+    Response(&'a Header),
+}
+
+///#### 重试前的等待时间和是否需要重新建立连接
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    pub delay: Duration,
+    pub reconnect: bool,
+}
+
+///#### 重试策略
+/// * attempt为已经失败的次数，从1开始，总次数不超过Timeout的handle_times/connect_times
+/// * 请求体是已读取的流、或者设置了回调时，收到响应后不会再重试
+pub trait RetryPolicy {
+    ///请求失败或收到响应头后调用，返回None时直接返回结果
+    fn retry(&self, attempt: u32, method: &Method, outcome: Outcome<'_>) -> Option<Retry>;
+
+    ///建立连接失败后调用，返回等待的时间，None时直接返回错误
+    fn retry_connect(&self, attempt: u32, error: &HlsError) -> Option<Duration>;
+}
+
+///#### 不重试
+pub struct NoRetry;
+
+impl RetryPolicy for NoRetry {
+    fn retry(&self, _: u32, _: &Method, _: Outcome<'_>) -> Option<Retry> { None }
+
+    fn retry_connect(&self, _: u32, _: &HlsError) -> Option<Duration> { None }
+}
+
+///#### 指数退避
+/// * 第n次重试前等待base*2^(n-1)，不超过max_delay，开启jitter时在[delay/2, delay]中随机
+/// * 可重试的错误(见`HlsError::is_retryable`)，非幂等的请求(POST等)只在确定没有发送到服务端时重试
+/// * 幂等的请求收到408/429/502/503/504时重试，`Retry-After`优先，超过max_delay时不再重试
+pub struct ExponentialBackoff {
+    max_retries: u32,
+    base: Duration,
+    max_delay: Duration,
+    jitter: bool,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        ExponentialBackoff::new(2)
+    }
+}
+
+impl ExponentialBackoff {
+    pub fn new(max_retries: u32) -> ExponentialBackoff {
+        ExponentialBackoff {
+            max_retries,
+            base: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+
+    pub fn with_base(mut self, base: Duration) -> Self {
+        self.base = base;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    ///第attempt次失败后的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base.saturating_mul(1 << attempt.saturating_sub(1).min(20)).min(self.max_delay);
+        match self.jitter {
+            true => delay / 2 + delay.mul_f64(random() / 2.0),
+            false => delay,
+        }
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn retry(&self, attempt: u32, method: &Method, outcome: Outcome<'_>) -> Option<Retry> {
+        if attempt > self.max_retries { return None; }
+        match outcome {
+            Outcome::Error(e) => {
+                if !e.is_retryable() { return None; }
+                //请求可能已经被服务端处理
+                if !method.is_idempotent() && !is_unsent(e) { return None; }
+                Some(Retry { delay: self.delay(attempt), reconnect: true })
+            }
+            Outcome::Response(header) => {
+                if !method.is_idempotent() || !matches!(header.status().status_num(), 408 | 429 | 502 | 503 | 504) { return None; }
+                let delay = match retry_after(header) {
+                    Some(delay) if delay > self.max_delay => return None,
+                    Some(delay) => delay,
+                    None => self.delay(attempt),
+                };
+                Some(Retry { delay, reconnect: false })
+            }
+        }
+    }
+
+    fn retry_connect(&self, attempt: u32, error: &HlsError) -> Option<Duration> {
+        (attempt <= self.max_retries && error.is_retryable()).then(|| self.delay(attempt))
+    }
+}
+
+///请求还没有发送到服务端
+fn is_unsent(error: &HlsError) -> bool {
+    match error {
+        HlsError::Resolve(_, _) | HlsError::Connect(_, _) => true,
+        HlsError::Timeout(phase) => matches!(phase, TimeoutPhase::Connect | TimeoutPhase::TlsHandshake),
+        HlsError::Proxy(_, e) => is_unsent(e),
+        e => e.is_unprocessed(),
+    }
+}
+
+///[0, 1)的随机数，只用于jitter
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+///Retry-After的秒数或HTTP-date(RFC 9110 10.2.3)
+pub fn retry_after(header: &Header) -> Option<Duration> {
+    let value = header.get("retry-after")?.as_string()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() { return Some(Duration::from_secs(seconds)); }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::Header;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = ExponentialBackoff::new(2).with_jitter(false);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+        assert_eq!(policy.delay(30), Duration::from_secs(10));
+        let closed = HlsError::PeerClosedConnection;
+        assert!(policy.retry(1, &Method::GET, Outcome::Error(&closed)).is_some_and(|x| x.reconnect));
+        assert!(policy.retry(3, &Method::GET, Outcome::Error(&closed)).is_none());
+        //POST可能已经被处理
+        assert!(policy.retry(1, &Method::POST, Outcome::Error(&closed)).is_none());
+        let refused = HlsError::Connect("127.0.0.1:1".to_string(), std::io::ErrorKind::ConnectionRefused.into());
+        assert!(policy.retry(1, &Method::POST, Outcome::Error(&refused)).is_some());
+
+        let mut header = Header::try_from("HTTP/1.1 503 Service Unavailable\r\nRetry-After: 2".to_string()).unwrap();
+        let retry = policy.retry(1, &Method::GET, Outcome::Response(&header)).unwrap();
+        assert_eq!(retry, Retry { delay: Duration::from_secs(2), reconnect: false });
+        assert!(policy.retry(1, &Method::POST, Outcome::Response(&header)).is_none());
+        header.insert("Retry-After", "Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(retry_after(&header), Some(Duration::ZERO));
+        header.insert("Retry-After", "60").unwrap();
+        assert!(policy.retry(1, &Method::GET, Outcome::Response(&header)).is_none());
+    }
+}
@@ -1,32 +1,57 @@
 use crate::alpn::ALPN;
-use crate::body::BodyType;
+use crate::body::{BodyReader, BodyType, BODY_CHUNK};
 use crate::buffer::Buffer;
-use crate::coder::{HPackCoding, HackDecode};
-use crate::error::HlsResult;
+use crate::error::{HlsError, HlsResult};
 use crate::ext::{ReqExt, ReqGenExt, ReqPriExt};
 use crate::packet::*;
-use crate::stream::{ConnParam, Proxy, Stream};
+use crate::stream::{ConnParam, Proxy, Stream, SystemProxy};
 use crate::timeout::Timeout;
-use crate::url::Url;
+use crate::url::{Protocol, Url};
 use json::JsonValue;
 #[cfg(feature = "cls_sync")]
 use reqtls::Fingerprint;
-use std::mem;
+use std::io::{self, Cursor, Read};
+use std::{mem, thread};
 use crate::ReqCallback;
+use crate::redirect::{Redirect, RedirectPolicy};
+use crate::retry::{ExponentialBackoff, Outcome, RetryPolicy};
+use crate::pool::{Pool, PoolConn, PoolKey};
+use crate::jar::CookieJar;
+use crate::resolve::{Resolver, SystemResolver};
+use crate::ws::{self, ScWs, WsCodec, WsConfig};
+use crate::sse::{self, Event, EventParser};
+
+//h2的stream_id和未发送完的请求体
+type H2Pending = (u32, Option<BodyReader>);
 
 pub struct ScReq {
     header: Header,
     url: Url,
-    hack_coder: HPackCoding,
+    //h2连接状态
+    h2: Option<H2Session>,
+    //h2未处理完的帧，跨请求保留
+    h2_buffer: Buffer,
     stream: Stream,
     body: BodyType,
     callback: Option<ReqCallback>,
     timeout: Timeout,
-    stream_id: u32,
     alpn: ALPN,
     proxy: Proxy,
+    //按目标url选择代理，设置后覆盖proxy
+    system_proxy: Option<SystemProxy>,
     #[cfg(feature = "cls_sync")]
     fingerprint: Fingerprint,
+    h2_fingerprint: H2Fingerprint,
+    pool: Option<Pool>,
+    cookie_jar: CookieJar,
+    pool_key: Option<PoolKey>,
+    reused: bool,
+    keep_alive: bool,
+    redirect: RedirectPolicy,
+    //本次请求已经跟随的重定向
+    redirects: Vec<Redirect>,
+    retry: Box<dyn RetryPolicy>,
+    resolver: Box<dyn Resolver>,
 }
 
 impl ScReq {
@@ -34,16 +59,27 @@ impl ScReq {
         ScReq {
             header: Header::new_req_h1(),
             url: Url::new(),
-            hack_coder: HPackCoding::new(),
+            h2: None,
+            h2_buffer: Buffer::with_capacity(0),
             stream: Stream::unconnection(),
             body: BodyType::Text("".to_string()),
             callback: None,
             timeout: Timeout::new(),
-            stream_id: 0,
             alpn: ALPN::Http11,
             proxy: Proxy::Null,
+            system_proxy: None,
             #[cfg(feature = "cls_sync")]
             fingerprint: Fingerprint::default(),
+            h2_fingerprint: H2Fingerprint::default(),
+            pool: None,
+            cookie_jar: CookieJar::new(),
+            pool_key: None,
+            reused: false,
+            keep_alive: true,
+            redirect: RedirectPolicy::None,
+            redirects: vec![],
+            retry: Box::new(ExponentialBackoff::default()),
+            resolver: Box::new(SystemResolver),
         }
     }
 
@@ -83,14 +119,44 @@ impl ScReq {
     }
 
     pub fn h1_io(&mut self, context: Vec<u8>) -> HlsResult<Response> {
-        self.stream.sync_write(context.as_slice())?;
-        let mut response = Response::new();
+        self.stream.sync_write(context.as_slice(), &self.timeout)?;
+        self.h1_read()
+    }
+
+    ///分块发送请求体，长度未知时使用chunked
+    fn h1_write_body(&mut self, mut body: BodyReader) -> HlsResult<()> {
+        let chunked = body.len().is_none();
+        let mut chunk = vec![0; BODY_CHUNK];
+        loop {
+            let len = body.read(&mut chunk)?;
+            if chunked {
+                let mut data = format!("{:x}\r\n", len).into_bytes();
+                data.extend_from_slice(&chunk[..len]);
+                data.extend_from_slice(b"\r\n");
+                self.stream.sync_write(&data, &self.timeout)?;
+            } else if len != 0 {
+                self.stream.sync_write(&chunk[..len], &self.timeout)?;
+            }
+            if len == 0 { return Ok(()); }
+        }
+    }
+
+    fn h1_read(&mut self) -> HlsResult<Response> {
+        let mut response = Response::new_h1(self.header.method());
         let mut buffer = Buffer::with_capacity(16413);
-        let mut read_len = 0;
+        let mut decoder = None;
         loop {
             buffer.reset();
-            self.stream.sync_read(&mut buffer)?;
-            if self.handle_h1_res(&buffer, &mut response, &mut read_len)? { break; }
+            match self.stream.sync_read(&mut buffer, &self.timeout) {
+                Ok(_) => if self.handle_h1_res(&buffer, &mut response, &mut decoder)? { break; },
+                //没有content-length和chunked的响应体读到连接关闭
+                Err(HlsError::PeerClosedConnection) if !response.header().is_empty() => {
+                    self.keep_alive = false;
+                    self.finish_h1_res(&mut response, &mut decoder)?;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
         }
         Ok(response)
     }
@@ -100,35 +166,76 @@ impl ScReq {
             ALPN::Http20 => {
                 let headers = self.gen_h2_header()?;
                 let body = self.gen_h2_body()?;
-                self.h2c_io(headers, body)
+                self.h2c_body_io(headers, body)
             }
-            _ => {
-                let context = self.gen_h1()?;
-                self.h1_io(context)
+            _ => match self.gen_h1()? {
+                (context, None) => self.h1_io(context),
+                (context, Some(body)) => {
+                    self.stream.sync_write(&context, &self.timeout)?;
+                    self.h1_write_body(body)?;
+                    self.h1_read()
+                }
             }
         }?;
         self.update_cookie(&response);
-        self.callback = None;
-        if let ALPN::Http20 = self.alpn { self.stream_id += 2; }
+        if !response.header().keep_alive() { self.keep_alive = false; }
         Ok(response)
     }
 
+    ///发送请求并按重定向策略跟随重定向，整个过程不超过总超时
     pub fn stream_io(&mut self) -> HlsResult<Response> {
-        for i in 0..self.timeout.handle_times() {
-            let res = self.handle_io();
-            match res {
-                Ok(res) => return Ok(res),
-                Err(e) => if i != self.timeout.handle_times() - 1 {
-                    println!("[ScReq] write/recv error, error: {}, handle: {}/{}", e.to_string(), i + 2, self.timeout.handle_times());
-                    continue;
+        self.timeout.start();
+        let res = self.redirect_io().map_err(|e| self.timeout.deadline_error(e));
+        self.timeout.stop();
+        res
+    }
+
+    fn redirect_io(&mut self) -> HlsResult<Response> {
+        self.redirects.clear();
+        loop {
+            let mut response = self.retry_io()?;
+            match self.next_redirect(response.header())? {
+                None => {
+                    response.set_history(mem::take(&mut self.redirects));
+                    self.callback = None;
+                    return Ok(response);
                 }
+                Some(redirect) => self.follow_redirect(redirect)?,
             }
         }
-        Err("stream io error".into())
+    }
+
+    fn follow_redirect(&mut self, redirect: Redirect) -> HlsResult<()> {
+        let url = self.prepare_redirect(redirect);
+        //set_url会清空请求体，307/308需要保留
+        let body = mem::replace(&mut self.body, BodyType::Text("".to_string()));
+        self.set_url(url)?;
+        self.body = body;
+        Ok(())
+    }
+
+    fn retry_io(&mut self) -> HlsResult<Response> {
+        //上一个响应要求关闭连接
+        if !self.keep_alive { self.re_conn()?; }
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let res = self.handle_io();
+            //读到了响应，复用的连接是可用的，之后的重试不需要因为复用而重连
+            if res.is_ok() { self.reused = false; }
+            let retry = match &res {
+                Ok(response) => self.next_retry(attempt, Outcome::Response(response.header())),
+                Err(e) => self.next_retry(attempt, Outcome::Error(e)),
+            };
+            let Some(retry) = retry else { return res; };
+            thread::sleep(retry.delay);
+            //连接池中的连接可能已被服务端关闭，未被处理的h2请求需要新的连接
+            if retry.reconnect || self.reused || !self.keep_alive { self.re_conn()?; }
+        }
     }
 
     pub fn with_proxy(mut self, proxy: Proxy) -> Self {
-        self.proxy = proxy;
+        self.set_proxy(proxy);
         self
     }
 
@@ -139,31 +246,32 @@ impl ScReq {
     }
 
     pub fn re_conn(&mut self) -> HlsResult<()> {
-        self.hack_coder = HPackCoding::new();
-        self.stream_id = 0;
-        for i in 0..self.timeout.connect_times() {
+        self.h2 = None;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
             let param = ConnParam {
                 url: &self.url,
                 proxy: &self.proxy,
                 timeout: &self.timeout,
                 #[cfg(feature = "cls_sync")]
                 fingerprint: &mut self.fingerprint,
-                alpn: &self.alpn,
+                //websocket只能使用http/1.1
+                alpn: if matches!(self.url.protocol(), Protocol::Ws | Protocol::Wss) { &ALPN::Http11 } else { &self.alpn },
+                resolver: self.resolver.as_ref(),
             };
-            match self.stream.sync_connect(param) {
-                Ok(_) => {
-                    // println!("{}", self.stream.alpn().alpn_str());
-                    self.header.init_by_alpn(self.stream.alpn());
-                    if self.stream.alpn() == &ALPN::Http20 { self.handle_h2_setting()?; }
-                    return Ok(());
-                }
-                Err(e) => if i != self.timeout.connect_times() - 1 {
-                    println!("[ScReq] continue with error-{}, handle: {}/{}", e.to_string(), i + 2, self.timeout.handle_times());
-                    continue;
-                }
+            let Err(e) = self.stream.sync_connect(param) else { break; };
+            match self.next_connect_retry(attempt, &e) {
+                None => return Err(e),
+                Some(delay) => thread::sleep(delay),
             }
         }
-        Err("[ScReq] connection error".into())
+        self.pool_key = Some(self.pool_key()?);
+        self.reused = false;
+        self.keep_alive = true;
+        self.header.init_by_alpn(self.stream.alpn());
+        if self.stream.alpn() == &ALPN::Http20 { self.handle_h2_setting()?; }
+        Ok(())
     }
 
     pub fn with_url(mut self, url: &str) -> HlsResult<Self> {
@@ -191,16 +299,56 @@ impl ScReq {
     pub fn set_url(&mut self, url: impl AsRef<str>) -> HlsResult<()> {
         let body = mem::replace(&mut self.body, BodyType::Text("".to_string()));
         drop(body);
-        let old_host = self.url.addr().host().to_string();
         self.url = Url::try_from(url.as_ref())?;
-        if self.url.addr().host() != old_host {
-            let host = self.url.addr().to_string().replace(":80", "").replace(":443", "");
+        if let Some(system) = &self.system_proxy { self.proxy = system.select(&self.url); }
+        let key = self.pool_key()?;
+        if self.pool_key.as_ref() != Some(&key) || !self.stream.is_connected() {
+            let host = self.url.authority();
             self.header.set_host(host)?;
-            self.re_conn()?;
+            self.release();
+            match self.pool.as_ref().map(|x| x.take(&key)).transpose()?.flatten() {
+                None => self.re_conn()?,
+                Some(conn) => self.reuse(key, conn),
+            }
         }
         Ok(())
     }
 
+    fn pool_key(&self) -> HlsResult<PoolKey> {
+        #[cfg(feature = "cls_sync")]
+        let fingerprint = format!("{}-{}-{}", self.fingerprint.ja3()?, self.fingerprint.verify(), self.fingerprint.root_store().id());
+        #[cfg(not(feature = "cls_sync"))]
+        let fingerprint = String::new();
+        //h2指纹不同的连接不能共享
+        let fingerprint = format!("{}-{}", fingerprint, self.h2_fingerprint.akamai());
+        Ok(PoolKey::new(&self.url, &self.proxy, &self.alpn, fingerprint, true))
+    }
+
+    fn reuse(&mut self, key: PoolKey, conn: PoolConn) {
+        self.stream = conn.stream;
+        self.h2 = conn.h2;
+        self.h2_buffer = Buffer::with_capacity(0xFFFF);
+        self.pool_key = Some(key);
+        self.reused = true;
+        self.keep_alive = true;
+        self.header.init_by_alpn(self.stream.alpn());
+    }
+
+    ///keep-alive的连接放回连接池，否则关闭
+    fn release(&mut self) {
+        let stream = mem::replace(&mut self.stream, Stream::unconnection());
+        let key = self.pool_key.take();
+        let mut stream = match (&self.pool, key) {
+            (Some(pool), Some(key)) if self.keep_alive && stream.is_connected() => {
+                let conn = PoolConn { stream, h2: self.h2.take() };
+                let _ = pool.put(key, conn);
+                return;
+            }
+            _ => stream
+        };
+        let _ = stream.sync_shutdown();
+    }
+
     pub fn send_check(&mut self, method: Method) -> HlsResult<Response> {
         self.header.set_method(method);
         let response = self.stream_io()?;
@@ -212,42 +360,252 @@ impl ScReq {
         let response = self.send_check(method)?;
         self.check_res(response, k, v, e)
     }
+
+    ///发送请求，读取到响应头后返回，响应体通过ScResStream按需读取
+    /// * 总超时到读取到响应头为止，之后只受单次读取的超时限制
+    pub fn send_stream(&mut self, method: Method) -> HlsResult<ScResStream<'_>> {
+        self.header.set_method(method);
+        let (response, body) = self.stream_head()?;
+        ScResStream::new(self, response, body)
+    }
+
+    ///#### 打开server-sent events，读取到响应头后返回ScSse
+    /// * 使用当前的请求头、请求体、cookie jar、代理和tls指纹，请求头的Accept设置为text/event-stream，结束后恢复原来的请求头
+    /// * 状态码不是200或content-type不是text/event-stream时返回错误，204时没有事件
+    pub fn event_source(&mut self, method: Method) -> HlsResult<ScSse<'_>> {
+        self.header.set_method(method);
+        let saved = sse::save_headers(&self.header);
+        let mut res = ScSse { req: self, body: None, parser: EventParser::new(), closed: false, saved };
+        res.connect()?;
+        Ok(res)
+    }
+
+    fn stream_head(&mut self) -> HlsResult<(Response, ResBody)> {
+        self.timeout.start();
+        let res = self.redirect_stream_head().map_err(|e| self.timeout.deadline_error(e));
+        self.timeout.stop();
+        let (response, decoder, h2) = res?;
+        Ok((response, ResBody::new(decoder, h2)))
+    }
+
+    fn redirect_stream_head(&mut self) -> HlsResult<(Response, BodyDecoder, Option<H2Pending>)> {
+        self.redirects.clear();
+        loop {
+            let (mut response, decoder, h2) = self.retry_stream_head()?;
+            match self.next_redirect(response.header())? {
+                None => {
+                    response.set_history(mem::take(&mut self.redirects));
+                    return Ok((response, decoder, h2));
+                }
+                Some(redirect) => {
+                    //不读取重定向的响应体，http/1.1的连接不再复用
+                    drop(ScResStream::new(self, response, ResBody::new(decoder, h2))?);
+                    self.follow_redirect(redirect)?;
+                }
+            }
+        }
+    }
+
+    fn retry_stream_head(&mut self) -> HlsResult<(Response, BodyDecoder, Option<H2Pending>)> {
+        //上一个响应要求关闭连接
+        if !self.keep_alive { self.re_conn()?; }
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let res = self.handle_stream_head();
+            if res.is_ok() { self.reused = false; }
+            let retry = match res {
+                Ok((response, decoder, h2)) => match self.next_retry(attempt, Outcome::Response(response.header())) {
+                    None => return Ok((response, decoder, h2)),
+                    Some(retry) => {
+                        //不读取需要重试的响应体，http/1.1的连接不再复用
+                        drop(ScResStream::new(self, response, ResBody::new(decoder, h2))?);
+                        retry
+                    }
+                },
+                Err(e) => match self.next_retry(attempt, Outcome::Error(&e)) {
+                    None => return Err(e),
+                    Some(retry) => retry,
+                },
+            };
+            thread::sleep(retry.delay);
+            if retry.reconnect || self.reused || !self.keep_alive { self.re_conn()?; }
+        }
+    }
+
+    ///#### 升级为websocket，url必须是ws://或wss://
+    /// * 握手使用当前的请求头(cookie、origin、user-agent等)、cookie jar、代理和tls指纹，不跟随重定向
+    /// * 连接交给返回的ScWs，之后的请求会重新连接
+    pub fn websocket(&mut self, config: WsConfig) -> HlsResult<ScWs> {
+        if !matches!(self.url.protocol(), Protocol::Ws | Protocol::Wss) { return Err("websocket url must be ws:// or wss://".into()); }
+        if !self.keep_alive || !self.stream.is_connected() || self.stream.alpn() != &ALPN::Http11 { self.re_conn()?; }
+        self.timeout.start();
+        let res = self.ws_handshake(&config).map_err(|e| self.timeout.deadline_error(e));
+        self.timeout.stop();
+        //握手失败时连接的状态未知，不再复用
+        let stream = mem::replace(&mut self.stream, Stream::unconnection());
+        self.pool_key = None;
+        self.keep_alive = false;
+        let (header, protocol, codec) = res?;
+        Ok(ScWs::new(stream, self.timeout.clone(), codec, header, protocol))
+    }
+
+    fn ws_handshake(&mut self, config: &WsConfig) -> HlsResult<(Header, Option<String>, WsCodec)> {
+        let key = ws::client_key();
+        let manual = self.merge_jar_cookies();
+        let request = ws::handshake_request(&self.url, &self.header, &key, config);
+        self.header.set_cookies(manual);
+        self.stream.sync_write(&request, &self.timeout)?;
+        let mut decoder = BodyDecoder::new_h1(&Method::GET);
+        let mut response = self.h1_read_header(&mut decoder)?;
+        self.update_cookie(&response);
+        let (protocol, deflate) = ws::handshake_response(response.header(), &key, config)?;
+        let codec = WsCodec::new(config, deflate, decoder.take_upgraded());
+        Ok((mem::replace(response.header_mut(), Header::new_res()), protocol, codec))
+    }
+
+    fn h1_read_header(&mut self, decoder: &mut BodyDecoder) -> HlsResult<Response> {
+        let mut response = Response::new();
+        let mut buffer = Buffer::with_capacity(16413);
+        loop {
+            buffer.reset();
+            self.stream.sync_read(&mut buffer, &self.timeout)?;
+            let data = decoder.decode(buffer.filled())?;
+            if let Some(header) = decoder.take_header() {
+                *response.header_mut() = header;
+                //和响应头一起读到的响应体，已经解码
+                response.push_raw(data);
+                return Ok(response);
+            }
+        }
+    }
+
+    ///发送请求并读取响应头，h2时同时返回stream_id和未发送完的请求体
+    fn handle_stream_head(&mut self) -> HlsResult<(Response, BodyDecoder, Option<H2Pending>)> {
+        let (response, decoder, h2) = match self.stream.alpn() {
+            ALPN::Http20 => {
+                let headers = self.gen_h2_header()?;
+                let body = self.gen_h2_body()?;
+                let (stream_id, mut body) = self.h2c_open(headers, body)?;
+                let mut response = Response::new();
+                let mut ended = false;
+                while response.header().is_empty() {
+                    let event = self.h2c_next_event(stream_id, &mut body)?;
+                    ended = self.handle_h2_event(event, &mut response, &mut None)?;
+                    if ended && response.header().is_empty() { return Err("h2 stream ended without headers".into()); }
+                }
+                let mut decoder = BodyDecoder::new_h2(response.header())?;
+                //没有响应体
+                if ended { decoder.finish()?; }
+                (response, decoder, Some((stream_id, body)))
+            }
+            _ => {
+                match self.gen_h1()? {
+                    (context, None) => self.stream.sync_write(&context, &self.timeout)?,
+                    (context, Some(body)) => {
+                        self.stream.sync_write(&context, &self.timeout)?;
+                        self.h1_write_body(body)?;
+                    }
+                }
+                let mut decoder = BodyDecoder::new_h1(self.header.method());
+                let response = self.h1_read_header(&mut decoder)?;
+                (response, decoder, None)
+            }
+        };
+        self.update_cookie(&response);
+        if !response.header().keep_alive() { self.keep_alive = false; }
+        Ok((response, decoder, h2))
+    }
 }
 
 impl ScReq {
     pub fn handle_h2_setting(&mut self) -> HlsResult<()> {
-        let mut handshake = "PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".as_bytes().to_vec();
-        let setting_frame = Frame::default_setting();
-        handshake.extend(setting_frame.to_bytes());
-        let update_frame = Frame::window_update();
-        handshake.extend(update_frame.to_bytes());
-        self.stream.sync_write(&handshake)?;
-        self.stream_id += 1;
+        let session = H2Session::with_fingerprint(self.h2_fingerprint.clone());
+        self.stream.sync_write(&session.preface(), &self.timeout)?;
+        self.h2 = Some(session);
+        self.h2_buffer = Buffer::with_capacity(0xFFFF);
         Ok(())
     }
 
     pub fn h2c_io(&mut self, headers: Vec<HeaderKey>, body: Vec<u8>) -> HlsResult<Response> {
-        let hdr_bs = self.hack_coder.encode(headers)?;
-        let header_frame = Frame::new_header(hdr_bs, body.len(), self.stream_id);
-        header_frame.set_weight(146);
-        header_frame.add_flag(FrameFlag::Priority);
-        self.stream.sync_write(header_frame.to_bytes().as_slice())?;
-        for body_frame in Frame::new_body(body, self.stream_id) {
-            self.stream.sync_write(body_frame.to_bytes().as_slice())?;
+        let mut reader = BodyReader::new();
+        reader.push_bytes(body);
+        self.h2c_body_io(headers, reader)
+    }
+
+    ///窗口允许时继续发送请求体，返回true时请求体已发送完
+    fn h2c_send_body(&mut self, stream_id: u32, body: &mut BodyReader) -> HlsResult<bool> {
+        let mut chunk = vec![0; BODY_CHUNK];
+        loop {
+            let session = self.h2.as_mut().ok_or("h2 session none")?;
+            if session.pending_len(stream_id) != 0 { return Ok(false); }
+            let len = body.read(&mut chunk)?;
+            session.send_data(stream_id, chunk[..len].to_vec(), len == 0)?;
+            let output = session.take_output();
+            self.stream.sync_write(&output, &self.timeout)?;
+            if len == 0 { return Ok(true); }
         }
-        let mut response = Response::new();
-        let mut buffer = Buffer::with_capacity(0xFFFF);
+    }
+
+    ///发送请求头，请求体全部在内存中时一起发送，否则返回需要继续发送的请求体
+    fn h2c_open(&mut self, headers: Vec<HeaderKey>, mut body: BodyReader) -> HlsResult<(u32, Option<BodyReader>)> {
+        let session = self.h2.as_mut().ok_or("h2 session none")?;
+        let (stream_id, body) = match body.take_bytes() {
+            Some(bytes) => (session.open_stream(headers, bytes, true)?, None),
+            None => (session.open_stream(headers, vec![], false)?, Some(body)),
+        };
+        self.stream.sync_write(&session.take_output(), &self.timeout)?;
+        Ok((stream_id, body))
+    }
+
+    ///读取stream_id的下一个事件，窗口允许时继续发送请求体
+    fn h2c_next_event(&mut self, stream_id: u32, body: &mut Option<BodyReader>) -> HlsResult<H2Event> {
         loop {
-            self.stream.sync_read(&mut buffer)?;
-            while let Ok(frame) = Frame::from_bytes(&mut buffer) {
-                if frame.frame_type() == &FrameType::Settings && frame.flags().contains(&FrameFlag::ACK) {
-                    let mut end_frame = Frame::none_frame();
-                    end_frame.set_frame_type(FrameType::Settings);
-                    end_frame.set_flags(vec![FrameFlag::EndStream]);
-                    self.stream.sync_write(end_frame.to_bytes().as_ref())?;
-                    continue;
+            while let Ok(frame) = Frame::from_bytes(&mut self.h2_buffer) {
+                let session = self.h2.as_mut().ok_or("h2 session none")?;
+                let event = match session.recv_frame(frame) {
+                    Ok(event) => event,
+                    //连接错误，写出GOAWAY后不再复用
+                    Err(e) => {
+                        self.keep_alive = false;
+                        let _ = self.stream.sync_write(&session.take_output(), &self.timeout);
+                        return Err(e);
+                    }
+                };
+                if let Some(H2Event::Data(id, data, _)) = &event { session.release_capacity(*id, data.len()); }
+                let output = session.take_output();
+                if !output.is_empty() { self.stream.sync_write(&output, &self.timeout)?; }
+                //GOAWAY后连接不能再复用，last_stream_id之后的请求没有被处理
+                if let Some(H2Event::GoAway(last_stream_id, code)) = &event {
+                    self.keep_alive = false;
+                    if stream_id > *last_stream_id { return Err(HlsError::GoAway(*last_stream_id, code.clone())); }
                 }
-                if self.handle_h2_res(frame, &mut response)? { return Ok(response); };
+                if let Some(event) = event && event.stream_id() == stream_id { return Ok(event); }
+            }
+            if let Some(reader) = body && self.h2c_send_body(stream_id, reader)? { *body = None; }
+            self.stream.sync_read(&mut self.h2_buffer, &self.timeout)?;
+        }
+    }
+
+    ///取消stream，不再发送剩余的请求体
+    fn h2c_cancel(&mut self, stream_id: u32) -> HlsResult<()> {
+        let session = self.h2.as_mut().ok_or("h2 session none")?;
+        session.reset_stream(stream_id, H2ErrorCode::Cancel);
+        let output = session.take_output();
+        if !output.is_empty() { self.stream.sync_write(&output, &self.timeout)?; }
+        Ok(())
+    }
+
+    fn h2c_body_io(&mut self, headers: Vec<HeaderKey>, body: BodyReader) -> HlsResult<Response> {
+        let (stream_id, mut body) = self.h2c_open(headers, body)?;
+        let mut response = Response::new();
+        let mut decoder = None;
+        loop {
+            let event = self.h2c_next_event(stream_id, &mut body)?;
+            if self.handle_h2_event(event, &mut response, &mut decoder)? {
+                //响应已结束，不再发送剩余的请求体
+                if body.is_some() { self.h2c_cancel(stream_id)?; }
+                return Ok(response);
             }
         }
     }
@@ -260,8 +618,24 @@ impl ReqPriExt for ScReq {
         &mut self.callback
     }
 
-    fn hack_decoder(&mut self) -> &mut HackDecode {
-        self.hack_coder.decoder()
+    fn h2_fingerprint(&self) -> &H2Fingerprint {
+        &self.h2_fingerprint
+    }
+
+    fn redirect_policy(&self) -> &RedirectPolicy {
+        &self.redirect
+    }
+
+    fn redirects(&self) -> &[Redirect] {
+        &self.redirects
+    }
+
+    fn redirects_mut(&mut self) -> &mut Vec<Redirect> {
+        &mut self.redirects
+    }
+
+    fn retry_policy(&self) -> &dyn RetryPolicy {
+        self.retry.as_ref()
     }
 }
 
@@ -299,9 +673,19 @@ impl ReqExt for ScReq {
     }
 
     fn set_proxy(&mut self, proxy: Proxy) {
+        self.system_proxy = None;
         self.proxy = proxy;
     }
 
+    fn set_system_proxy(&mut self, proxy: Option<SystemProxy>) {
+        self.proxy = proxy.as_ref().map(|x| x.select(&self.url)).unwrap_or(Proxy::Null);
+        self.system_proxy = proxy;
+    }
+
+    fn proxy(&self) -> &Proxy {
+        &self.proxy
+    }
+
     fn set_alpn(&mut self, alpn: ALPN) {
         self.alpn = alpn;
     }
@@ -314,13 +698,237 @@ impl ReqExt for ScReq {
     fn set_fingerprint(&mut self, fingerprint: Fingerprint) {
         self.fingerprint = fingerprint;
     }
+
+    fn set_h2_fingerprint(&mut self, fingerprint: H2Fingerprint) {
+        self.h2_fingerprint = fingerprint;
+    }
+
+    fn set_redirect(&mut self, policy: RedirectPolicy) {
+        self.redirect = policy;
+    }
+
+    fn set_retry(&mut self, policy: impl RetryPolicy + 'static) {
+        self.retry = Box::new(policy);
+    }
+
+    fn set_pool(&mut self, pool: Pool) {
+        self.pool = Some(pool);
+    }
+
+    fn set_resolver(&mut self, resolver: impl Resolver + 'static) {
+        self.resolver = Box::new(resolver);
+    }
+
+    fn set_cookie_jar(&mut self, jar: CookieJar) {
+        self.cookie_jar = jar;
+    }
+
+    fn cookie_jar(&self) -> &CookieJar {
+        &self.cookie_jar
+    }
 }
 
 impl Drop for ScReq {
     fn drop(&mut self) {
-        let _ = self.stream.sync_shutdown();
+        self.release();
     }
 }
 
 #[cfg(feature = "export")]
-unsafe impl Send for ScReq {}
\ No newline at end of file
+unsafe impl Send for ScReq {}
+
+///#### 流式响应体
+/// * 由`ScReq::send_stream`返回，响应体按需从连接读取，去掉chunked并解压后输出，内存占用不随响应体大小增长
+/// * 响应体读完前drop时，http/1.1的连接不再复用，h2的stream被取消
+pub struct ScResStream<'a> {
+    req: &'a mut ScReq,
+    response: Response,
+    body: ResBody,
+    //已解码但还没有被Read读取的数据
+    data: Cursor<Vec<u8>>,
+}
+
+impl<'a> ScResStream<'a> {
+    fn new(req: &'a mut ScReq, mut response: Response, mut body: ResBody) -> HlsResult<ScResStream<'a>> {
+        //和响应头一起读到并已解码的响应体
+        let data = response.raw_body().to_vec();
+        response.clear_raw();
+        body.check_end(req)?;
+        Ok(ScResStream { req, response, body, data: Cursor::new(data) })
+    }
+
+    pub fn header(&self) -> &Header { self.response.header() }
+
+    ///返回下一段解码后的响应体，None时响应体已读完
+    pub fn next_chunk(&mut self) -> HlsResult<Option<Vec<u8>>> {
+        //先返回Read没有读完的数据
+        let pos = self.data.position() as usize;
+        if pos < self.data.get_ref().len() {
+            let data = mem::take(self.data.get_mut()).split_off(pos);
+            self.data.set_position(0);
+            return Ok(Some(data));
+        }
+        self.body.next_chunk(self.req)
+    }
+}
+
+impl Read for ScResStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.data.position() >= self.data.get_ref().len() as u64 {
+            match self.next_chunk()? {
+                None => return Ok(0),
+                Some(data) => self.data = Cursor::new(data),
+            }
+        }
+        self.data.read(buf)
+    }
+}
+
+impl Drop for ScResStream<'_> {
+    fn drop(&mut self) {
+        self.body.abort(self.req);
+    }
+}
+
+///从连接按需读取响应体的状态
+struct ResBody {
+    decoder: BodyDecoder,
+    //http/1.1的读取缓冲区，h2使用ScReq中的h2_buffer
+    buffer: Buffer,
+    h2: Option<H2Pending>,
+}
+
+impl ResBody {
+    fn new(decoder: BodyDecoder, h2: Option<H2Pending>) -> ResBody {
+        ResBody { decoder, buffer: Buffer::with_capacity(16413), h2 }
+    }
+
+    fn next_chunk(&mut self, req: &mut ScReq) -> HlsResult<Option<Vec<u8>>> {
+        while !self.decoder.is_ended() {
+            let data = match &mut self.h2 {
+                None => {
+                    self.buffer.reset();
+                    match req.stream.sync_read(&mut self.buffer, &req.timeout) {
+                        Ok(_) => self.decoder.decode(self.buffer.filled())?,
+                        //没有content-length的响应体读到连接关闭
+                        Err(HlsError::PeerClosedConnection) => {
+                            req.keep_alive = false;
+                            self.decoder.finish()?
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Some((stream_id, body)) => match req.h2c_next_event(*stream_id, body)? {
+                    H2Event::Data(_, data, ended) => {
+                        let mut data = self.decoder.decode(&data)?;
+                        if ended { data.extend(self.decoder.finish()?); }
+                        data
+                    }
+                    H2Event::Headers(_, _, true) => self.decoder.finish()?,
+                    H2Event::Reset(_, code) => return Err(HlsError::StreamReset(code)),
+                    _ => vec![],
+                }
+            };
+            self.check_end(req)?;
+            if !data.is_empty() { return Ok(Some(data)); }
+        }
+        Ok(None)
+    }
+
+    ///响应体已读完，不再发送剩余的请求体
+    fn check_end(&mut self, req: &mut ScReq) -> HlsResult<()> {
+        if !self.decoder.is_ended() { return Ok(()); }
+        if let Some((stream_id, body)) = &mut self.h2 && body.take().is_some() { req.h2c_cancel(*stream_id)?; }
+        Ok(())
+    }
+
+    ///响应体没有读完时，http/1.1的连接不再复用，h2的stream被取消
+    fn abort(&mut self, req: &mut ScReq) {
+        if self.decoder.is_ended() { return; }
+        match self.h2 {
+            None => req.keep_alive = false,
+            Some((stream_id, _)) => if req.h2c_cancel(stream_id).is_err() { req.keep_alive = false; },
+        }
+    }
+}
+
+///#### server-sent events
+/// * 由`ScReq::event_source`返回，通过`next_event`或Iterator读取事件
+/// * 连接断开或读取失败时，等待retry(默认3秒)后带上Last-Event-ID重新请求，重新请求失败时返回错误，之后继续读取会再次重连
+/// * 重连的响应状态码不是200或content-type不是text/event-stream时不再重连，204时结束
+pub struct ScSse<'a> {
+    req: &'a mut ScReq,
+    body: Option<ResBody>,
+    parser: EventParser,
+    closed: bool,
+    //打开前的Accept/Cache-Control/Last-Event-ID，结束时恢复
+    saved: sse::SavedHeaders,
+}
+
+impl ScSse<'_> {
+    ///返回下一个事件，None时服务端已经结束
+    pub fn next_event(&mut self) -> HlsResult<Option<Event>> {
+        loop {
+            if let Some(event) = self.parser.next() { return Ok(Some(event)); }
+            if self.closed { return Ok(None); }
+            let Some(body) = &mut self.body else {
+                thread::sleep(self.parser.reconnect_time().unwrap_or(sse::RECONNECT_TIME));
+                self.connect()?;
+                continue;
+            };
+            match body.next_chunk(self.req) {
+                Ok(Some(data)) => self.parser.feed(&data),
+                //连接断开，重连
+                Ok(None) | Err(_) => self.disconnect(),
+            }
+        }
+    }
+
+    ///重连时发送的Last-Event-ID
+    pub fn last_event_id(&self) -> &str {
+        self.parser.last_event_id()
+    }
+
+    fn connect(&mut self) -> HlsResult<()> {
+        let header = &mut self.req.header;
+        header.set_accept("text/event-stream")?;
+        header.insert("Cache-Control", "no-cache")?;
+        match self.parser.last_event_id() {
+            "" => { header.remove("Last-Event-ID"); }
+            id => header.insert("Last-Event-ID", id)?,
+        }
+        let (mut response, mut body) = self.req.stream_head()?;
+        let res = sse::check_response(&response);
+        if res.is_err() || response.header().status().status_num() == 204 {
+            self.closed = true;
+            body.abort(self.req);
+            return res;
+        }
+        //和响应头一起读到的响应体
+        self.parser.feed(response.raw_body());
+        response.clear_raw();
+        body.check_end(self.req)?;
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(mut body) = self.body.take() { body.abort(self.req); }
+        self.parser.reset();
+    }
+}
+
+impl Iterator for ScSse<'_> {
+    type Item = HlsResult<Event>;
+
+    fn next(&mut self) -> Option<HlsResult<Event>> {
+        self.next_event().transpose()
+    }
+}
+
+impl Drop for ScSse<'_> {
+    fn drop(&mut self) {
+        self.disconnect();
+        sse::restore_headers(&mut self.req.header, mem::take(&mut self.saved));
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+#[cfg(anys)]
+use crate::error::{HlsError, HlsResult};
+#[cfg(anys)]
+use crate::packet::{Header, Response};
+
+///没有收到retry时重连前等待的时间
+#[cfg(anys)]
+pub(crate) const RECONNECT_TIME: Duration = Duration::from_secs(3);
+
+///#### server-sent events的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    event: String,
+    data: String,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    ///事件类型，没有event字段时为message
+    pub fn event(&self) -> &str { &self.event }
+
+    ///多个data字段用\n连接
+    pub fn data(&self) -> &str { &self.data }
+
+    ///分发时的最后一个事件id，没有或为空时为None
+    pub fn id(&self) -> Option<&str> { self.id.as_deref() }
+
+    ///这个事件中的retry字段，断开后重连前等待的时间
+    pub fn retry(&self) -> Option<Duration> { self.retry }
+}
+
+///#### text/event-stream解析器
+/// * 按WHATWG的规则解析，换行可以是\r\n、\n或\r，忽略注释和未知字段
+/// * 数据可以任意分块传入，解析出的事件通过Iterator取出
+#[derive(Default)]
+pub struct EventParser {
+    //未完成的行
+    line: Vec<u8>,
+    //上一块以\r结束，下一块开头的\n属于同一个换行
+    cr: bool,
+    //流开头的BOM已经处理
+    started: bool,
+    event: String,
+    data: String,
+    last_id: String,
+    retry: Option<Duration>,
+    //最近一次收到的retry，重连时使用
+    reconnect: Option<Duration>,
+    events: VecDeque<Event>,
+}
+
+impl EventParser {
+    pub fn new() -> EventParser {
+        EventParser::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        let mut data = data;
+        if !self.started {
+            //BOM可能被分在多个块中
+            let bom = b"\xef\xbb\xbf";
+            let len = (self.line.len() + data.len()).min(3);
+            let head = [self.line.as_slice(), data].concat();
+            if head[..len] != bom[..len] {
+                self.started = true;
+            } else if len == 3 {
+                self.started = true;
+                data = &data[3 - self.line.len()..];
+                self.line.clear();
+            } else {
+                self.line.extend_from_slice(data);
+                return;
+            }
+        }
+        for &b in data {
+            match b {
+                b'\n' if self.cr => self.cr = false,
+                b'\r' | b'\n' => {
+                    self.cr = b == b'\r';
+                    let line = String::from_utf8_lossy(&self.line).to_string();
+                    self.line.clear();
+                    self.handle_line(&line);
+                }
+                _ => {
+                    self.cr = false;
+                    self.line.push(b);
+                }
+            }
+        }
+    }
+
+    fn handle_line(&mut self, line: &str) {
+        if line.is_empty() { return self.dispatch(); }
+        if line.starts_with(':') { return; }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|x| x.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                    self.reconnect = self.retry;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ///空行时分发事件，data为空时只清空event
+    fn dispatch(&mut self) {
+        let event = if self.event.is_empty() { "message".to_string() } else { self.event.clone() };
+        self.event.clear();
+        let retry = self.retry.take();
+        if self.data.is_empty() { return; }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        let id = if self.last_id.is_empty() { None } else { Some(self.last_id.clone()) };
+        self.events.push_back(Event { event, data, id, retry });
+    }
+
+    ///连接断开时丢弃未完成的事件，最后的事件id和retry保留
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.cr = false;
+        self.started = false;
+        self.event.clear();
+        self.data.clear();
+        self.retry = None;
+    }
+
+    ///重连时作为Last-Event-ID发送
+    pub fn last_event_id(&self) -> &str { &self.last_id }
+
+    ///最近一次收到的retry
+    pub fn reconnect_time(&self) -> Option<Duration> { self.reconnect }
+}
+
+impl Iterator for EventParser {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+}
+
+///状态码必须是200(204时没有事件)，content-type必须是text/event-stream
+#[cfg(anys)]
+pub(crate) fn check_response(response: &Response) -> HlsResult<()> {
+    let status = response.header().status().status_num();
+    //响应头的content-type保存为字符串
+    let content_type = response.header().get("content-type").map(|x| x.to_string()).unwrap_or_default();
+    let event_stream = content_type.split(';').next().is_some_and(|x| x.trim().eq_ignore_ascii_case("text/event-stream"));
+    match status {
+        204 => Ok(()),
+        200 if event_stream => Ok(()),
+        200 => Err(HlsError::Protocol(format!("event stream with content-type: {}", content_type))),
+        _ => Err(HlsError::Status(status)),
+    }
+}
+
+///SSE请求会修改的请求头
+#[cfg(anys)]
+const SSE_HEADERS: [&str; 3] = ["Accept", "Cache-Control", "Last-Event-ID"];
+
+///SSE请求会修改的请求头原来的值
+#[cfg(anys)]
+pub(crate) type SavedHeaders = Vec<(&'static str, Option<String>)>;
+
+#[cfg(anys)]
+pub(crate) fn save_headers(header: &Header) -> SavedHeaders {
+    SSE_HEADERS.iter().map(|x| (*x, header.get(x).map(|v| v.to_string()))).collect()
+}
+
+///恢复为打开SSE前的请求头，原来没有的请求头被删除
+#[cfg(anys)]
+pub(crate) fn restore_headers(header: &mut Header, saved: SavedHeaders) {
+    for (name, value) in saved {
+        match value {
+            None => { header.remove(name); }
+            Some(value) => { let _ = header.insert(name, value); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventParser;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sse_parser() {
+        let stream = "\u{feff}: comment\r\nevent: add\r\ndata: 1\r\ndata:2\r\nid: 7\r\nretry: 1500\r\n\r\ndata\n\ndata: a\rid\r\rretry: x\nevent: e\n\ndata: tail";
+        //分成各种大小的块，结果都一样
+        for size in [1, 2, 3, 5, stream.len()] {
+            let mut parser = EventParser::new();
+            for chunk in stream.as_bytes().chunks(size) { parser.feed(chunk); }
+            let events = parser.by_ref().collect::<Vec<_>>();
+            assert_eq!(events.len(), 3);
+            assert_eq!((events[0].event(), events[0].data(), events[0].id(), events[0].retry()), ("add", "1\n2", Some("7"), Some(Duration::from_millis(1500))));
+            assert_eq!((events[1].event(), events[1].data(), events[1].id(), events[1].retry()), ("message", "", Some("7"), None));
+            //空的id清空最后的事件id
+            assert_eq!((events[2].event(), events[2].data(), events[2].id()), ("message", "a", None));
+            assert_eq!(parser.reconnect_time(), Some(Duration::from_millis(1500)));
+            //没有空行结束的事件在断开时丢弃
+            parser.reset();
+            parser.feed(b"event: e\n\nid: 9\n\n");
+            assert!(parser.next().is_none());
+            assert_eq!(parser.last_event_id(), "9");
+        }
+    }
+
+    #[cfg(anys)]
+    #[test]
+    fn test_restore_headers() {
+        use crate::packet::Header;
+        let mut header = Header::new_req_h1();
+        header.set_accept("application/json").unwrap();
+        header.remove("Cache-Control");
+        let saved = super::save_headers(&header);
+        header.set_accept("text/event-stream").unwrap();
+        header.insert("Cache-Control", "no-cache").unwrap();
+        header.insert("Last-Event-ID", "3").unwrap();
+        super::restore_headers(&mut header, saved);
+        assert_eq!(header.get("accept").map(|x| x.to_string()), Some("application/json".to_string()));
+        assert!(header.get("cache-control").is_none());
+        assert!(header.get("last-event-id").is_none());
+    }
+}
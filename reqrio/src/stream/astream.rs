@@ -1,18 +1,22 @@
 #[cfg(cls_async)]
 use super::async_stream::TlsStream;
 use crate::error::HlsResult;
-use crate::stream::ConnParam;
+use crate::stream::{eyeballs, ConnParam};
 use crate::Buffer;
 #[cfg(feature = "std_async")]
 use crate::ALPN;
 #[cfg(all(feature = "std_async", not(feature = "cls_sync")))]
-use rustls::pki_types::{DnsName, ServerName};
+use rustls::pki_types::ServerName;
 #[cfg(all(feature = "std_async", not(feature = "cls_sync")))]
 use rustls::{ClientConfig, RootCertStore};
 #[cfg(all(feature = "std_async", not(feature = "cls_sync")))]
 use std::sync::Arc;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::time::Duration;
 use tokio::io;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 #[cfg(any(feature = "cls_async", feature = "std_async"))]
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 #[cfg(any(feature = "cls_async", feature = "std_async"))]
@@ -43,10 +47,10 @@ pub trait TimeoutRW<S: AsyncReadExt + AsyncWriteExt + Unpin> {
         }
     }
 
-    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    async fn write(&mut self, buf: &[u8]) -> io::Result<()> {
         match self.write_timeout() {
-            None => self.stream().write(buf).await,
-            Some(timeout) => tokio::time::timeout(timeout, self.stream().write(buf)).await?
+            None => self.stream().write_all(buf).await,
+            Some(timeout) => tokio::time::timeout(timeout, self.stream().write_all(buf)).await?
         }
     }
 
@@ -66,18 +70,62 @@ pub trait TimeoutRW<S: AsyncReadExt + AsyncWriteExt + Unpin> {
     }
 }
 
+///到目标(无代理)或代理的连接，https代理时是到代理的tls连接
+pub enum AsyncProxyStream {
+    Tcp(TcpStream),
+    #[cfg(any(cls_async, std_async))]
+    Tls(Box<TlsStream<AsyncProxyStream>>),
+}
+
+impl AsyncRead for AsyncProxyStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AsyncProxyStream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(any(cls_async, std_async))]
+            AsyncProxyStream::Tls(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for AsyncProxyStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            AsyncProxyStream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(any(cls_async, std_async))]
+            AsyncProxyStream::Tls(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AsyncProxyStream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(any(cls_async, std_async))]
+            AsyncProxyStream::Tls(s) => Pin::new(s.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AsyncProxyStream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(any(cls_async, std_async))]
+            AsyncProxyStream::Tls(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
 #[cfg(any(feature = "cls_async", feature = "std_async"))]
 pub struct AsyncTcpStream {
-    stream: TcpStream,
+    stream: AsyncProxyStream,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
 }
 
 #[cfg(any(feature = "cls_async", feature = "std_async"))]
 impl AsyncTcpStream {
-    pub async fn connect_timeout(addr: impl tokio::net::ToSocketAddrs, timeout: Duration) -> HlsResult<AsyncTcpStream> {
+    ///多个地址时使用Happy Eyeballs
+    pub async fn connect_timeout(addrs: Vec<SocketAddr>, timeout: Duration) -> io::Result<AsyncTcpStream> {
         Ok(AsyncTcpStream {
-            stream: tokio::time::timeout(timeout, TcpStream::connect(addr)).await??,
+            stream: AsyncProxyStream::Tcp(eyeballs::connect_async(addrs, timeout).await?),
             read_timeout: None,
             write_timeout: None,
         })
@@ -90,13 +138,39 @@ impl AsyncTcpStream {
     pub fn set_write_timeout(&mut self, write_timeout: Duration) {
         self.write_timeout = Some(write_timeout);
     }
-    pub fn into_inner(self) -> TcpStream {
+    pub fn into_inner(self) -> AsyncProxyStream {
         self.stream
     }
+
+    ///与https代理tls握手，之后的读写都经过这个tls连接
+    #[cfg(cls_async)]
+    pub async fn into_tls(self, param: ConnParam<'_>) -> HlsResult<AsyncTcpStream> {
+        let tls = AsyncTlsStream::connect_timeout(param, self).await?;
+        Ok(AsyncTcpStream {
+            stream: AsyncProxyStream::Tls(Box::new(tls.stream)),
+            read_timeout: tls.read_timeout,
+            write_timeout: tls.write_timeout,
+        })
+    }
+
+    #[cfg(std_async)]
+    pub async fn into_tls(self, param: ConnParam<'_>) -> HlsResult<AsyncTcpStream> {
+        let tls = StdAsyncTlsStream::connect_timeout(param, self).await?;
+        Ok(AsyncTcpStream {
+            stream: AsyncProxyStream::Tls(Box::new(tls.stream)),
+            read_timeout: tls.read_timeout,
+            write_timeout: tls.write_timeout,
+        })
+    }
+
+    #[cfg(not(any(cls_async, std_async)))]
+    pub async fn into_tls(self, _param: ConnParam<'_>) -> HlsResult<AsyncTcpStream> {
+        Err("tls proxy not supported".into())
+    }
 }
 
-impl TimeoutRW<TcpStream> for AsyncTcpStream {
-    fn stream(&mut self) -> &mut TcpStream {
+impl TimeoutRW<AsyncProxyStream> for AsyncTcpStream {
+    fn stream(&mut self) -> &mut AsyncProxyStream {
         &mut self.stream
     }
 
@@ -111,7 +185,7 @@ impl TimeoutRW<TcpStream> for AsyncTcpStream {
 
 #[cfg(std_async)]
 pub struct StdAsyncTlsStream {
-    stream: TlsStream<TcpStream>,
+    stream: TlsStream<AsyncProxyStream>,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
 }
@@ -119,8 +193,8 @@ pub struct StdAsyncTlsStream {
 #[cfg(std_async)]
 impl StdAsyncTlsStream {
     pub async fn connect_timeout(param: ConnParam<'_>, tcp: AsyncTcpStream) -> HlsResult<StdAsyncTlsStream> {
-        let dns_name = DnsName::try_from(param.url.addr().host().to_string())?;
-        let server_name = ServerName::DnsName(dns_name);
+        //ip的host使用ip地址校验证书
+        let server_name = ServerName::try_from(param.url.addr().host().to_string())?;
         let mut root = RootCertStore::empty();
         root.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
         let mut config = ClientConfig::builder()
@@ -135,7 +209,7 @@ impl StdAsyncTlsStream {
             ]
         }
         let connector = TlsConnector::from(Arc::new(config));
-        let stream = tokio::time::timeout(param.timeout.connect(), connector.connect(server_name, tcp.stream)).await??;
+        let stream = tokio::time::timeout(param.timeout.tls_handshake(), connector.connect(server_name, tcp.stream)).await??;
         Ok(StdAsyncTlsStream {
             stream,
             read_timeout: tcp.read_timeout,
@@ -178,8 +252,8 @@ impl StdAsyncTlsStream {
 }
 
 #[cfg(std_async)]
-impl TimeoutRW<TlsStream<TcpStream>> for StdAsyncTlsStream {
-    fn stream(&mut self) -> &mut TlsStream<TcpStream> {
+impl TimeoutRW<TlsStream<AsyncProxyStream>> for StdAsyncTlsStream {
+    fn stream(&mut self) -> &mut TlsStream<AsyncProxyStream> {
         &mut self.stream
     }
 
@@ -195,7 +269,7 @@ impl TimeoutRW<TlsStream<TcpStream>> for StdAsyncTlsStream {
 
 #[cfg(cls_async)]
 pub struct AsyncTlsStream {
-    stream: TlsStream<TcpStream>,
+    stream: TlsStream<AsyncProxyStream>,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
 }
@@ -203,10 +277,10 @@ pub struct AsyncTlsStream {
 #[cfg(cls_async)]
 impl AsyncTlsStream {
     pub async fn connect_timeout(param: ConnParam<'_>, tcp: AsyncTcpStream) -> HlsResult<AsyncTlsStream> {
-        let connect_timeout = param.timeout.connect();
+        let handshake_timeout = param.timeout.tls_handshake();
         let stream = TlsConnector::from(param).connect(tcp.stream); //TlsStream::connect(param, tcp.stream);
         Ok(AsyncTlsStream {
-            stream: tokio::time::timeout(connect_timeout, stream).await??,
+            stream: tokio::time::timeout(handshake_timeout, stream).await??,
             read_timeout: tcp.read_timeout,
             write_timeout: tcp.write_timeout,
         })
@@ -218,8 +292,8 @@ impl AsyncTlsStream {
 }
 
 #[cfg(cls_async)]
-impl TimeoutRW<TlsStream<TcpStream>> for AsyncTlsStream {
-    fn stream(&mut self) -> &mut TlsStream<TcpStream> {
+impl TimeoutRW<TlsStream<AsyncProxyStream>> for AsyncTlsStream {
+    fn stream(&mut self) -> &mut TlsStream<AsyncProxyStream> {
         &mut self.stream
     }
 
@@ -1,5 +1,5 @@
 use crate::error::{HlsError, HlsResult};
-use crate::stream::ConnParam;
+use crate::stream::{ConnParam, MAX_RECORD_LEN};
 use crate::{Buffer, ALPN};
 use reqtls::*;
 use std::io::Error;
@@ -48,13 +48,20 @@ pub struct TlsStream<S> {
     write_buffer: Buffer,
     shutdown_wrote: bool,
     wrote_len: usize,
+    //当前record已经写出的长度
+    record_wrote: usize,
+    //write_buffer中是KeyUpdate记录
+    key_updating: bool,
     pending: Vec<usize>,
+    //解密后超出读取缓冲区的数据
+    read_pending: Vec<u8>,
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
     pub async fn connect(mut connector: TlsConnector<'_>, mut stream: S) -> HlsResult<TlsStream<S>> {
         let client_random = rand::random::<[u8; 32]>();
         let mut conn = Connection::new(client_random.to_vec());
+        conn.set_verifier(connector.fingerprint.verifier(connector.sni));
         let mut record = RecordLayer::from_bytes(connector.fingerprint.client_hello_mut(), false)?;
         let message = record.messages.get_mut(0).ok_or(RlsError::ClientHelloNone)?;
         message.client_mut().ok_or(HlsError::NonePointer)?.set_random(client_random.clone());
@@ -64,7 +71,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
             ALPN::Http20 => message.client_mut().ok_or(HlsError::NonePointer)?.add_h2_alpn(),
             _ => message.client_mut().ok_or(HlsError::NonePointer)?.remove_h2_alpn()
         }
-        message.client_mut().ok_or(HlsError::NonePointer)?.remove_tls13();
+        conn.set_key_shares(message.client_mut().ok_or(HlsError::NonePointer)?)?;
         let bs = record.handshake_bytes();
         conn.update_session(&bs[5..])?;
         stream.write(&bs).await?;
@@ -73,20 +80,28 @@ impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
             stream,
             conn,
             handshake_finished: false,
-            read_buffer: Buffer::with_capacity(16413),
-            write_buffer: Buffer::with_capacity(16413),
+            read_buffer: Buffer::with_capacity(MAX_RECORD_LEN),
+            write_buffer: Buffer::with_capacity(MAX_RECORD_LEN),
             shutdown_wrote: false,
             wrote_len: 0,
+            record_wrote: 0,
+            key_updating: false,
             pending: vec![],
+            read_pending: vec![],
         };
         while !stream.handshake_finished {
             stream.read_packet().await?;
-            stream.handle_message(&mut connector).await?;
+            match stream.conn.is_tls13() {
+                true => stream.handle_tls13(&mut connector).await?,
+                false => stream.handle_message(&mut connector).await?
+            }
         }
 
-        stream.read_packet().await?;
-        let mut record = RecordLayer::from_bytes(stream.read_buffer.filled_mut(), stream.handshake_finished)?;
-        stream.conn.read_message(&mut record)?;
+        if !stream.conn.is_tls13() {
+            stream.read_packet().await?;
+            let mut record = RecordLayer::from_bytes(stream.read_buffer.filled_mut(), stream.handshake_finished)?;
+            stream.conn.read_message(&mut record)?;
+        }
         stream.read_buffer.reset();
         stream.write_buffer.reset();
         Ok(stream)
@@ -97,18 +112,33 @@ impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
         self.read_buffer.async_read_limit(&mut self.stream, 5).await?;
         if self.read_buffer.len() < 5 { return Err(HlsError::InvalidHeadSize)?; }
         let payload_len = u16::from_be_bytes([self.read_buffer[3], self.read_buffer[4]]) as usize;
-        // if payload_len > self.read_buffer.capacity() - self.read_buffer.len() {
-        //
-        // }
+        //不是tls记录，例如trojan密码错误时服务端返回的http响应
+        if payload_len + 5 > self.read_buffer.capacity() { return Err(format!("invalid tls record length: {}", payload_len).into()); }
         while self.read_buffer.len() - 5 < payload_len {
             // println!("{} {} {:?} {}", payload_len, self.read_buffer.len(), &self.read_buffer[..10],String::from_utf8_lossy(&self.read_buffer[..10]).to_string());
             self.read_buffer.async_read_limit(&mut self.stream, payload_len + 5 - self.read_buffer.len()).await?;
         }
-        if !self.handshake_finished { self.conn.update_session(&self.read_buffer.filled()[5..])?; }
+        //tls1.3的握手消息由Connection解密后计算摘要
+        if !self.handshake_finished && !self.conn.is_tls13() { self.conn.update_session(&self.read_buffer.filled()[5..])?; }
+        Ok(())
+    }
+
+    async fn handle_tls13(&mut self, connector: &mut TlsConnector<'_>) -> HlsResult<()> {
+        if let Some(finished) = self.conn.read_handshake13(self.read_buffer.filled_mut())? {
+            //兼容模式，Finished前发送ChangeCipherSpec
+            self.stream.write_all(connector.fingerprint.change_cipher_spec()).await?;
+            self.stream.write_all(&finished).await?;
+            self.stream.flush().await?;
+            self.handshake_finished = true;
+        }
         Ok(())
     }
 
     async fn handle_message(&mut self, connector: &mut TlsConnector<'_>) -> HlsResult<()> {
+        //握手阶段对端发送的明文alert，例如handshake_failure
+        if let [0x15, _, _, _, _, level, description, ..] = self.read_buffer.filled() && !self.handshake_finished {
+            return Err(RlsError::Alert(*level, *description).into());
+        }
         let record = RecordLayer::from_bytes(self.read_buffer.filled_mut(), self.handshake_finished)?;
         match record.context_type {
             RecordType::CipherSpec => self.handshake_finished = true,
@@ -117,13 +147,13 @@ impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
                 for message in record.messages {
                     match message {
                         Message::ServerHello(v) => {
-                            println!("{:#?}-{}", v.cipher_suite, connector.sni);
                             self.conn.set_by_server_hello(v)?;
+                            if let Some(hello) = self.conn.take_retry_hello() {
+                                self.stream.write_all(&hello).await?;
+                            }
                         }
-                        Message::ServerKeyExchange(v) => {
-                            // println!("{:#?}", v);
-                            self.conn.set_by_exchange_key(v.hellman_param().pub_key().clone(), v.hellman_param().named_curve().clone())
-                        }
+                        Message::Certificate(v) => self.conn.set_by_certificate(&v)?,
+                        Message::ServerKeyExchange(v) => self.conn.set_by_server_key_exchange(&v)?,
                         Message::ServerHelloDone(_) => {
                             let keypair = PriKey::new(self.conn.named_curve())?;
                             let client_pub_key = keypair.pub_key();
@@ -163,40 +193,70 @@ impl<S: AsyncRead + Unpin> AsyncRead for TlsStream<S> {
     fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
         if self.shutdown_wrote { return Poll::Ready(Ok(())); }
         let stream = self.get_mut();
+        if !stream.read_pending.is_empty() {
+            let len = buf.remaining().min(stream.read_pending.len());
+            buf.put_slice(&stream.read_pending[..len]);
+            stream.read_pending.drain(..len);
+            return Poll::Ready(Ok(()));
+        }
+        let filled = buf.filled().len();
         loop {
-            let mut rd = ReadBuf::new(stream.read_buffer.unfilled_mut());
-            match Pin::new(&mut stream.stream).poll_read(cx, &mut rd) {
-                Poll::Ready(Ok(_)) => {
-                    let fl = rd.filled().len();
-                    if fl == 0 { return Poll::Ready(Ok(())); }
-                    let nl = stream.read_buffer.len() + fl;
-                    stream.read_buffer.set_len(nl);
-                    let pdl = u16::from_be_bytes([stream.read_buffer[3], stream.read_buffer[4]]) as usize;
-                    if stream.read_buffer.len() >= pdl + 5 { break; }
+            //缓冲区中没有完整的记录时继续读取
+            let record_len = match stream.read_buffer.len() >= 5 {
+                true => u16::from_be_bytes([stream.read_buffer[3], stream.read_buffer[4]]) as usize + 5,
+                false => 5
+            };
+            if record_len > stream.read_buffer.capacity() {
+                return Poll::Ready(Err(HlsError::from(format!("invalid tls record length: {}", record_len - 5)).into()));
+            }
+            let complete = stream.read_buffer.len() >= record_len;
+            if !complete {
+                let mut rd = ReadBuf::new(stream.read_buffer.unfilled_mut());
+                match Pin::new(&mut stream.stream).poll_read(cx, &mut rd) {
+                    Poll::Ready(Ok(_)) => {
+                        let fl = rd.filled().len();
+                        if fl == 0 { return Poll::Ready(Ok(())); }
+                        let nl = stream.read_buffer.len() + fl;
+                        stream.read_buffer.set_len(nl);
+                        continue;
+                    }
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
                 }
-                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                Poll::Pending => return Poll::Pending,
             }
-        }
-        let mut read = 0;
-        while let Ok(mut record) = RecordLayer::from_bytes(&mut stream.read_buffer.filled_mut()[read..], stream.handshake_finished) {
-            let rt = record.context_type.as_u8();
-            let rl = record.len;
-            let pdl = stream.conn.read_message(&mut record)?;
-            let aead = stream.conn.aead().ok_or(RlsError::AeadNone)?;
-            if rt == 0x15 && &stream.read_buffer[aead.payload_start()..aead.payload_start() + pdl] == &[1, 0] {
-                return Poll::Ready(Ok(()));
+            let mut read = 0;
+            let mut closed = false;
+            while let Ok(mut record) = RecordLayer::from_bytes(&mut stream.read_buffer.filled_mut()[read..], stream.handshake_finished) {
+                let rl = record.len;
+                let pdl = stream.conn.read_message(&mut record)?;
+                let start = read + stream.conn.payload_start();
+                match stream.conn.read_type() {
+                    RecordType::Alert => match stream.read_buffer[start..start + pdl] {
+                        [1, 0] => closed = true,
+                        [level, description] if level != 1 => return Poll::Ready(Err(RlsError::Alert(level, description).into())),
+                        //warning级别的alert忽略
+                        _ => {}
+                    },
+                    //tls1.3握手后的NewSessionTicket/KeyUpdate
+                    RecordType::HandShake => {}
+                    _ => {
+                        let data = &stream.read_buffer[start..start + pdl];
+                        let len = buf.remaining().min(data.len());
+                        buf.put_slice(&data[..len]);
+                        stream.read_pending.extend_from_slice(&data[len..]);
+                    }
+                }
+                read += rl as usize + 5;
+                if closed { break; }
             }
-            buf.put_slice(&stream.read_buffer[read + aead.payload_start()..read + aead.payload_start() + pdl]);
-            read += rl as usize + 5;
-        }
-        if read < stream.read_buffer.len() {
-            stream.read_buffer.copy_within(read..stream.read_buffer.len(), 0);
-            stream.read_buffer.set_len(stream.read_buffer.len() - read);
-        } else {
-            stream.read_buffer.reset();
+            if read < stream.read_buffer.len() {
+                stream.read_buffer.copy_within(read..stream.read_buffer.len(), 0);
+                stream.read_buffer.set_len(stream.read_buffer.len() - read);
+            } else {
+                stream.read_buffer.reset();
+            }
+            if closed || buf.filled().len() > filled { return Poll::Ready(Ok(())); }
         }
-        Poll::Ready(Ok(()))
     }
 }
 
@@ -211,27 +271,37 @@ impl<S: AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
         }
         loop {
             if stream.pending.len() == 0 { break; }
-            let aead = stream.conn.aead().ok_or(RlsError::AeadNone)?;
-            let record_len = aead.encrypted_payload_len(chucks[stream.pending[0]].len()) + 5;
             if stream.write_buffer.len() == 0 {
-                let push_len = stream.write_buffer.push_slice_in(aead.payload_start(), chucks[stream.pending[0]]);
-                let record_len = aead.encrypted_payload_len(push_len) + 5;
-                stream.write_buffer.set_len(record_len);
-                stream.conn.make_message(RecordType::ApplicationData, &mut stream.write_buffer[..], push_len)?;
-                stream.wrote_len += chucks[stream.pending[0]].len();
+                match stream.conn.take_key_update() {
+                    //对端请求更新密钥，先发送KeyUpdate
+                    Some(record) => {
+                        let len = stream.write_buffer.push_slice_in(0, &record);
+                        stream.write_buffer.set_len(len);
+                        stream.key_updating = true;
+                    }
+                    None => {
+                        let push_len = stream.write_buffer.push_slice_in(stream.conn.payload_start(), chucks[stream.pending[0]]);
+                        let record_len = stream.conn.encrypted_payload_len(push_len) + 5;
+                        stream.write_buffer.set_len(record_len);
+                        stream.conn.make_message(RecordType::ApplicationData, &mut stream.write_buffer[..], push_len)?;
+                        stream.wrote_len += chucks[stream.pending[0]].len();
+                    }
+                }
             }
-            match Pin::new(&mut stream.stream).poll_write(cx, &stream.write_buffer[..record_len]) {
+            let record_len = stream.write_buffer.len();
+            match Pin::new(&mut stream.stream).poll_write(cx, &stream.write_buffer[stream.record_wrote..record_len]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(Error::from(std::io::ErrorKind::WriteZero))),
+                //底层只写出了部分record
+                Poll::Ready(Ok(len)) if stream.record_wrote + len < record_len => stream.record_wrote += len,
                 Poll::Ready(Ok(_)) => {
-                    stream.pending.remove(0);
+                    if !std::mem::take(&mut stream.key_updating) { stream.pending.remove(0); }
                     stream.write_buffer.reset();
+                    stream.record_wrote = 0;
                 }
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
                 Poll::Pending => return Poll::Pending,
             }
         }
-        if stream.wrote_len > buf.len() {
-            println!("write {} {}", stream.wrote_len, buf.len());
-        }
         Poll::Ready(Ok(stream.wrote_len))
     }
 
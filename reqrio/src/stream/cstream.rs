@@ -1,21 +1,21 @@
 use crate::error::HlsResult;
-use rustls::pki_types::{DnsName, ServerName};
+use rustls::pki_types::ServerName;
 use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
 use std::io::{Read, Write};
-use std::net::{Shutdown, TcpStream};
+use std::net::TcpStream;
 use std::sync::Arc;
 use crate::alpn::ALPN;
-use crate::stream::ConnParam;
+use crate::stream::{ConnParam, ProxyStream};
 
 pub struct StdSyncTlsStream {
-    stream: StreamOwned<ClientConnection, TcpStream>,
+    stream: StreamOwned<ClientConnection, ProxyStream>,
 }
 
 
 impl StdSyncTlsStream {
-    pub fn connect(param: ConnParam, mut stream: TcpStream) -> HlsResult<StdSyncTlsStream> {
-        let dns_name = DnsName::try_from(param.url.addr().host().to_string())?;
-        let server_name = ServerName::DnsName(dns_name);
+    pub fn connect(param: ConnParam, mut stream: ProxyStream) -> HlsResult<StdSyncTlsStream> {
+        //ip的host使用ip地址校验证书
+        let server_name = ServerName::try_from(param.url.addr().host().to_string())?;
         let mut root = RootCertStore::empty();
         root.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
         let mut config = ClientConfig::builder()
@@ -37,9 +37,11 @@ impl StdSyncTlsStream {
     pub fn shutdown(&mut self) -> std::io::Result<()> {
         self.stream.conn.send_close_notify();
         self.stream.conn.complete_io(&mut self.stream.sock)?;
-        self.stream.sock.shutdown(Shutdown::Both)
+        Ok(self.stream.sock.shutdown()?)
     }
 
+    pub fn get_ref(&self) -> &TcpStream { self.stream.get_ref().get_ref() }
+
     pub fn alpn(&self) -> Option<ALPN> {
         match self.stream.conn.alpn_protocol() {
             None => None,
@@ -0,0 +1,141 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+#[cfg(sync)]
+use std::net::TcpStream;
+#[cfg(sync)]
+use std::sync::mpsc;
+#[cfg(sync)]
+use std::thread;
+#[cfg(sync)]
+use std::time::Instant;
+#[cfg(aync)]
+use std::future::{poll_fn, Future};
+#[cfg(aync)]
+use std::pin::{pin, Pin};
+#[cfg(aync)]
+use std::task::Poll;
+
+///RFC 8305推荐的连接尝试间隔
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+///按地址族交替排列，解析结果中第一个地址的地址族在前，同一地址族保持原来的顺序
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first = addrs.first().map(|x| x.is_ipv6()).unwrap_or_default();
+    let (mut first, mut second): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|x| x.is_ipv6() == first);
+    let mut res = Vec::with_capacity(first.len() + second.len());
+    first.reverse();
+    second.reverse();
+    while !first.is_empty() || !second.is_empty() {
+        res.extend(first.pop());
+        res.extend(second.pop());
+    }
+    res
+}
+
+///#### Happy Eyeballs(RFC 8305)
+/// * ipv6和ipv4的地址交替尝试，每次尝试开始ATTEMPT_DELAY后还没有连接成功时开始下一次尝试，失败时立即开始下一次
+/// * 最先连接成功的连接被使用，其他连接在成功后关闭
+/// * 所有尝试共用timeout，都失败时返回最后一个错误
+#[cfg(sync)]
+pub(crate) fn connect_sync(addrs: Vec<SocketAddr>, timeout: Duration) -> io::Result<TcpStream> {
+    let deadline = Instant::now() + timeout;
+    let mut addrs = interleave(addrs).into_iter();
+    if addrs.len() == 1 { return TcpStream::connect_timeout(&addrs.as_slice()[0], timeout); }
+    let (sender, receiver) = mpsc::channel();
+    let mut running = 0;
+    let mut error = None;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() { return Err(io::ErrorKind::TimedOut.into()); }
+        if let Some(addr) = addrs.next() {
+            let sender = sender.clone();
+            //已经有连接成功时send失败，连接被丢弃
+            thread::spawn(move || { let _ = sender.send(TcpStream::connect_timeout(&addr, remaining)); });
+            running += 1;
+        }
+        if running == 0 { return Err(error.unwrap_or(io::ErrorKind::NotFound.into())); }
+        let wait = match addrs.as_slice().is_empty() {
+            true => remaining,
+            false => ATTEMPT_DELAY.min(remaining),
+        };
+        match receiver.recv_timeout(wait) {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => {
+                running -= 1;
+                error = Some(e);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(aync)]
+type Attempt = Pin<Box<dyn Future<Output=io::Result<tokio::net::TcpStream>> + Send>>;
+
+///和connect_sync相同，所有尝试在当前任务中同时poll，返回时未完成的尝试被取消
+#[cfg(aync)]
+pub(crate) async fn connect_async(addrs: Vec<SocketAddr>, timeout: Duration) -> io::Result<tokio::net::TcpStream> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut addrs = interleave(addrs).into_iter();
+    let mut attempts: Vec<Attempt> = vec![];
+    let mut error = None;
+    loop {
+        if let Some(addr) = addrs.next() { attempts.push(Box::pin(tokio::net::TcpStream::connect(addr))); }
+        if attempts.is_empty() { return Err(error.unwrap_or(io::ErrorKind::NotFound.into())); }
+        let wake = match addrs.as_slice().is_empty() {
+            true => deadline,
+            false => deadline.min(tokio::time::Instant::now() + ATTEMPT_DELAY),
+        };
+        let mut sleep = pin!(tokio::time::sleep_until(wake));
+        let res = poll_fn(|cx| {
+            let ready = attempts.iter_mut().enumerate().find_map(|(i, x)| match x.as_mut().poll(cx) {
+                Poll::Ready(res) => Some((i, res)),
+                Poll::Pending => None,
+            });
+            if let Some((i, res)) = ready {
+                drop(attempts.swap_remove(i));
+                return Poll::Ready(Some(res));
+            }
+            sleep.as_mut().poll(cx).map(|_| None)
+        }).await;
+        match res {
+            Some(Ok(stream)) => return Ok(stream),
+            Some(Err(e)) => error = Some(e),
+            None if wake == deadline => return Err(io::ErrorKind::TimedOut.into()),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interleave;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn test_interleave() {
+        let addrs = ["[::1]:1", "[::2]:1", "[::3]:1", "127.0.0.1:1", "127.0.0.2:1"].map(|x| x.parse::<SocketAddr>().unwrap());
+        let res = interleave(addrs.to_vec());
+        assert_eq!(res, vec![addrs[0], addrs[3], addrs[1], addrs[4], addrs[2]]);
+        let res = interleave(vec![addrs[3], addrs[0], addrs[4], addrs[1]]);
+        assert_eq!(res, vec![addrs[3], addrs[0], addrs[4], addrs[1]]);
+        assert!(interleave(vec![]).is_empty());
+    }
+
+    #[cfg(sync)]
+    #[test]
+    fn test_happy_eyeballs() {
+        use std::net::TcpListener;
+        use std::time::{Duration, Instant};
+        //第一个地址不能连接时使用下一个地址
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let start = Instant::now();
+        let stream = super::connect_sync(vec![closed, listener.local_addr().unwrap()], Duration::from_secs(3)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap().port(), port);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(super::connect_sync(vec![closed, closed], Duration::from_secs(3)).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::error::{HlsError, HlsResult, TimeoutPhase};
+use crate::packet::{Frame, H2ErrorCode, H2Event, H2Fingerprint, H2Session, HeaderKey};
+use crate::stream::Stream;
+use crate::Buffer;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+
+struct H2State {
+    session: H2Session,
+    //进行中的stream
+    streams: HashMap<u32, UnboundedSender<HlsResult<H2Event>>>,
+    //连接关闭的原因
+    closed: Option<HlsError>,
+}
+
+struct H2Shared {
+    state: Mutex<H2State>,
+    //stream结束或SETTINGS变化时唤醒等待并发数的请求
+    released: Notify,
+}
+
+///#### h2多路复用连接
+/// * 后台任务独占Stream，按stream_id把帧分发到各请求的channel，多个请求可以同时使用一个连接
+/// * 同时进行的stream数不超过对端的SETTINGS_MAX_CONCURRENT_STREAMS，超出时等待
+/// * 所有clone都drop后后台任务退出，连接关闭
+#[derive(Clone)]
+pub(crate) struct H2Conn {
+    shared: Arc<H2Shared>,
+    writer: UnboundedSender<Vec<u8>>,
+}
+
+impl H2Conn {
+    ///发送连接前言并启动后台任务
+    pub async fn handshake(mut stream: Stream, fingerprint: H2Fingerprint) -> HlsResult<H2Conn> {
+        let session = H2Session::with_fingerprint(fingerprint);
+        stream.async_write(&session.preface()).await?;
+        let shared = Arc::new(H2Shared {
+            state: Mutex::new(H2State { session, streams: HashMap::new(), closed: None }),
+            released: Notify::new(),
+        });
+        let (writer, receiver) = unbounded_channel();
+        tokio::spawn(H2Conn::run(shared.clone(), stream, receiver));
+        Ok(H2Conn { shared, writer })
+    }
+
+    ///已关闭或收到GOAWAY，不能再发送新的请求
+    pub fn is_closed(&self) -> bool {
+        self.writer.is_closed() || self.shared.state.lock().map(|x| x.closed.is_some() || x.session.is_going_away()).unwrap_or(true)
+    }
+
+    ///发送请求，并发数已满时等待其他stream结束
+    /// * end_stream为false时请求体通过H2Stream::send_data继续发送
+    pub async fn request(&self, headers: Vec<HeaderKey>, body: Vec<u8>, end_stream: bool) -> HlsResult<H2Stream> {
+        loop {
+            let released = self.shared.released.notified();
+            {
+                let mut state = self.shared.state.lock().map_err(|e| e.to_string())?;
+                if let Some(e) = &state.closed { return Err(e.duplicate()); }
+                if (state.streams.len() as u32) < state.session.max_concurrent_streams() {
+                    //hpack和stream_id都要求按顺序写出，所以编码和发送都在锁内
+                    let stream_id = state.session.open_stream(headers, body, end_stream)?;
+                    let (sender, receiver) = unbounded_channel();
+                    state.streams.insert(stream_id, sender);
+                    self.writer.send(state.session.take_output()).map_err(|_| "h2 connection closed")?;
+                    return Ok(H2Stream { stream_id, receiver, conn: self.clone() });
+                }
+            }
+            released.await;
+        }
+    }
+
+    ///响应体已处理，归还接收窗口
+    fn release_capacity(&self, stream_id: u32, len: usize) -> HlsResult<()> {
+        let mut state = self.shared.state.lock().map_err(|e| e.to_string())?;
+        state.session.release_capacity(stream_id, len);
+        let output = state.session.take_output();
+        if !output.is_empty() { self.writer.send(output).map_err(|_| "h2 connection closed")?; }
+        Ok(())
+    }
+
+    ///请求结束，未结束的stream发送RST_STREAM(CANCEL)
+    fn release(&self, stream_id: u32) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.streams.remove(&stream_id);
+            state.session.reset_stream(stream_id, H2ErrorCode::Cancel);
+            let output = state.session.take_output();
+            if !output.is_empty() { let _ = self.writer.send(output); }
+        }
+        self.shared.released.notify_waiters();
+    }
+
+    async fn run(shared: Arc<H2Shared>, mut stream: Stream, mut receiver: UnboundedReceiver<Vec<u8>>) {
+        let error = match H2Conn::dispatch(&shared, &mut stream, &mut receiver).await {
+            Ok(_) => "h2 connection closed".into(),
+            Err(e) => e,
+        };
+        if let Ok(mut state) = shared.state.lock() {
+            for (_, sender) in state.streams.drain() {
+                let _ = sender.send(Err(error.duplicate()));
+            }
+            state.closed = Some(error);
+        }
+        shared.released.notify_waiters();
+    }
+
+    async fn dispatch(shared: &H2Shared, stream: &mut Stream, receiver: &mut UnboundedReceiver<Vec<u8>>) -> HlsResult<()> {
+        let mut buffer = Buffer::with_capacity(0xFFFF);
+        loop {
+            tokio::select! {
+                data = receiver.recv() => match data {
+                    None => return Ok(()),
+                    Some(data) => stream.async_write(&data).await?,
+                },
+                res = stream.async_read(&mut buffer) => match res {
+                    Ok(_) => H2Conn::dispatch_frames(shared, stream, receiver, &mut buffer).await?,
+                    //空闲时的读超时
+                    Err(e) if e.is_timeout() => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    async fn dispatch_frames(shared: &H2Shared, stream: &mut Stream, receiver: &mut UnboundedReceiver<Vec<u8>>, buffer: &mut Buffer) -> HlsResult<()> {
+        while let Ok(frame) = Frame::from_bytes(buffer) {
+            let (output, error) = {
+                let mut state = shared.state.lock().map_err(|e| e.to_string())?;
+                let error = match state.session.recv_frame(frame) {
+                    Ok(event) => {
+                        match event {
+                            //last_stream_id之后的请求没有被处理
+                            Some(H2Event::GoAway(last_stream_id, code)) => {
+                                let refused = state.streams.keys().filter(|x| **x > last_stream_id).cloned().collect::<Vec<_>>();
+                                for stream_id in refused {
+                                    if let Some(sender) = state.streams.remove(&stream_id) {
+                                        let _ = sender.send(Err(HlsError::GoAway(last_stream_id, code.clone())));
+                                    }
+                                }
+                            }
+                            Some(event) => {
+                                let stream_id = event.stream_id();
+                                let ended = event.is_end();
+                                match state.streams.get(&stream_id) {
+                                    Some(sender) => { let _ = sender.send(Ok(event)); }
+                                    //请求已取消，直接归还窗口
+                                    None => if let H2Event::Data(_, data, _) = event { state.session.release_capacity(stream_id, data.len()); }
+                                }
+                                if ended { state.streams.remove(&stream_id); }
+                            }
+                            None => {}
+                        }
+                        None
+                    }
+                    Err(e) => Some(e),
+                };
+                (state.session.take_output(), error)
+            };
+            //连接错误，写出GOAWAY后关闭连接
+            if let Some(e) = error {
+                let _ = stream.async_write(&output).await;
+                return Err(e);
+            }
+            if !output.is_empty() {
+                //先写出已经在channel中的帧，保证同一个stream的HEADERS在DATA之前
+                while let Ok(data) = receiver.try_recv() { stream.async_write(&data).await?; }
+                stream.async_write(&output).await?;
+            }
+        }
+        shared.released.notify_waiters();
+        Ok(())
+    }
+}
+
+///h2连接上的一个请求，drop后释放并发数
+pub(crate) struct H2Stream {
+    stream_id: u32,
+    receiver: UnboundedReceiver<HlsResult<H2Event>>,
+    conn: H2Conn,
+}
+
+impl H2Stream {
+    ///继续发送请求体，上一块因窗口不足还没有发送完时等待，返回false时对端已经结束stream
+    pub async fn send_data(&mut self, data: Vec<u8>, end_stream: bool) -> HlsResult<bool> {
+        loop {
+            let released = self.conn.shared.released.notified();
+            {
+                let mut state = self.conn.shared.state.lock().map_err(|e| e.to_string())?;
+                if let Some(e) = &state.closed { return Err(e.duplicate()); }
+                if state.session.is_remote_ended(self.stream_id) { return Ok(false); }
+                if state.session.pending_len(self.stream_id) == 0 {
+                    state.session.send_data(self.stream_id, data, end_stream)?;
+                    self.conn.writer.send(state.session.take_output()).map_err(|_| "h2 connection closed")?;
+                    return Ok(true);
+                }
+            }
+            released.await;
+        }
+    }
+
+    ///timeout为单次读取的超时
+    pub async fn recv(&mut self, timeout: Duration) -> HlsResult<H2Event> {
+        let event = match tokio::time::timeout(timeout, self.receiver.recv()).await.map_err(|_| HlsError::Timeout(TimeoutPhase::Read))? {
+            None => Err("h2 connection closed".into()),
+            Some(event) => event,
+        }?;
+        if let H2Event::Data(_, data, _) = &event { self.conn.release_capacity(self.stream_id, data.len())?; }
+        Ok(event)
+    }
+}
+
+impl Drop for H2Stream {
+    fn drop(&mut self) {
+        self.conn.release(self.stream_id);
+        //未读取的响应体也要归还窗口
+        while let Ok(Ok(event)) = self.receiver.try_recv() {
+            if let H2Event::Data(_, data, _) = event { let _ = self.conn.release_capacity(self.stream_id, data.len()); }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 #[cfg(feature = "cls_sync")]
 use super::sync_stream::SyncStream;
-use crate::error::HlsResult;
+#[cfg(aync)]
+use crate::error::HlsError;
+use crate::error::{HlsResult, TimeoutPhase};
 #[cfg(feature = "cls_async")]
 use crate::stream::astream::AsyncTlsStream;
 #[cfg(std_async)]
@@ -9,21 +11,23 @@ use crate::stream::astream::StdAsyncTlsStream;
 use crate::stream::astream::{AsyncTcpStream, TimeoutRW};
 #[cfg(std_sync)]
 use crate::stream::cstream::StdSyncTlsStream;
+#[cfg(sync)]
+use crate::stream::ProxyStream;
 use crate::stream::ConnParam;
 use crate::url::Protocol;
 use crate::{Buffer, ALPN};
 #[cfg(sync)]
 use std::io::Write;
 #[cfg(sync)]
-use std::net::Shutdown;
+use std::time::Duration;
 
 pub enum StreamKind {
     NonConnection,
     //同步
     #[cfg(any(feature = "std_sync", feature = "cls_sync"))]
-    SyncHttp(std::net::TcpStream),
+    SyncHttp(ProxyStream),
     #[cfg(all(feature = "cls_sync", not(feature = "std_sync"), not(feature = "std_async")))]
-    SyncHttps(SyncStream<std::net::TcpStream>),
+    SyncHttps(SyncStream<ProxyStream>),
     #[cfg(std_sync)]
     StdSyncHttps(StdSyncTlsStream),
     //异步
@@ -37,24 +41,26 @@ pub enum StreamKind {
 
 #[cfg(aync)]
 impl StreamKind {
-    pub async fn async_conn(&mut self, param: ConnParam<'_>) -> HlsResult<ALPN> {
+    pub async fn async_conn(&mut self, mut param: ConnParam<'_>) -> HlsResult<ALPN> {
         let _ = self.async_shutdown().await;
-        let stream = tokio::time::timeout(param.timeout.connect(),param.proxy.create_async_stream(param.url.addr(), param.timeout)).await??;
+        let proxy = param.proxy;
+        let stream = tokio::time::timeout(param.timeout.connect(), proxy.create_async_stream(&mut param)).await
+            .map_err(|_| HlsError::Timeout(TimeoutPhase::Connect))??;
         match param.url.protocol() {
-            Protocol::Http => {
+            Protocol::Http | Protocol::Ws => {
                 *self = StreamKind::AsyncHttp(stream);
                 Ok(ALPN::Http11)
             }
             #[cfg(feature = "std_async")]
-            Protocol::Https => {
-                let tls_stream = StdAsyncTlsStream::connect_timeout(param, stream).await?;
+            Protocol::Https | Protocol::Wss => {
+                let tls_stream = StdAsyncTlsStream::connect_timeout(param, stream).await.map_err(|e| e.phase(TimeoutPhase::TlsHandshake))?;
                 let alpn = tls_stream.alpn().unwrap_or(ALPN::Http11);
                 *self = StreamKind::StdAsyncHttps(tls_stream);
                 Ok(alpn)
             }
             #[cfg(cls_async)]
-            Protocol::Https => {
-                let tls_stream = AsyncTlsStream::connect_timeout(param, stream).await?;
+            Protocol::Https | Protocol::Wss => {
+                let tls_stream = AsyncTlsStream::connect_timeout(param, stream).await.map_err(|e| e.phase(TimeoutPhase::TlsHandshake))?;
                 let alpn = tls_stream.alpn().map(|x| ALPN::from_slice(x.as_bytes())).unwrap_or(ALPN::Http11);
                 *self = StreamKind::AsyncHttps(tls_stream);
                 Ok(alpn)
@@ -103,7 +109,7 @@ impl StreamKind {
             StreamKind::AsyncHttp(s) => Ok(s.shutdown().await?),
             #[cfg(cls_async)]
             StreamKind::AsyncHttps(s) => Ok(s.shutdown().await?),
-            #[cfg(std_sync)]
+            #[cfg(std_async)]
             StreamKind::StdAsyncHttps(s) => Ok(s.shutdown().await?),
             _ => Err("Unsupported async read".into()),
         }
@@ -112,24 +118,30 @@ impl StreamKind {
 
 #[cfg(any(feature = "std_sync", feature = "cls_sync"))]
 impl StreamKind {
-    pub fn sync_conn(&mut self, param: ConnParam) -> HlsResult<ALPN> {
+    pub fn sync_conn(&mut self, mut param: ConnParam) -> HlsResult<ALPN> {
         let _ = self.sync_shutdown();
-        let stream = param.proxy.create_sync_stream(param.url.addr(), param.timeout)?;
+        let proxy = param.proxy;
+        let stream = proxy.create_sync_stream(&mut param)?;
+        if let Protocol::Https | Protocol::Wss = param.url.protocol() {
+            let handshake_timeout = param.timeout.limit(param.timeout.tls_handshake())?;
+            stream.get_ref().set_read_timeout(Some(handshake_timeout))?;
+            stream.get_ref().set_write_timeout(Some(handshake_timeout))?;
+        }
         match param.url.protocol() {
-            Protocol::Http => {
+            Protocol::Http | Protocol::Ws => {
                 *self = StreamKind::SyncHttp(stream);
                 Ok(ALPN::Http11)
             }
             #[cfg(std_sync)]
-            Protocol::Https => {
-                let tls_stream = StdSyncTlsStream::connect(param, stream)?;
+            Protocol::Https | Protocol::Wss => {
+                let tls_stream = StdSyncTlsStream::connect(param, stream).map_err(|e| e.phase(TimeoutPhase::TlsHandshake))?;
                 let alpn = tls_stream.alpn().unwrap_or(ALPN::Http11);
                 *self = StreamKind::StdSyncHttps(tls_stream);
                 Ok(alpn)
             }
             #[cfg(cls_sync)]
-            Protocol::Https => {
-                let tls_stream = SyncStream::connect(param, stream)?;
+            Protocol::Https | Protocol::Wss => {
+                let tls_stream = SyncStream::connect(param, stream).map_err(|e| e.phase(TimeoutPhase::TlsHandshake))?;
                 let alpn = tls_stream.alpn().map(|x| ALPN::from_slice(x.as_bytes())).unwrap_or(ALPN::Http11);
                 *self = StreamKind::SyncHttps(tls_stream);
                 Ok(alpn)
@@ -141,20 +153,20 @@ impl StreamKind {
     pub fn sync_write(&mut self, buf: &[u8]) -> HlsResult<()> {
         match self {
             StreamKind::SyncHttp(s) => {
-                s.write(buf)?;
+                s.write_all(buf)?;
                 s.flush()?;
                 Ok(())
             }
             #[cfg(cls_sync)]
             StreamKind::SyncHttps(s) => {
-                s.write(buf)?;
+                s.write_all(buf)?;
                 // s.write_tls(buf)?;
                 s.flush()?;
                 Ok(())
             }
             #[cfg(std_sync)]
             StreamKind::StdSyncHttps(s) => {
-                s.write(buf)?;
+                s.write_all(buf)?;
                 s.flush()?;
                 Ok(())
             }
@@ -173,9 +185,24 @@ impl StreamKind {
         }
     }
 
+    ///同步读写前设置socket的单次超时
+    pub fn sync_set_timeout(&self, read: Duration, write: Duration) -> HlsResult<()> {
+        let tcp = match self {
+            StreamKind::SyncHttp(s) => s.get_ref(),
+            #[cfg(cls_sync)]
+            StreamKind::SyncHttps(s) => s.get_ref().get_ref(),
+            #[cfg(std_sync)]
+            StreamKind::StdSyncHttps(s) => s.get_ref(),
+            _ => return Ok(()),
+        };
+        tcp.set_read_timeout(Some(read))?;
+        tcp.set_write_timeout(Some(write))?;
+        Ok(())
+    }
+
     pub fn sync_shutdown(&mut self) -> HlsResult<()> {
         match self {
-            StreamKind::SyncHttp(s) => Ok(s.shutdown(Shutdown::Both)?),
+            StreamKind::SyncHttp(s) => s.shutdown(),
             #[cfg(cls_sync)]
             StreamKind::SyncHttps(s) => Ok(s.shutdown()?),
             #[cfg(std_sync)]
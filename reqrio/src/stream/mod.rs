@@ -5,17 +5,24 @@ use super::url::Url;
 #[cfg(anys)]
 use crate::alpn::ALPN;
 #[cfg(anys)]
-use crate::error::HlsResult;
+use crate::error::{HlsResult, TimeoutPhase};
 #[cfg(anys)]
 use crate::stream::kind::StreamKind;
 #[cfg(anys)]
 use crate::timeout::Timeout;
+#[cfg(anys)]
+use crate::resolve::Resolver;
 
-pub use proxy::Proxy;
+pub use proxy::{Proxy, ProxyAuth};
+pub use system::{NoProxy, SystemProxy};
+#[cfg(sync)]
+pub(crate) use proxy::ProxyStream;
 #[cfg(anys)]
 use crate::Buffer;
 #[cfg(feature = "cls_async")]
 pub use async_stream::{TlsStream, TlsConnector};
+#[cfg(aync)]
+pub(crate) use h2conn::{H2Conn, H2Stream};
 
 #[cfg(feature = "cls_async")]
 mod async_stream;
@@ -25,7 +32,16 @@ mod sync_stream;
 
 #[cfg(aync)]
 mod astream;
+#[cfg(aync)]
+mod h2conn;
 mod proxy;
+mod system;
+#[cfg(anys)]
+mod eyeballs;
+
+///tls记录的最大长度，tls1.3密文最多比明文(16384)多256字节，加上5字节的记录头
+#[cfg(use_cls)]
+pub(crate) const MAX_RECORD_LEN: usize = 16384 + 256 + 5;
 #[cfg(feature = "std_sync")]
 mod cstream;
 #[cfg(anys)]
@@ -39,6 +55,7 @@ pub struct ConnParam<'a> {
     #[cfg(any(feature = "cls_sync", feature = "cls_async"))]
     pub fingerprint: &'a mut Fingerprint,
     pub alpn: &'a ALPN,
+    pub resolver: &'a dyn Resolver,
 }
 
 #[cfg(anys)]
@@ -58,6 +75,10 @@ impl Stream {
     pub fn alpn(&self) -> &ALPN {
         &self.alpn
     }
+
+    pub fn is_connected(&self) -> bool {
+        !matches!(self.kind, StreamKind::NonConnection)
+    }
 }
 
 #[cfg(aync)]
@@ -68,16 +89,16 @@ impl Stream {
         Ok(())
     }
     pub async fn async_read(&mut self, buffer: &mut Buffer) -> HlsResult<()> {
-        self.kind.async_read(buffer).await
+        self.kind.async_read(buffer).await.map_err(|e| e.phase(TimeoutPhase::Read))
     }
 
     pub async fn async_write(&mut self, data: &[u8]) -> HlsResult<()> {
-        self.kind.async_write(data).await
+        self.kind.async_write(data).await.map_err(|e| e.phase(TimeoutPhase::Write))
     }
 
-    // pub async fn async_shutdown(&mut self) -> HlsResult<()> {
-    //     self.kind.async_shutdown().await
-    // }
+    pub async fn async_shutdown(&mut self) -> HlsResult<()> {
+        self.kind.async_shutdown().await
+    }
 }
 
 #[cfg(sync)]
@@ -87,12 +108,15 @@ impl Stream {
         self.alpn = alpn;
         Ok(())
     }
-    pub fn sync_read(&mut self, buffer: &mut Buffer) -> HlsResult<()> {
-        self.kind.sync_read(buffer)
+    ///单次读写的超时不超过请求的截止时间
+    pub fn sync_read(&mut self, buffer: &mut Buffer, timeout: &Timeout) -> HlsResult<()> {
+        self.kind.sync_set_timeout(timeout.limit(timeout.read())?, timeout.limit(timeout.write())?)?;
+        self.kind.sync_read(buffer).map_err(|e| timeout.deadline_error(e.phase(TimeoutPhase::Read)))
     }
 
-    pub fn sync_write(&mut self, data: &[u8]) -> HlsResult<()> {
-        self.kind.sync_write(data)
+    pub fn sync_write(&mut self, data: &[u8], timeout: &Timeout) -> HlsResult<()> {
+        self.kind.sync_set_timeout(timeout.limit(timeout.read())?, timeout.limit(timeout.write())?)?;
+        self.kind.sync_write(data).map_err(|e| timeout.deadline_error(e.phase(TimeoutPhase::Write)))
     }
 
     pub fn sync_shutdown(&mut self) -> HlsResult<()> {
@@ -1,7 +1,7 @@
 use std::io;
 use reqtls::*;
 use crate::error::{HlsError, HlsResult};
-use crate::stream::ConnParam;
+use crate::stream::{ConnParam, MAX_RECORD_LEN};
 use std::io::{Read, Write};
 use crate::{Buffer, ALPN};
 
@@ -10,12 +10,15 @@ pub struct SyncStream<S> {
     stream: S,
     handshake_finished: bool,
     buffer: Buffer,
+    //解密后超出读取缓冲区的数据
+    pending: Vec<u8>,
 }
 
 impl<S: Read + Write> SyncStream<S> {
     pub fn connect(mut param: ConnParam, mut stream: S) -> HlsResult<SyncStream<S>> {
         let client_random = rand::random::<[u8; 32]>();
         let mut conn = Connection::new(client_random.to_vec());
+        conn.set_verifier(param.fingerprint.verifier(param.url.addr().host()));
         let mut client_hello = RecordLayer::from_bytes(param.fingerprint.client_hello_mut(), false)?;
         client_hello.messages[0].client_mut().ok_or(HlsError::NonePointer)?.set_random(client_random.clone());
         client_hello.messages[0].client_mut().ok_or(HlsError::NonePointer)?.set_server_name(param.url.addr().host());
@@ -24,7 +27,7 @@ impl<S: Read + Write> SyncStream<S> {
             ALPN::Http20 => client_hello.messages[0].client_mut().ok_or(HlsError::NonePointer)?.add_h2_alpn(),
             _ => client_hello.messages[0].client_mut().ok_or(HlsError::NonePointer)?.remove_h2_alpn()
         }
-        client_hello.messages[0].client_mut().ok_or(HlsError::NonePointer)?.remove_tls13();
+        conn.set_key_shares(client_hello.messages[0].client_mut().ok_or(HlsError::NonePointer)?)?;
         let bs = client_hello.handshake_bytes();
         conn.update_session(&bs[5..])?;
         stream.write(&bs)?;
@@ -33,15 +36,22 @@ impl<S: Read + Write> SyncStream<S> {
             stream,
             conn,
             handshake_finished: false,
-            buffer: Buffer::with_capacity(16413),
+            buffer: Buffer::with_capacity(MAX_RECORD_LEN),
+            pending: vec![],
         };
         while !stream.handshake_finished {
             stream.read_packet()?;
-            stream.handle_message(&mut param)?;
+            match stream.conn.is_tls13() {
+                true => stream.handle_tls13(&mut param)?,
+                false => stream.handle_message(&mut param)?
+            }
+        }
+        if !stream.conn.is_tls13() {
+            stream.read_packet()?;
+            let mut record = RecordLayer::from_bytes(stream.buffer.filled_mut(), stream.handshake_finished)?;
+            stream.conn.read_message(&mut record)?;
         }
-        stream.read_packet()?;
-        let mut record = RecordLayer::from_bytes(stream.buffer.filled_mut(), stream.handshake_finished)?;
-        stream.conn.read_message(&mut record)?;
+        stream.buffer.reset();
         Ok(stream)
     }
 
@@ -50,9 +60,13 @@ impl<S: Read + Write> SyncStream<S> {
         self.buffer.sync_read_limit(&mut self.stream, 5)?;
         if self.buffer.len() < 5 { return Err(HlsError::InvalidHeadSize)?; }
         let payload_len = u16::from_be_bytes([self.buffer[3], self.buffer[4]]) as usize;
+        //不是tls记录，例如trojan密码错误时服务端返回的http响应
+        if payload_len + 5 > self.buffer.capacity() { return Err(format!("invalid tls record length: {}", payload_len).into()); }
         while self.buffer.len() - 5 < payload_len {
             self.buffer.sync_read_limit(&mut self.stream, payload_len + 5 - self.buffer.len())?;
         }
+        //tls1.3的握手消息由Connection解密后计算摘要
+        if self.conn.is_tls13() { return Ok(()); }
         if !self.handshake_finished { self.conn.update_session(&self.buffer.filled()[5..])?; }
         let record_type = RecordType::from_byte(self.buffer[0]).ok_or("LayerType Unknown")?;
         if let RecordType::CipherSpec = record_type {
@@ -61,7 +75,22 @@ impl<S: Read + Write> SyncStream<S> {
         Ok(())
     }
 
+    fn handle_tls13(&mut self, param: &mut ConnParam) -> HlsResult<()> {
+        if let Some(finished) = self.conn.read_handshake13(self.buffer.filled_mut())? {
+            //兼容模式，Finished前发送ChangeCipherSpec
+            self.stream.write(param.fingerprint.change_cipher_spec())?;
+            self.stream.write(&finished)?;
+            self.stream.flush()?;
+            self.handshake_finished = true;
+        }
+        Ok(())
+    }
+
     fn handle_message(&mut self, param: &mut ConnParam) -> HlsResult<()> {
+        //握手阶段对端发送的明文alert，例如handshake_failure
+        if let [0x15, _, _, _, _, level, description, ..] = self.buffer.filled() && !self.handshake_finished {
+            return Err(RlsError::Alert(*level, *description).into());
+        }
         let record = RecordLayer::from_bytes(self.buffer.filled_mut(), self.handshake_finished)?;
         for message in record.messages {
             match record.context_type {
@@ -69,11 +98,15 @@ impl<S: Read + Write> SyncStream<S> {
                 RecordType::Alert => {}
                 RecordType::HandShake => {
                     match message {
-                        Message::ServerHello(v) => self.conn.set_by_server_hello(v)?,
-                        Message::ServerKeyExchange(v) => {
-                            // println!("{:#?}", v);
-                            self.conn.set_by_exchange_key(v.hellman_param().pub_key().clone(), v.hellman_param().named_curve().clone())
+                        Message::ServerHello(v) => {
+                            self.conn.set_by_server_hello(v)?;
+                            if let Some(hello) = self.conn.take_retry_hello() {
+                                self.stream.write(&hello)?;
+                                self.stream.flush()?;
+                            }
                         }
+                        Message::Certificate(v) => self.conn.set_by_certificate(&v)?,
+                        Message::ServerKeyExchange(v) => self.conn.set_by_server_key_exchange(&v)?,
                         Message::ServerHelloDone(_) => {
                             let keypair = PriKey::new(self.conn.named_curve())?;
                             let client_pub_key = keypair.pub_key();
@@ -92,7 +125,7 @@ impl<S: Read + Write> SyncStream<S> {
 
                             self.buffer.reset();
                             let record_len = self.conn.make_finish_message(&handshake_hash, &mut self.buffer[..])?;
-                            self.stream.write(&self.buffer[..record_len])?;
+                            self.stream.write_all(&self.buffer[..record_len])?;
                             self.stream.flush()?;
                             break;
                         }
@@ -107,16 +140,21 @@ impl<S: Read + Write> SyncStream<S> {
     }
 
     pub fn shutdown(&mut self) -> HlsResult<()> {
+        if let Some(record) = self.conn.take_key_update() { self.stream.write_all(&record)?; }
         self.buffer.reset();
-        let aead = self.conn.aead().ok_or(RlsError::AeadNone)?;
-        self.buffer.set_len(aead.encrypted_payload_len(2) + 5);
-        self.buffer[aead.payload_start()..aead.payload_start() + 2].copy_from_slice(&[1, 0]);
+        self.buffer.set_len(self.conn.encrypted_payload_len(2) + 5);
+        let start = self.conn.payload_start();
+        self.buffer[start..start + 2].copy_from_slice(&[1, 0]);
         let record_len = self.conn.make_message(RecordType::Alert, &mut self.buffer[..], 2)?;
         self.stream.write(&self.buffer[..record_len])?;
         self.stream.flush()?;
         Ok(())
     }
 
+    pub fn get_ref(&self) -> &S { &self.stream }
+
+    pub fn get_mut(&mut self) -> &mut S { &mut self.stream }
+
     pub fn alpn(&self) -> Option<&str> {
         Some(self.conn.alpn()?.value())
     }
@@ -124,32 +162,59 @@ impl<S: Read + Write> SyncStream<S> {
 
 impl<S: Read> Read for SyncStream<S> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.buffer.sync_read_limit(&mut self.stream, 5)?;
-        let len = u16::from_be_bytes([self.buffer[3], self.buffer[4]]) as usize;
-        while self.buffer.len() - 5 < len {
-            self.buffer.sync_read_limit(&mut self.stream, len + 5 - self.buffer.len())?;
+        if !self.pending.is_empty() {
+            let len = buf.len().min(self.pending.len());
+            buf[..len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            return Ok(len);
         }
-        let mut record = RecordLayer::from_bytes(self.buffer.filled_mut(), self.handshake_finished)?;
-        let rt = record.context_type.as_u8();
-        let len = self.conn.read_message(&mut record)?;
-        let aead = self.conn.aead().ok_or(RlsError::AeadNone)?;
-        if rt == 0x15 && &self.buffer[aead.payload_start()..aead.payload_start() + len] == &[1, 0] {
-            return Err(HlsError::PeerClosedConnection.into());
+        loop {
+            while self.buffer.len() < 5 {
+                self.buffer.sync_read_limit(&mut self.stream, 5 - self.buffer.len())?;
+            }
+            let len = u16::from_be_bytes([self.buffer[3], self.buffer[4]]) as usize;
+            if len + 5 > self.buffer.capacity() { return Err(HlsError::from(format!("invalid tls record length: {}", len)).into()); }
+            while self.buffer.len() - 5 < len {
+                self.buffer.sync_read_limit(&mut self.stream, len + 5 - self.buffer.len())?;
+            }
+            let mut record = RecordLayer::from_bytes(self.buffer.filled_mut(), self.handshake_finished)?;
+            let len = self.conn.read_message(&mut record)?;
+            let start = self.conn.payload_start();
+            match self.conn.read_type() {
+                RecordType::Alert => match self.buffer[start..start + len] {
+                    [1, 0] => return Err(HlsError::PeerClosedConnection.into()),
+                    [level, description] if level != 1 => return Err(RlsError::Alert(level, description).into()),
+                    //warning级别的alert忽略
+                    _ => {
+                        self.buffer.reset();
+                        continue;
+                    }
+                },
+                //tls1.3握手后的NewSessionTicket/KeyUpdate
+                RecordType::HandShake => {
+                    self.buffer.reset();
+                    continue;
+                }
+                _ => {}
+            }
+            let plain = &self.buffer[start..start + len];
+            let len = buf.len().min(plain.len());
+            buf[..len].copy_from_slice(&plain[..len]);
+            self.pending.extend_from_slice(&plain[len..]);
+            self.buffer.reset();
+            return Ok(len);
         }
-        buf[..len].copy_from_slice(&self.buffer[13..13 + len]);
-        self.buffer.reset();
-        Ok(len)
     }
 }
 
 
 impl<S: Write> Write for SyncStream<S> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(record) = self.conn.take_key_update() { self.stream.write_all(&record)?; }
         let mut sent = 0;
         for chunk in buf.chunks(16384) {
-            let aead = self.conn.aead().ok_or(RlsError::AeadNone)?;
             self.buffer.reset();
-            let pln = self.buffer.push_slice_in(aead.payload_start(), chunk);
+            let pln = self.buffer.push_slice_in(self.conn.payload_start(), chunk);
             let record_len = self.conn.make_message(RecordType::ApplicationData, &mut self.buffer[..], pln)?;
             self.stream.write(&self.buffer[..record_len])?;
             sent += chunk.len();
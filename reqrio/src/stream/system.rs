@@ -0,0 +1,231 @@
+use std::env;
+use std::net::IpAddr;
+use crate::error::HlsResult;
+use crate::url::{Protocol, Url};
+use super::Proxy;
+
+///NO_PROXY中的一条规则
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum NoProxyRule {
+    ///*，所有目标都不使用代理
+    All,
+    ///域名，匹配自身和子域名，可以限定端口
+    Domain(String, Option<u16>),
+    ///ip网段，单个ip的前缀长度为全长，可以限定端口
+    Cidr(IpAddr, u8, Option<u16>),
+}
+
+impl NoProxyRule {
+    fn parse(value: &str) -> Option<NoProxyRule> {
+        if value == "*" { return Some(NoProxyRule::All); }
+        //[::1]、[::1]:8080
+        let (host, port) = match value.strip_prefix('[') {
+            Some(rest) => {
+                let (host, port) = rest.split_once(']')?;
+                let port = match port.strip_prefix(':') {
+                    None if port.is_empty() => None,
+                    None => return None,
+                    Some(port) => Some(port.parse().ok()?),
+                };
+                (host, port)
+            }
+            //没有中括号的ipv6不能带端口
+            None if value.parse::<IpAddr>().is_ok() || value.contains('/') => (value, None),
+            None => match value.rsplit_once(':') {
+                None => (value, None),
+                Some((host, port)) => (host, Some(port.parse().ok()?)),
+            }
+        };
+        if let Some((ip, len)) = host.split_once('/') {
+            let ip: IpAddr = ip.parse().ok()?;
+            let len: u8 = len.parse().ok()?;
+            if len > max_prefix(&ip) { return None; }
+            return Some(NoProxyRule::Cidr(ip, len, port));
+        }
+        if let Ok(ip) = host.parse::<IpAddr>() { return Some(NoProxyRule::Cidr(ip, max_prefix(&ip), port)); }
+        let domain = host.trim_start_matches("*.").trim_start_matches('.').trim_end_matches('.').to_lowercase();
+        if domain.is_empty() { return None; }
+        Some(NoProxyRule::Domain(domain, port))
+    }
+
+    fn matches(&self, host: &str, ip: Option<IpAddr>, port: u16) -> bool {
+        let (matched, rule_port) = match self {
+            NoProxyRule::All => return true,
+            NoProxyRule::Domain(domain, rule_port) => {
+                let matched = host == domain || host.strip_suffix(domain.as_str()).is_some_and(|x| x.ends_with('.'));
+                (matched, rule_port)
+            }
+            NoProxyRule::Cidr(net, len, rule_port) => (ip.is_some_and(|ip| in_cidr(&ip, net, *len)), rule_port),
+        };
+        matched && rule_port.is_none_or(|x| x == port)
+    }
+}
+
+fn max_prefix(ip: &IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn in_cidr(ip: &IpAddr, net: &IpAddr, len: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - len as u32).unwrap_or(0);
+            ip.to_bits() & mask == net.to_bits() & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - len as u32).unwrap_or(0);
+            ip.to_bits() & mask == net.to_bits() & mask
+        }
+        _ => false,
+    }
+}
+
+///NO_PROXY规则，逗号或空格分隔，支持`*`、域名后缀(`example.com`、`.example.com`、`*.example.com`)、
+/// ip和网段(`10.0.0.0/8`、`::1`、`fd00::/8`)，以及端口(`example.com:8080`、`[::1]:8080`)，无法解析的规则会被忽略
+#[derive(Clone, Debug, Default)]
+pub struct NoProxy {
+    rules: Vec<NoProxyRule>,
+}
+
+impl NoProxy {
+    pub fn new(value: impl AsRef<str>) -> NoProxy {
+        let rules = value.as_ref().split([',', ' ', '\t']).map(|x| x.trim()).filter(|x| !x.is_empty());
+        NoProxy { rules: rules.filter_map(NoProxyRule::parse).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    ///目标是否不使用代理
+    pub fn matches(&self, url: &Url) -> bool {
+        self.matches_host(url.addr().host(), url.addr().port())
+    }
+
+    fn matches_host(&self, host: &str, port: u16) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_lowercase();
+        let ip = host.parse().ok();
+        self.rules.iter().any(|x| x.matches(&host, ip, port))
+    }
+}
+
+///系统代理，按目标url的协议选择代理，http和ws使用http代理，https和wss使用https代理，没有设置时使用all代理，
+/// 匹配NO_PROXY的目标不使用代理
+#[derive(Clone, Debug, Default)]
+pub struct SystemProxy {
+    http: Option<Proxy>,
+    https: Option<Proxy>,
+    all: Option<Proxy>,
+    no_proxy: NoProxy,
+}
+
+impl SystemProxy {
+    pub fn new() -> SystemProxy {
+        SystemProxy::default()
+    }
+
+    ///读取环境变量HTTP_PROXY、HTTPS_PROXY、ALL_PROXY和NO_PROXY，小写优先，没有协议的代理地址视为http://
+    pub fn from_env() -> HlsResult<SystemProxy> {
+        SystemProxy::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> HlsResult<SystemProxy> {
+        let var = |name: &str| {
+            [name.to_lowercase(), name.to_string()].into_iter()
+                .filter_map(|x| var(&x)).map(|x| x.trim().to_string()).find(|x| !x.is_empty())
+        };
+        let proxy = |name: &str| -> HlsResult<Option<Proxy>> {
+            let Some(value) = var(name) else { return Ok(None); };
+            let value = if value.contains("://") { value } else { format!("http://{}", value) };
+            Ok(Some(Proxy::try_from(value)?))
+        };
+        Ok(SystemProxy {
+            http: proxy("HTTP_PROXY")?,
+            https: proxy("HTTPS_PROXY")?,
+            all: proxy("ALL_PROXY")?,
+            no_proxy: NoProxy::new(var("NO_PROXY").unwrap_or_default()),
+        })
+    }
+
+    pub fn with_http(mut self, proxy: Proxy) -> Self {
+        self.http = Some(proxy);
+        self
+    }
+
+    pub fn with_https(mut self, proxy: Proxy) -> Self {
+        self.https = Some(proxy);
+        self
+    }
+
+    pub fn with_all(mut self, proxy: Proxy) -> Self {
+        self.all = Some(proxy);
+        self
+    }
+
+    pub fn with_no_proxy(mut self, no_proxy: NoProxy) -> Self {
+        self.no_proxy = no_proxy;
+        self
+    }
+
+    pub fn no_proxy(&self) -> &NoProxy {
+        &self.no_proxy
+    }
+
+    ///目标url使用的代理
+    pub fn select(&self, url: &Url) -> Proxy {
+        if self.no_proxy.matches(url) { return Proxy::Null; }
+        let proxy = match url.protocol() {
+            Protocol::Http | Protocol::Ws => self.http.as_ref(),
+            Protocol::Https | Protocol::Wss => self.https.as_ref(),
+            _ => None,
+        };
+        proxy.or(self.all.as_ref()).cloned().unwrap_or(Proxy::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NoProxy, SystemProxy};
+    use crate::stream::Proxy;
+    use crate::url::Url;
+
+    #[test]
+    fn test_system_proxy() {
+        let no_proxy = NoProxy::new("localhost, .internal.com,example.org:8080 10.0.0.0/8,192.168.1.5,[::1]:9000,fd00::/8,bad/99");
+        let matches = |url: &str| no_proxy.matches(&Url::try_from(url).unwrap());
+        assert!(matches("http://localhost:3000/"));
+        assert!(matches("https://a.b.internal.com/"));
+        assert!(matches("https://internal.com/"));
+        assert!(!matches("https://notinternal.com/"));
+        assert!(matches("http://example.org:8080/"));
+        assert!(!matches("http://example.org/"));
+        assert!(matches("http://10.20.30.40/"));
+        assert!(!matches("http://11.0.0.1/"));
+        assert!(matches("http://192.168.1.5/"));
+        assert!(!matches("http://192.168.1.6/"));
+        assert!(no_proxy.matches_host("[::1]", 9000));
+        assert!(!no_proxy.matches_host("::1", 9001));
+        assert!(no_proxy.matches_host("fd12::1", 443));
+        assert!(NoProxy::new("*").matches_host("anything", 80));
+        assert!(NoProxy::new("").is_empty());
+
+        let vars = |name: &str| match name {
+            "https_proxy" => Some("socks5h://127.0.0.1:1080".to_string()),
+            "HTTP_PROXY" => Some("127.0.0.1:3128".to_string()),
+            "ALL_PROXY" => Some("http://all:8080".to_string()),
+            "no_proxy" => Some("*.local".to_string()),
+            _ => None,
+        };
+        let system = SystemProxy::from_vars(vars).unwrap();
+        let select = |url: &str| system.select(&Url::try_from(url).unwrap()).to_string();
+        assert_eq!(select("https://www.example.com/"), "socks5h://127.0.0.1:1080");
+        assert_eq!(select("http://www.example.com/"), "http://127.0.0.1:3128");
+        assert_eq!(select("ws://www.example.com/"), "http://127.0.0.1:3128");
+        assert!(matches!(system.select(&Url::try_from("https://host.local/").unwrap()), Proxy::Null));
+        let system = SystemProxy::new().with_all(Proxy::try_from("http://all:8080").unwrap());
+        assert_eq!(system.select(&Url::try_from("https://www.example.com/").unwrap()).to_string(), "http://all:8080");
+        assert!(SystemProxy::from_vars(|x| (x == "HTTP_PROXY").then(|| "ftp://x:1".to_string())).is_err());
+    }
+}
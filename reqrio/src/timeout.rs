@@ -1,10 +1,17 @@
 use std::time::Duration;
+#[cfg(anys)]
+use std::time::Instant;
 use json::JsonValue;
-use crate::error::HlsError;
+use crate::error::{HlsError, HlsResult};
+#[cfg(sync)]
+use crate::error::TimeoutPhase;
 
+#[derive(Clone)]
 pub struct Timeout {
-    //连接超时
+    //连接超时，包括域名解析和代理握手
     connect: Duration,
+    //tls握手超时
+    tls_handshake: Duration,
     //读取超时，单次
     read: Duration,
     //写出超时，单次
@@ -16,17 +23,24 @@ pub struct Timeout {
     connect_times: i32,
     //处理次数
     handle_times: i32,
+
+    //本次请求的截止时间
+    #[cfg(anys)]
+    deadline: Option<Instant>,
 }
 
 impl Timeout {
     pub fn new() -> Timeout {
         Timeout {
             connect: Duration::from_secs(3),
+            tls_handshake: Duration::from_secs(3),
             read: Duration::from_secs(3),
             write: Duration::from_secs(3),
             handle: Duration::from_secs(30),
             connect_times: 3,
             handle_times: 3,
+            #[cfg(anys)]
+            deadline: None,
         }
     }
 
@@ -40,6 +54,10 @@ impl Timeout {
         self.connect
     }
 
+    pub fn tls_handshake(&self) -> Duration {
+        self.tls_handshake
+    }
+
     pub fn read(&self) -> Duration {
         self.read
     }
@@ -60,20 +78,25 @@ impl Timeout {
         self.handle_times
     }
 
-    pub fn set_connect(&mut self, connect: u64) {
-        self.connect = Duration::from_secs(connect);
+    pub fn set_connect(&mut self, connect: Duration) {
+        self.connect = connect;
     }
 
-    pub fn set_read(&mut self, read: u64) {
-        self.read = Duration::from_secs(read);
+    pub fn set_tls_handshake(&mut self, tls_handshake: Duration) {
+        self.tls_handshake = tls_handshake;
     }
 
-    pub fn set_write(&mut self, write: u64) {
-        self.write = Duration::from_secs(write);
+    pub fn set_read(&mut self, read: Duration) {
+        self.read = read;
     }
 
-    pub fn set_handle(&mut self, handle: u64) {
-        self.handle = Duration::from_secs(handle);
+    pub fn set_write(&mut self, write: Duration) {
+        self.write = write;
+    }
+
+    ///从开始请求到收到响应(send_stream为响应头)的总超时，包括重试和重定向
+    pub fn set_handle(&mut self, handle: Duration) {
+        self.handle = handle;
     }
 
     pub fn set_connect_times(&mut self, connect_times: i32) {
@@ -83,18 +106,91 @@ impl Timeout {
     pub fn set_handle_times(&mut self, handle_times: i32) {
         self.handle_times = handle_times;
     }
+
+    ///开始一次请求，截止时间为现在加上handle
+    #[cfg(anys)]
+    pub(crate) fn start(&mut self) -> Instant {
+        let deadline = Instant::now() + self.handle;
+        self.deadline = Some(deadline);
+        deadline
+    }
+
+    ///请求结束，之后只受单次读写的超时限制
+    #[cfg(anys)]
+    pub(crate) fn stop(&mut self) {
+        self.deadline = None;
+    }
+
+    ///距离截止时间还剩的时间，没有开始请求时为None
+    #[cfg(anys)]
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        Some(self.deadline?.saturating_duration_since(Instant::now()))
+    }
+
+    ///不超过截止时间的单阶段超时，已经超时时返回错误
+    #[cfg(sync)]
+    pub(crate) fn limit(&self, timeout: Duration) -> HlsResult<Duration> {
+        match self.remaining() {
+            None => Ok(timeout),
+            Some(remaining) if remaining.is_zero() => Err(HlsError::Timeout(TimeoutPhase::Handle)),
+            Some(remaining) => Ok(timeout.min(remaining)),
+        }
+    }
+
+    ///截止时间已过时，单阶段的超时错误改为总超时
+    #[cfg(sync)]
+    pub(crate) fn deadline_error(&self, error: HlsError) -> HlsError {
+        match self.remaining() {
+            Some(remaining) if remaining.is_zero() && error.is_timeout() => HlsError::Timeout(TimeoutPhase::Handle),
+            _ => error,
+        }
+    }
+}
+
+///秒数，可以是小数
+fn duration(value: &JsonValue, default: Duration) -> HlsResult<Duration> {
+    if value.is_null() { return Ok(default); }
+    Duration::try_from_secs_f64(value.as_f64()?).map_err(|e| e.to_string().into())
 }
 
 impl TryFrom<JsonValue> for Timeout {
     type Error = HlsError;
     fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        let connect = duration(&value["connect"], Duration::from_secs(3))?;
         Ok(Timeout {
-            connect: Duration::from_secs(value["connect"].as_u64()?),
-            read:Duration::from_secs(value["read"].as_u64()?),
-            write:Duration::from_secs(value["write"].as_u64()?),
-            handle:Duration::from_secs(value["handle"].as_u64()?),
-            connect_times:value["connect_times"].as_i32()?,
-            handle_times:value["handle_times"].as_i32()?,
+            connect,
+            tls_handshake: duration(&value["tls_handshake"], connect)?,
+            read: duration(&value["read"], Duration::from_secs(3))?,
+            write: duration(&value["write"], Duration::from_secs(3))?,
+            handle: duration(&value["handle"], Duration::from_secs(30))?,
+            connect_times: value["connect_times"].as_i32()?,
+            handle_times: value["handle_times"].as_i32()?,
+            #[cfg(anys)]
+            deadline: None,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, sync))]
+mod tests {
+    use super::Timeout;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timeout_deadline() {
+        let mut timeout = Timeout::new();
+        timeout.set_read(Duration::from_millis(1500));
+        assert_eq!(timeout.limit(timeout.read()).unwrap(), Duration::from_millis(1500));
+        timeout.set_handle(Duration::from_millis(200));
+        timeout.start();
+        assert!(timeout.limit(timeout.read()).unwrap() <= Duration::from_millis(200));
+        std::thread::sleep(Duration::from_millis(210));
+        assert!(timeout.limit(timeout.read()).unwrap_err().is_timeout());
+        timeout.stop();
+        assert!(timeout.remaining().is_none());
+
+        let value = json::parse(r#"{"connect":0.5,"read":2,"write":2,"handle":10,"connect_times":1,"handle_times":1}"#).unwrap();
+        let timeout = Timeout::try_from(value).unwrap();
+        assert_eq!(timeout.tls_handshake(), Duration::from_millis(500));
+    }
+}
@@ -1,10 +1,12 @@
 use crate::error::{HlsError, HlsResult};
 use std::fmt::Display;
-use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 use std::str::FromStr;
 use std::vec::IntoIter;
 
-#[derive(Debug, Clone, PartialEq)]
+///#### 主机和端口
+/// * ipv6的host不带[]，Display和url中带上[]，例如`[::1]:443`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Addr {
     host: String,
     port: u16,
@@ -33,6 +35,10 @@ impl Addr {
         res
     }
 
+    pub fn new_bits_v6(host: u128, port: u16) -> Addr {
+        Addr::new_addr(Ipv6Addr::from_bits(host), port)
+    }
+
     pub fn host(&self) -> &str {
         &self.host
     }
@@ -66,19 +72,44 @@ impl Addr {
     pub fn to_bits(&self) -> HlsResult<u32> {
         Ok(Ipv4Addr::from_str(self.host())?.to_bits())
     }
+
+    pub fn to_bits_v6(&self) -> HlsResult<u128> {
+        Ok(Ipv6Addr::from_str(self.host())?.to_bits())
+    }
+
+    ///host是ip时返回ip，域名为None
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.host.parse().ok()
+    }
 }
 
 impl Display for Addr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}:{}", self.host, self.port))
+        match self.host.contains(':') {
+            true => f.write_fmt(format_args!("[{}]:{}", self.host, self.port)),
+            false => f.write_fmt(format_args!("{}:{}", self.host, self.port)),
+        }
     }
 }
 
+///`host:port`、`[ipv6]:port`，没有端口时为0，也可以是不带[]和端口的ipv6地址
 impl TryFrom<&str> for Addr {
     type Error = HlsError;
     fn try_from(value: &str) -> HlsResult<Addr> {
-        let mut i = value.split(':');
         let mut res = Addr::new();
+        if let Some(value) = value.strip_prefix('[') {
+            let (host, port) = value.split_once(']').ok_or("addr error")?;
+            res.host = Ipv6Addr::from_str(host)?.to_string();
+            if !port.is_empty() {
+                res.port = port.strip_prefix(':').ok_or("addr error")?.parse()?;
+            }
+            return Ok(res);
+        }
+        if let Ok(ip) = Ipv6Addr::from_str(value) {
+            res.host = ip.to_string();
+            return Ok(res);
+        }
+        let mut i = value.split(':');
         res.host = i.next().ok_or("addr error")?.to_string();
         if let Some(port) = i.next() {
             res.port = port.parse()?;
@@ -46,12 +46,71 @@ impl Url {
     pub fn protocol(&self) -> &Protocol {
         &self.protocol
     }
+
+    ///Host请求头和:authority，默认端口时省略端口，ipv6带上[]
+    pub fn authority(&self) -> String {
+        match self.addr.port() == self.protocol.default_port() {
+            true => self.addr.to_string().rsplit_once(':').map(|x| x.0.to_string()).unwrap_or_default(),
+            false => self.addr.to_string(),
+        }
+    }
+
+    ///协议、主机和端口都相同
+    pub fn is_same_origin(&self, other: &Url) -> bool {
+        self.protocol == other.protocol && self.addr.host().eq_ignore_ascii_case(other.addr.host()) && self.addr.port() == other.addr.port()
+    }
+
+    ///以当前url为基准解析相对地址，例如重定向的Location
+    pub fn join(&self, location: impl AsRef<str>) -> HlsResult<Url> {
+        let location = location.as_ref().trim();
+        //#之后的片段不发送给服务器
+        let location = location.split('#').next().unwrap_or("");
+        if location.contains("://") { return Url::try_from(location); }
+        if let Some(location) = location.strip_prefix("//") {
+            return Url::try_from(format!("{}://{}", self.protocol, location));
+        }
+        let mut res = self.clone();
+        let (path, query) = match location.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (location, None),
+        };
+        let path = match path {
+            "" => self.uri.path().to_string(),
+            _ if path.starts_with('/') => path.to_string(),
+            _ => {
+                //相对路径基于当前路径最后一个/之前的部分
+                let base = self.uri.path().rsplit_once('/').map(|x| x.0).unwrap_or("");
+                format!("{}/{}", base, path)
+            }
+        };
+        res.uri.set_uri(Url::remove_dot_segments(&path));
+        //只有片段或空地址时保留原来的参数
+        if !location.is_empty() { res.uri.clear_params(); }
+        if let Some(query) = query && !query.is_empty() { res.uri.parse_param(query)?; }
+        Ok(res)
+    }
+
+    fn remove_dot_segments(path: &str) -> String {
+        let mut segments: Vec<&str> = vec![];
+        let mut items = path.split('/').skip(1).peekable();
+        while let Some(item) = items.next() {
+            let last = items.peek().is_none();
+            match item {
+                "." => if last { segments.push(""); },
+                ".." => {
+                    segments.pop();
+                    if last { segments.push(""); }
+                }
+                _ => segments.push(item),
+            }
+        }
+        format!("/{}", segments.join("/"))
+    }
 }
 
 impl Display for Url {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let addr = self.addr.to_string().replace(":443", "").replace(":80", "");
-        let mut res = format!("{}://{}{}", self.protocol, addr, self.uri());
+        let mut res = format!("{}://{}{}", self.protocol, self.authority(), self.uri());
         if res.ends_with("?") {
             res = res[..res.len() - 1].to_string();
         }
@@ -99,7 +158,7 @@ impl TryFrom<&str> for Url {
 
 #[cfg(test)]
 mod tests {
-    use crate::url::{Uri, Url};
+    use crate::url::{Addr, Uri, Url};
 
     #[test]
     fn test_url() {
@@ -131,4 +190,41 @@ mod tests {
         let uri = Uri::try_from("/").unwrap();
         println!("{}", uri);
     }
+
+    #[test]
+    fn test_url_join() {
+        let base = Url::try_from("https://a.com:8443/x/y/z?p=1").unwrap();
+        let cases = [
+            ("https://b.com/c", "https://b.com/c"),
+            ("//b.com/c?d=1", "https://b.com/c?d=1"),
+            ("/c", "https://a.com:8443/c"),
+            ("c?d=1", "https://a.com:8443/x/y/c?d=1"),
+            ("../c", "https://a.com:8443/x/c"),
+            ("./", "https://a.com:8443/x/y/"),
+            ("?d=1", "https://a.com:8443/x/y/z?d=1"),
+            ("#top", "https://a.com:8443/x/y/z?p=1"),
+        ];
+        for (location, url) in cases {
+            assert_eq!(base.join(location).unwrap().to_string(), url);
+        }
+        assert!(base.is_same_origin(&base.join("/c").unwrap()));
+        assert!(!base.is_same_origin(&base.join("http://a.com:8443/").unwrap()));
+    }
+
+    #[test]
+    fn test_url_ipv6() {
+        let url = Url::try_from("https://[2001:DB8::1]:8443/a?b=1").unwrap();
+        assert_eq!((url.addr().host(), url.addr().port()), ("2001:db8::1", 8443));
+        assert_eq!(url.addr().to_string(), "[2001:db8::1]:8443");
+        assert_eq!(url.to_string(), "https://[2001:db8::1]:8443/a?b=1");
+        let url = Url::try_from("http://[::1]").unwrap();
+        assert_eq!((url.authority().as_str(), url.addr().port()), ("[::1]", 80));
+        assert_eq!(url.addr().to_bits_v6().unwrap(), 1);
+        assert_eq!(Addr::new_bits_v6(1, 443), Addr::try_from("[::1]:443").unwrap());
+        assert_eq!(Addr::try_from("::1").unwrap().ip(), Some("::1".parse().unwrap()));
+        assert!(Addr::try_from("[::1").is_err() && Addr::try_from("[::1]443").is_err() && Addr::try_from("[a.com]:1").is_err());
+        //默认端口的判断不受端口中的数字影响
+        assert_eq!(Url::try_from("http://a.com:8080/").unwrap().authority(), "a.com:8080");
+        assert_eq!(Url::try_from("http://a.com:443/").unwrap().authority(), "a.com:443");
+    }
 }
@@ -1,7 +1,7 @@
 use std::fmt::Display;
 use crate::error::HlsError;
 
-#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
 pub enum Protocol {
     Http,
     Https,
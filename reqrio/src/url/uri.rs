@@ -45,6 +45,9 @@ impl Uri {
 
     pub fn params(&self) -> &Vec<Param> { &self.params }
 
+    ///不含参数的路径
+    pub fn path(&self) -> &str { &self.uri }
+
     pub fn clear_params(&mut self) {
         self.params.clear();
     }
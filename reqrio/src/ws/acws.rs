@@ -0,0 +1,93 @@
+use super::{CloseCode, CloseFrame, Message, WsCodec};
+use crate::buffer::Buffer;
+use crate::error::{HlsError, HlsResult};
+use crate::packet::Header;
+use crate::stream::Stream;
+use std::mem;
+
+///#### 异步websocket，通过`AcReq::websocket`创建
+/// * 连接和AcReq一样使用代理和tls指纹
+/// * 连接时Timeout的read为recv等待每次读取的超时，超时后连接仍然可以继续使用
+pub struct AcWs {
+    stream: Stream,
+    codec: WsCodec,
+    buffer: Buffer,
+    header: Header,
+    protocol: Option<String>,
+}
+
+impl AcWs {
+    pub(crate) fn new(stream: Stream, codec: WsCodec, header: Header, protocol: Option<String>) -> AcWs {
+        AcWs { stream, codec, buffer: Buffer::new(), header, protocol }
+    }
+
+    ///握手的响应头
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    ///服务端选择的子协议
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    pub async fn send(&mut self, message: Message) -> HlsResult<()> {
+        let data = self.codec.encode(message)?;
+        self.stream.async_write(&data).await
+    }
+
+    pub async fn send_text(&mut self, text: impl ToString) -> HlsResult<()> {
+        self.send(Message::Text(text.to_string())).await
+    }
+
+    pub async fn send_binary(&mut self, data: impl Into<Vec<u8>>) -> HlsResult<()> {
+        self.send(Message::Binary(data.into())).await
+    }
+
+    pub async fn ping(&mut self, data: impl Into<Vec<u8>>) -> HlsResult<()> {
+        self.send(Message::Ping(data.into())).await
+    }
+
+    ///读取下一个消息，收到ping时自动回复pong，收到关闭帧时回复后关闭连接
+    pub async fn recv(&mut self) -> HlsResult<Message> {
+        loop {
+            let message = self.codec.next();
+            let reply = self.codec.take_reply();
+            if !reply.is_empty() {
+                let res = self.stream.async_write(&reply).await;
+                if message.is_ok() { res?; }
+            }
+            if self.codec.is_closed() { self.shutdown().await; }
+            if let Some(message) = message? { return Ok(message); }
+            self.buffer.reset();
+            self.stream.async_read(&mut self.buffer).await?;
+            self.codec.feed(self.buffer.filled());
+        }
+    }
+
+    ///发送关闭帧，等待对端的关闭帧后关闭连接，期间收到的消息被丢弃
+    pub async fn close(&mut self, code: CloseCode, reason: impl AsRef<str>) -> HlsResult<()> {
+        if self.codec.is_closed() { return Ok(()); }
+        if !self.codec.is_closing() { self.send(Message::Close(Some(CloseFrame::new(code, reason.as_ref())))).await?; }
+        loop {
+            match self.recv().await {
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => continue,
+                //对端直接关闭了连接
+                Err(HlsError::PeerClosedConnection) => {
+                    self.shutdown().await;
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.shutdown().await;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    async fn shutdown(&mut self) {
+        let mut stream = mem::replace(&mut self.stream, Stream::unconnection());
+        if stream.is_connected() { let _ = stream.async_shutdown().await; }
+    }
+}
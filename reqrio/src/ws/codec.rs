@@ -0,0 +1,375 @@
+use super::deflate::Deflate;
+use super::{random_bytes, CloseCode, CloseFrame, Message, WsConfig};
+use crate::error::{HlsError, HlsResult};
+use std::mem;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(value: u8) -> Option<Opcode> {
+        match value {
+            0 => Some(Opcode::Continuation),
+            1 => Some(Opcode::Text),
+            2 => Some(Opcode::Binary),
+            8 => Some(Opcode::Close),
+            9 => Some(Opcode::Ping),
+            10 => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Opcode::Continuation => 0,
+            Opcode::Text => 1,
+            Opcode::Binary => 2,
+            Opcode::Close => 8,
+            Opcode::Ping => 9,
+            Opcode::Pong => 10,
+        }
+    }
+
+    fn is_control(&self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+//fin、rsv、opcode和去掉掩码后的数据
+type WsFrame = (bool, u8, Opcode, Vec<u8>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WsState {
+    Open,
+    //已经发送关闭帧，等待对端的关闭帧
+    CloseSent,
+    Closed,
+}
+
+///#### websocket的帧编解码(RFC 6455)，不涉及读写，ScWs和AcWs共用
+/// * 发送的帧使用随机掩码，按frame_size分片，数据消息在协商后压缩
+/// * 接收时合并分片、解压并检查utf-8，收到ping时生成pong，收到关闭帧时生成回复的关闭帧
+/// * 对端违反协议时生成对应关闭码的关闭帧，返回HlsError::WebSocket
+pub(crate) struct WsCodec {
+    max_message: usize,
+    frame_size: Option<usize>,
+    deflate: Option<Deflate>,
+    //还没有解析的数据
+    buffer: Vec<u8>,
+    //分片消息的类型、是否压缩和已收到的数据
+    fragment: Option<(Opcode, bool, Vec<u8>)>,
+    //需要发送给对端的控制帧
+    reply: Vec<u8>,
+    state: WsState,
+}
+
+impl WsCodec {
+    ///upgraded为和握手响应一起读到的数据
+    pub(crate) fn new(config: &WsConfig, deflate: Option<Deflate>, upgraded: Vec<u8>) -> WsCodec {
+        WsCodec {
+            max_message: config.max_message,
+            frame_size: config.frame_size,
+            deflate,
+            buffer: upgraded,
+            fragment: None,
+            reply: vec![],
+            state: WsState::Open,
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.state == WsState::Closed
+    }
+
+    ///是否已经发送或回复了关闭帧
+    pub(crate) fn is_closing(&self) -> bool {
+        self.state != WsState::Open
+    }
+
+    pub(crate) fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    ///取出需要发送给对端的控制帧
+    pub(crate) fn take_reply(&mut self) -> Vec<u8> {
+        mem::take(&mut self.reply)
+    }
+
+    ///编码需要发送的消息，发送关闭帧之后不能再发送
+    pub(crate) fn encode(&mut self, message: Message) -> HlsResult<Vec<u8>> {
+        if self.state != WsState::Open { return Err(HlsError::WebSocketClosed); }
+        let (opcode, payload) = match message {
+            Message::Text(text) => (Opcode::Text, text.into_bytes()),
+            Message::Binary(data) => (Opcode::Binary, data),
+            Message::Ping(data) => (Opcode::Ping, data),
+            Message::Pong(data) => (Opcode::Pong, data),
+            Message::Close(frame) => (Opcode::Close, close_payload(frame.as_ref())?),
+        };
+        if opcode.is_control() {
+            if payload.len() > 125 { return Err("websocket control frame payload too long".into()); }
+            if opcode == Opcode::Close { self.state = WsState::CloseSent; }
+            return Ok(encode_frame(true, false, opcode, &payload));
+        }
+        let (compressed, payload) = match &mut self.deflate {
+            None => (false, payload),
+            Some(deflate) => (true, deflate.compress(&payload)?),
+        };
+        let frame_size = self.frame_size.unwrap_or(payload.len()).max(1);
+        let chunks = payload.chunks(frame_size).collect::<Vec<_>>();
+        if chunks.is_empty() { return Ok(encode_frame(true, compressed, opcode, &[])); }
+        let mut res = Vec::with_capacity(payload.len() + chunks.len() * 14);
+        for (index, chunk) in chunks.iter().enumerate() {
+            //第一帧带类型和压缩标志，后面的帧为continuation
+            let opcode = if index == 0 { opcode } else { Opcode::Continuation };
+            res.extend(encode_frame(index + 1 == chunks.len(), compressed && index == 0, opcode, chunk));
+        }
+        Ok(res)
+    }
+
+    ///解析下一个完整的消息，数据不够时返回None
+    pub(crate) fn next(&mut self) -> HlsResult<Option<Message>> {
+        loop {
+            if self.state == WsState::Closed { return Err(HlsError::WebSocketClosed); }
+            let Some((fin, rsv, opcode, payload)) = self.next_frame()? else { return Ok(None); };
+            if let Some(message) = self.handle_frame(fin, rsv, opcode, payload)? { return Ok(Some(message)); }
+        }
+    }
+
+    ///违反协议时回复关闭帧，之后不再收发
+    fn fail(&mut self, code: CloseCode, reason: impl ToString) -> HlsError {
+        let reason = reason.to_string();
+        if self.state == WsState::Open {
+            let payload = close_payload(Some(&CloseFrame::new(code, ""))).unwrap_or_default();
+            self.reply.extend(encode_frame(true, false, Opcode::Close, &payload));
+        }
+        self.state = WsState::Closed;
+        self.buffer.clear();
+        self.fragment = None;
+        HlsError::WebSocket(code, reason)
+    }
+
+    fn next_frame(&mut self) -> HlsResult<Option<WsFrame>> {
+        if self.buffer.len() < 2 { return Ok(None); }
+        let fin = self.buffer[0] & 0x80 != 0;
+        let rsv = (self.buffer[0] >> 4) & 0x07;
+        let Some(opcode) = Opcode::from_u8(self.buffer[0] & 0x0f) else {
+            return Err(self.fail(CloseCode::Protocol, format!("unknown opcode {}", self.buffer[0] & 0x0f)));
+        };
+        //服务端发送的帧不能有掩码
+        if self.buffer[1] & 0x80 != 0 { return Err(self.fail(CloseCode::Protocol, "masked frame from server")); }
+        let (head, len) = match self.buffer[1] & 0x7f {
+            126 if self.buffer.len() < 4 => return Ok(None),
+            126 => (4, u16::from_be_bytes([self.buffer[2], self.buffer[3]]) as u64),
+            127 if self.buffer.len() < 10 => return Ok(None),
+            127 => (10, u64::from_be_bytes(self.buffer[2..10].try_into()?)),
+            len => (2, len as u64),
+        };
+        if opcode.is_control() && (!fin || len > 125) { return Err(self.fail(CloseCode::Protocol, "invalid control frame")); }
+        if len > self.max_message as u64 { return Err(self.fail(CloseCode::TooBig, "message too big")); }
+        let len = len as usize;
+        if self.buffer.len() < head + len { return Ok(None); }
+        let payload = self.buffer[head..head + len].to_vec();
+        self.buffer.drain(..head + len);
+        Ok(Some((fin, rsv, opcode, payload)))
+    }
+
+    fn handle_frame(&mut self, fin: bool, rsv: u8, opcode: Opcode, payload: Vec<u8>) -> HlsResult<Option<Message>> {
+        //rsv1只能出现在协商了压缩的数据消息的第一帧
+        let compressed = rsv == 4 && self.deflate.is_some() && matches!(opcode, Opcode::Text | Opcode::Binary);
+        if rsv != 0 && !compressed { return Err(self.fail(CloseCode::Protocol, "reserved bits set")); }
+        match opcode {
+            Opcode::Ping => {
+                if self.state == WsState::Open { self.reply.extend(encode_frame(true, false, Opcode::Pong, &payload)); }
+                Ok(Some(Message::Ping(payload)))
+            }
+            Opcode::Pong => Ok(Some(Message::Pong(payload))),
+            Opcode::Close => self.handle_close(payload).map(Some),
+            Opcode::Text | Opcode::Binary => {
+                if self.fragment.is_some() { return Err(self.fail(CloseCode::Protocol, "expected continuation frame")); }
+                match fin {
+                    true => self.finish_message(opcode, compressed, payload).map(Some),
+                    false => {
+                        self.fragment = Some((opcode, compressed, payload));
+                        Ok(None)
+                    }
+                }
+            }
+            Opcode::Continuation => {
+                let Some((_, _, data)) = &mut self.fragment else {
+                    return Err(self.fail(CloseCode::Protocol, "unexpected continuation frame"));
+                };
+                if data.len() + payload.len() > self.max_message { return Err(self.fail(CloseCode::TooBig, "message too big")); }
+                data.extend(payload);
+                if !fin { return Ok(None); }
+                let (opcode, compressed, data) = self.fragment.take().ok_or(HlsError::NonePointer)?;
+                self.finish_message(opcode, compressed, data).map(Some)
+            }
+        }
+    }
+
+    fn finish_message(&mut self, opcode: Opcode, compressed: bool, payload: Vec<u8>) -> HlsResult<Message> {
+        let payload = match (compressed, &mut self.deflate) {
+            (true, Some(deflate)) => match deflate.decompress(&payload, self.max_message) {
+                Ok(payload) => payload,
+                Err(e) => return Err(self.fail(CloseCode::TooBig, e)),
+            },
+            _ => payload,
+        };
+        match opcode {
+            Opcode::Text => match String::from_utf8(payload) {
+                Ok(text) => Ok(Message::Text(text)),
+                Err(_) => Err(self.fail(CloseCode::Invalid, "text message is not utf-8")),
+            },
+            _ => Ok(Message::Binary(payload)),
+        }
+    }
+
+    ///对端主动关闭时回复相同的关闭码，回复之前发送的关闭帧时结束
+    fn handle_close(&mut self, payload: Vec<u8>) -> HlsResult<Message> {
+        let frame = match payload.len() {
+            0 => None,
+            1 => return Err(self.fail(CloseCode::Protocol, "invalid close frame")),
+            _ => {
+                let code = CloseCode::from(u16::from_be_bytes([payload[0], payload[1]]));
+                if !code.is_valid() { return Err(self.fail(CloseCode::Protocol, format!("invalid close code {}", code))); }
+                let Ok(reason) = String::from_utf8(payload[2..].to_vec()) else {
+                    return Err(self.fail(CloseCode::Invalid, "close reason is not utf-8"));
+                };
+                Some(CloseFrame::new(code, reason))
+            }
+        };
+        if self.state == WsState::Open {
+            let reply = frame.as_ref().map(|x| CloseFrame::new(x.code(), ""));
+            self.reply.extend(encode_frame(true, false, Opcode::Close, &close_payload(reply.as_ref())?));
+        }
+        self.state = WsState::Closed;
+        Ok(Message::Close(frame))
+    }
+}
+
+fn close_payload(frame: Option<&CloseFrame>) -> HlsResult<Vec<u8>> {
+    let Some(frame) = frame else { return Ok(vec![]); };
+    if !frame.code().is_valid() { return Err(format!("websocket close code {} can not be sent", frame.code()).into()); }
+    let mut payload = u16::from(frame.code()).to_be_bytes().to_vec();
+    payload.extend_from_slice(frame.reason().as_bytes());
+    Ok(payload)
+}
+
+///客户端发送的帧必须使用掩码
+fn encode_frame(fin: bool, compressed: bool, opcode: Opcode, payload: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(payload.len() + 14);
+    res.push((fin as u8) << 7 | (compressed as u8) << 6 | opcode.as_u8());
+    match payload.len() {
+        0..126 => res.push(0x80 | payload.len() as u8),
+        126..65536 => {
+            res.push(0x80 | 126);
+            res.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        _ => {
+            res.push(0x80 | 127);
+            res.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        }
+    }
+    let mask = random_bytes::<4>();
+    res.extend_from_slice(&mask);
+    res.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Opcode, WsCodec};
+    use crate::error::HlsError;
+    use crate::ws::{CloseCode, CloseFrame, Message, WsConfig};
+
+    //服务端发送的帧，没有掩码
+    fn frame(b0: u8, payload: &[u8]) -> Vec<u8> {
+        let mut res = vec![b0];
+        match payload.len() {
+            0..126 => res.push(payload.len() as u8),
+            _ => {
+                res.push(126);
+                res.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+            }
+        }
+        res.extend_from_slice(payload);
+        res
+    }
+
+    //去掉客户端帧的掩码，返回第一个字节和数据
+    fn unmask(data: &[u8]) -> (u8, Vec<u8>) {
+        assert_ne!(data[1] & 0x80, 0);
+        let (head, len) = match data[1] & 0x7f {
+            126 => (4, u16::from_be_bytes([data[2], data[3]]) as usize),
+            len => (2, len as usize),
+        };
+        let mask = &data[head..head + 4];
+        (data[0], data[head + 4..head + 4 + len].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect())
+    }
+
+    #[test]
+    fn test_ws_codec() {
+        let mut codec = WsCodec::new(&WsConfig::new(), None, frame(0x81, b"hi"));
+        assert_eq!(codec.next().unwrap(), Some(Message::Text("hi".to_string())));
+        assert_eq!(codec.next().unwrap(), None);
+        //分片中间夹着ping，数据可以在任意位置截断
+        let mut data = frame(0x01, b"Hel");
+        data.extend(frame(0x89, b"p"));
+        data.extend(frame(0x80, b"lo"));
+        data.extend(frame(0x82, &[7u8; 300]));
+        let mut messages = vec![];
+        for chunk in data.chunks(3) {
+            codec.feed(chunk);
+            while let Some(message) = codec.next().unwrap() { messages.push(message); }
+        }
+        assert_eq!(messages, vec![Message::Ping(b"p".to_vec()), Message::Text("Hello".to_string()), Message::Binary(vec![7; 300])]);
+        assert_eq!(unmask(&codec.take_reply()), (0x8a, b"p".to_vec()));
+
+        let encoded = codec.encode(Message::Text("x".repeat(200))).unwrap();
+        assert_eq!(unmask(&encoded), (0x81, "x".repeat(200).into_bytes()));
+        let mut codec = WsCodec::new(&WsConfig::new().with_frame_size(2), None, vec![]);
+        let encoded = codec.encode(Message::Binary(b"abcde".to_vec())).unwrap();
+        assert_eq!(encoded.len(), 3 * 6 + 5);
+        assert_eq!((encoded[0], encoded[6 + 2], encoded[12 + 2 * 2]), (0x02, Opcode::Continuation.as_u8(), 0x80));
+
+        //对端关闭时回复相同的关闭码
+        codec.feed(&frame(0x88, b"\x03\xe9bye"));
+        assert_eq!(codec.next().unwrap(), Some(Message::Close(Some(CloseFrame::new(CloseCode::Away, "bye")))));
+        assert_eq!(unmask(&codec.take_reply()), (0x88, vec![0x03, 0xe9]));
+        assert!(matches!(codec.next(), Err(HlsError::WebSocketClosed)));
+        assert!(codec.encode(Message::Text("late".to_string())).is_err());
+
+        //主动关闭后等待对端的关闭帧
+        let mut codec = WsCodec::new(&WsConfig::new(), None, vec![]);
+        let encoded = codec.encode(Message::Close(Some(CloseFrame::new(CloseCode::Normal, "done")))).unwrap();
+        assert_eq!(unmask(&encoded), (0x88, b"\x03\xe8done".to_vec()));
+        assert!(codec.encode(Message::Close(Some(CloseFrame::new(CloseCode::Abnormal, "")))).is_err());
+        codec.feed(&frame(0x88, b"\x03\xe8"));
+        assert!(codec.next().unwrap().unwrap().is_close());
+        assert!(codec.take_reply().is_empty() && codec.is_closed());
+
+        let errors = [
+            (frame(0x91, b"x"), CloseCode::Protocol),
+            (frame(0x83, b"x"), CloseCode::Protocol),
+            (frame(0x09, b"x"), CloseCode::Protocol),
+            (frame(0x80, b"x"), CloseCode::Protocol),
+            (frame(0x81, &[0xff, 0xfe]), CloseCode::Invalid),
+            (frame(0x88, b"\x03\xed"), CloseCode::Protocol),
+            (frame(0x82, &[0; 200]), CloseCode::TooBig),
+            (vec![0x81, 0x81, 0, 0, 0, 0, b'x'], CloseCode::Protocol),
+        ];
+        for (data, code) in errors {
+            let mut codec = WsCodec::new(&WsConfig::new().with_max_message(100), None, data);
+            assert!(matches!(codec.next(), Err(HlsError::WebSocket(c, _)) if c == code));
+            assert_eq!(unmask(&codec.take_reply()), (0x88, u16::from(code).to_be_bytes().to_vec()));
+            assert!(codec.is_closed());
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::{HlsError, HlsResult};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+//每个压缩消息末尾被去掉的空块
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+///#### permessage-deflate(RFC 7692)
+/// * 压缩使用32K的窗口，所以不请求client_max_window_bits，服务端要求更小的窗口时握手失败
+/// * 服务端的窗口不超过32K，解压时不需要限制
+pub(crate) struct Deflate {
+    compress: Compress,
+    decompress: Decompress,
+    //每个消息之后重置压缩的上下文
+    client_no_context_takeover: bool,
+    server_no_context_takeover: bool,
+}
+
+impl Deflate {
+    pub(crate) const OFFER: &'static str = "permessage-deflate";
+
+    ///解析服务端返回的Sec-WebSocket-Extensions
+    pub(crate) fn negotiate(extension: &str) -> HlsResult<Deflate> {
+        let error = |e: &str| HlsError::Protocol(format!("websocket handshake failed: {}: {}", e, extension));
+        if extension.contains(',') { return Err(error("more than one extension")); }
+        let mut params = extension.split(';').map(|x| x.trim());
+        if params.next() != Some(Deflate::OFFER) { return Err(error("unexpected extension")); }
+        let mut res = Deflate {
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+            client_no_context_takeover: false,
+            server_no_context_takeover: false,
+        };
+        let mut names = vec![];
+        for param in params.filter(|x| !x.is_empty()) {
+            let (name, value) = param.split_once('=').map(|(k, v)| (k.trim(), Some(v.trim().trim_matches('"')))).unwrap_or((param, None));
+            if names.contains(&name) { return Err(error("duplicate parameter")); }
+            names.push(name);
+            let bits = value.and_then(|x| x.parse::<u8>().ok());
+            match (name, value) {
+                ("server_no_context_takeover", None) => res.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => res.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(_)) if bits.is_some_and(|x| (8..=15).contains(&x)) => {}
+                ("client_max_window_bits", Some(_)) if bits == Some(15) => {}
+                _ => return Err(error("unsupported parameter")),
+            }
+        }
+        Ok(res)
+    }
+
+    pub(crate) fn compress(&mut self, data: &[u8]) -> HlsResult<Vec<u8>> {
+        let mut res = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            if res.len() == res.capacity() { res.reserve(res.capacity().max(64)); }
+            self.compress.compress_vec(&data[consumed..], &mut res, FlushCompress::Sync).map_err(|e| e.to_string())?;
+            //输入全部压缩并且输出没有填满时结束
+            if self.compress.total_in() - start == data.len() as u64 && res.len() < res.capacity() { break; }
+        }
+        if res.ends_with(&TAIL) { res.truncate(res.len() - TAIL.len()); }
+        if self.client_no_context_takeover { self.compress.reset(); }
+        Ok(res)
+    }
+
+    ///解压后超过max时返回错误
+    pub(crate) fn decompress(&mut self, data: &[u8], max: usize) -> HlsResult<Vec<u8>> {
+        let data = [data, &TAIL].concat();
+        let mut res = Vec::with_capacity((data.len() * 4).min(max.saturating_add(1)).max(64));
+        let start = self.decompress.total_in();
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if res.len() == res.capacity() { res.reserve(res.capacity()); }
+            let status = self.decompress.decompress_vec(&data[consumed..], &mut res, FlushDecompress::Sync).map_err(|e| e.to_string())?;
+            if res.len() > max { return Err("message too big".into()); }
+            if status == Status::StreamEnd || (self.decompress.total_in() - start == data.len() as u64 && res.len() < res.capacity()) { break; }
+            //输出还有空间但是没有进展
+            if status == Status::BufError && res.len() < res.capacity() { return Err("invalid deflate data".into()); }
+        }
+        if self.server_no_context_takeover { self.decompress.reset(false); }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Deflate;
+
+    #[test]
+    fn test_ws_deflate() {
+        //RFC 7692 7.2.3.1的例子
+        let mut deflate = Deflate::negotiate("permessage-deflate; client_max_window_bits=15").unwrap();
+        assert_eq!(deflate.decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], 100).unwrap(), b"Hello");
+        //共享上下文时第二个消息引用第一个
+        assert_eq!(deflate.decompress(&[0xf2, 0x00, 0x11, 0x00, 0x00], 100).unwrap(), b"Hello");
+        assert!(deflate.decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], 3).is_err());
+
+        let data = "websocket ".repeat(1000);
+        let compressed = deflate.compress(data.as_bytes()).unwrap();
+        assert!(compressed.len() < 100);
+        let mut peer = Deflate::negotiate("permessage-deflate").unwrap();
+        assert_eq!(peer.decompress(&compressed, data.len()).unwrap(), data.as_bytes());
+        assert_eq!(peer.decompress(&deflate.compress(data.as_bytes()).unwrap(), data.len()).unwrap(), data.as_bytes());
+        assert_eq!(peer.decompress(&deflate.compress(b"").unwrap(), 10).unwrap(), b"");
+
+        assert!(Deflate::negotiate("permessage-deflate; client_max_window_bits=10").is_err());
+        assert!(Deflate::negotiate("permessage-deflate; server_max_window_bits=10; client_no_context_takeover").is_ok());
+        assert!(Deflate::negotiate("permessage-deflate; server_no_context_takeover; server_no_context_takeover").is_err());
+        assert!(Deflate::negotiate("x-webkit-deflate-frame").is_err());
+    }
+}
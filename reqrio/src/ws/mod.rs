@@ -0,0 +1,324 @@
+#[cfg(anys)]
+use crate::coder;
+#[cfg(anys)]
+use crate::error::{HlsError, HlsResult};
+#[cfg(anys)]
+use crate::packet::Header;
+#[cfg(anys)]
+use crate::url::Url;
+#[cfg(aync)]
+pub use acws::AcWs;
+#[cfg(anys)]
+pub(crate) use codec::WsCodec;
+#[cfg(anys)]
+use deflate::Deflate;
+#[cfg(sync)]
+pub use scws::ScWs;
+use std::fmt::Display;
+#[cfg(anys)]
+use std::collections::hash_map::RandomState;
+#[cfg(anys)]
+use std::hash::{BuildHasher, Hasher};
+#[cfg(anys)]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(anys)]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(aync)]
+mod acws;
+#[cfg(anys)]
+mod codec;
+#[cfg(anys)]
+mod deflate;
+#[cfg(sync)]
+mod scws;
+
+///websocket的关闭码(RFC 6455 7.4)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseCode {
+    ///1000
+    Normal,
+    ///1001，服务端关闭或页面离开
+    Away,
+    ///1002
+    Protocol,
+    ///1003，不支持的数据类型
+    Unsupported,
+    ///1005，关闭帧中没有关闭码，不能发送
+    NoStatus,
+    ///1006，连接没有发送关闭帧就断开，不能发送
+    Abnormal,
+    ///1007，数据和类型不一致，例如text不是utf-8
+    Invalid,
+    ///1008
+    Policy,
+    ///1009
+    TooBig,
+    ///1010，服务端没有协商需要的扩展
+    Extension,
+    ///1011
+    Error,
+    Other(u16),
+}
+
+impl CloseCode {
+    ///可以出现在关闭帧中的关闭码
+    pub fn is_valid(&self) -> bool {
+        matches!(u16::from(*self), 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
+
+impl From<u16> for CloseCode {
+    fn from(value: u16) -> Self {
+        match value {
+            1000 => CloseCode::Normal,
+            1001 => CloseCode::Away,
+            1002 => CloseCode::Protocol,
+            1003 => CloseCode::Unsupported,
+            1005 => CloseCode::NoStatus,
+            1006 => CloseCode::Abnormal,
+            1007 => CloseCode::Invalid,
+            1008 => CloseCode::Policy,
+            1009 => CloseCode::TooBig,
+            1010 => CloseCode::Extension,
+            1011 => CloseCode::Error,
+            code => CloseCode::Other(code),
+        }
+    }
+}
+
+impl From<CloseCode> for u16 {
+    fn from(value: CloseCode) -> Self {
+        match value {
+            CloseCode::Normal => 1000,
+            CloseCode::Away => 1001,
+            CloseCode::Protocol => 1002,
+            CloseCode::Unsupported => 1003,
+            CloseCode::NoStatus => 1005,
+            CloseCode::Abnormal => 1006,
+            CloseCode::Invalid => 1007,
+            CloseCode::Policy => 1008,
+            CloseCode::TooBig => 1009,
+            CloseCode::Extension => 1010,
+            CloseCode::Error => 1011,
+            CloseCode::Other(code) => code,
+        }
+    }
+}
+
+impl Display for CloseCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", u16::from(*self))
+    }
+}
+
+///关闭帧中的关闭码和原因
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseFrame {
+    code: CloseCode,
+    reason: String,
+}
+
+impl CloseFrame {
+    pub fn new(code: CloseCode, reason: impl ToString) -> CloseFrame {
+        CloseFrame { code, reason: reason.to_string() }
+    }
+
+    pub fn code(&self) -> CloseCode {
+        self.code
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+///websocket的消息，分片的消息已经合并，压缩的消息已经解压
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    ///收到ping时已经自动回复pong
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    ///对端的关闭帧，没有关闭码时为None
+    Close(Option<CloseFrame>),
+}
+
+impl Message {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Message::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn is_close(&self) -> bool {
+        matches!(self, Message::Close(_))
+    }
+}
+
+///#### websocket握手和收发的设置
+/// * 默认请求permessage-deflate压缩，服务端不支持时不压缩
+/// * 默认单个消息(合并分片并解压后)最大64M，超过时以1009关闭连接
+/// * 默认发送的消息不分片，frame_size为分片时每帧的最大长度
+#[derive(Clone, Debug)]
+pub struct WsConfig {
+    protocols: Vec<String>,
+    deflate: bool,
+    max_message: usize,
+    frame_size: Option<usize>,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        WsConfig {
+            protocols: vec![],
+            deflate: true,
+            max_message: 64 << 20,
+            frame_size: None,
+        }
+    }
+}
+
+impl WsConfig {
+    pub fn new() -> WsConfig {
+        WsConfig::default()
+    }
+
+    ///Sec-WebSocket-Protocol中的子协议，按添加的顺序
+    pub fn with_protocol(mut self, protocol: impl ToString) -> Self {
+        self.protocols.push(protocol.to_string());
+        self
+    }
+
+    pub fn with_deflate(mut self, deflate: bool) -> Self {
+        self.deflate = deflate;
+        self
+    }
+
+    pub fn with_max_message(mut self, max_message: usize) -> Self {
+        self.max_message = max_message;
+        self
+    }
+
+    pub fn with_frame_size(mut self, frame_size: usize) -> Self {
+        self.frame_size = Some(frame_size.max(1));
+        self
+    }
+
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+}
+
+#[cfg(anys)]
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+///随机数，用于Sec-WebSocket-Key和掩码
+#[cfg(anys)]
+pub(crate) fn random_bytes<const N: usize>() -> [u8; N] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut res = [0; N];
+    for chunk in res.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+        chunk.copy_from_slice(&hasher.finish().to_be_bytes()[..chunk.len()]);
+    }
+    res
+}
+
+///握手请求的Sec-WebSocket-Key
+#[cfg(anys)]
+pub(crate) fn client_key() -> String {
+    coder::base64_encode(random_bytes::<16>())
+}
+
+///服务端应该返回的Sec-WebSocket-Accept
+#[cfg(anys)]
+fn accept_key(key: &str) -> String {
+    coder::base64_encode(coder::sha1(format!("{}{}", key, GUID)))
+}
+
+///握手请求，保留手动设置的请求头(cookie、origin、user-agent等)，去掉请求体和升级相关的请求头
+#[cfg(anys)]
+pub(crate) fn handshake_request(url: &Url, header: &Header, key: &str, config: &WsConfig) -> Vec<u8> {
+    let mut lines = vec![format!("GET {} HTTP/1.1", url.uri())];
+    lines.extend(header.raw().into_iter().filter(|x| {
+        let name = x.split(':').next().unwrap_or("").trim().to_lowercase();
+        !matches!(name.as_str(), "content-length" | "transfer-encoding" | "connection" | "upgrade") && !name.starts_with("sec-websocket-")
+    }));
+    lines.push("Upgrade: websocket".to_string());
+    lines.push("Connection: Upgrade".to_string());
+    lines.push(format!("Sec-WebSocket-Key: {}", key));
+    lines.push("Sec-WebSocket-Version: 13".to_string());
+    if !config.protocols.is_empty() { lines.push(format!("Sec-WebSocket-Protocol: {}", config.protocols.join(", "))); }
+    if config.deflate { lines.push(format!("Sec-WebSocket-Extensions: {}", Deflate::OFFER)); }
+    lines.push("".to_string());
+    lines.push("".to_string());
+    lines.join("\r\n").into_bytes()
+}
+
+///检查握手的响应，返回服务端选择的子协议和协商的压缩
+#[cfg(anys)]
+pub(crate) fn handshake_response(header: &Header, key: &str, config: &WsConfig) -> HlsResult<(Option<String>, Option<Deflate>)> {
+    let status = header.status().status_num();
+    if status != 101 { return Err(HlsError::Protocol(format!("websocket handshake failed: status {}", status))); }
+    let value = |name: &str| header.get(name).and_then(|x| x.as_string()).map(|x| x.trim());
+    if !value("upgrade").is_some_and(|x| x.eq_ignore_ascii_case("websocket")) {
+        return Err(HlsError::Protocol("websocket handshake failed: missing Upgrade: websocket".to_string()));
+    }
+    if !value("connection").is_some_and(|x| x.split(',').any(|x| x.trim().eq_ignore_ascii_case("upgrade"))) {
+        return Err(HlsError::Protocol("websocket handshake failed: missing Connection: Upgrade".to_string()));
+    }
+    if value("sec-websocket-accept") != Some(accept_key(key).as_str()) {
+        return Err(HlsError::Protocol("websocket handshake failed: invalid Sec-WebSocket-Accept".to_string()));
+    }
+    let protocol = value("sec-websocket-protocol").filter(|x| !x.is_empty()).map(|x| x.to_string());
+    if let Some(protocol) = &protocol && !config.protocols.contains(protocol) {
+        return Err(HlsError::Protocol(format!("websocket handshake failed: unexpected protocol {}", protocol)));
+    }
+    let deflate = match value("sec-websocket-extensions").filter(|x| !x.is_empty()) {
+        None => None,
+        Some(_) if !config.deflate => return Err(HlsError::Protocol("websocket handshake failed: unexpected extension".to_string())),
+        Some(extension) => Some(Deflate::negotiate(extension)?),
+    };
+    Ok((protocol, deflate))
+}
+
+#[cfg(all(test, anys))]
+mod tests {
+    use super::{accept_key, handshake_request, handshake_response, WsConfig};
+    use crate::packet::Header;
+    use crate::url::Url;
+
+    #[test]
+    fn test_ws_handshake() {
+        //RFC 6455 1.3的例子
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        let url = Url::try_from("ws://example.com/chat?room=1").unwrap();
+        let mut header = Header::new_req_h1();
+        header.set_host("example.com").unwrap();
+        header.insert("Connection", "keep-alive").unwrap();
+        let config = WsConfig::new().with_protocol("chat").with_protocol("superchat");
+        let request = String::from_utf8(handshake_request(&url, &header, "dGhlIHNhbXBsZSBub25jZQ==", &config)).unwrap();
+        assert!(request.starts_with("GET /chat?room=1 HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(!request.contains("keep-alive") && !request.contains("Content-Length"));
+        assert!(request.contains("Sec-WebSocket-Protocol: chat, superchat\r\n"));
+        assert!(request.ends_with("Sec-WebSocket-Extensions: permessage-deflate\r\n\r\n"));
+
+        let response = |extra: &str| {
+            let raw = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo={}", extra);
+            Header::try_from(raw).unwrap()
+        };
+        let (protocol, deflate) = handshake_response(&response("\r\nSec-WebSocket-Protocol: chat"), "dGhlIHNhbXBsZSBub25jZQ==", &config).unwrap();
+        assert_eq!(protocol.as_deref(), Some("chat"));
+        assert!(deflate.is_none());
+        let extension = "\r\nSec-WebSocket-Extensions: permessage-deflate; server_no_context_takeover";
+        assert!(handshake_response(&response(extension), "dGhlIHNhbXBsZSBub25jZQ==", &config).unwrap().1.is_some());
+        assert!(handshake_response(&response(extension), "dGhlIHNhbXBsZSBub25jZQ==", &config.clone().with_deflate(false)).is_err());
+        assert!(handshake_response(&response("\r\nSec-WebSocket-Protocol: other"), "dGhlIHNhbXBsZSBub25jZQ==", &config).is_err());
+        assert!(handshake_response(&response(""), "x3JJHMbDL1EzLkh9GBhXDw==", &config).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use super::{CloseCode, CloseFrame, Message, WsCodec};
+use crate::buffer::Buffer;
+use crate::error::{HlsError, HlsResult};
+use crate::packet::Header;
+use crate::stream::Stream;
+use crate::timeout::Timeout;
+use std::mem;
+
+///#### 同步websocket，通过`ScReq::websocket`创建
+/// * 连接和ScReq一样使用代理和tls指纹
+/// * Timeout的read为recv等待每次读取的超时，超时后连接仍然可以继续使用
+pub struct ScWs {
+    stream: Stream,
+    timeout: Timeout,
+    codec: WsCodec,
+    buffer: Buffer,
+    header: Header,
+    protocol: Option<String>,
+}
+
+impl ScWs {
+    pub(crate) fn new(stream: Stream, timeout: Timeout, codec: WsCodec, header: Header, protocol: Option<String>) -> ScWs {
+        ScWs { stream, timeout, codec, buffer: Buffer::new(), header, protocol }
+    }
+
+    ///握手的响应头
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    ///服务端选择的子协议
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    pub fn timeout(&self) -> &Timeout {
+        &self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Timeout) {
+        self.timeout = timeout;
+    }
+
+    pub fn send(&mut self, message: Message) -> HlsResult<()> {
+        let data = self.codec.encode(message)?;
+        self.stream.sync_write(&data, &self.timeout)
+    }
+
+    pub fn send_text(&mut self, text: impl ToString) -> HlsResult<()> {
+        self.send(Message::Text(text.to_string()))
+    }
+
+    pub fn send_binary(&mut self, data: impl Into<Vec<u8>>) -> HlsResult<()> {
+        self.send(Message::Binary(data.into()))
+    }
+
+    pub fn ping(&mut self, data: impl Into<Vec<u8>>) -> HlsResult<()> {
+        self.send(Message::Ping(data.into()))
+    }
+
+    ///读取下一个消息，收到ping时自动回复pong，收到关闭帧时回复后关闭连接
+    pub fn recv(&mut self) -> HlsResult<Message> {
+        loop {
+            let message = self.codec.next();
+            let reply = self.codec.take_reply();
+            if !reply.is_empty() {
+                let res = self.stream.sync_write(&reply, &self.timeout);
+                if message.is_ok() { res?; }
+            }
+            if self.codec.is_closed() { self.shutdown(); }
+            if let Some(message) = message? { return Ok(message); }
+            self.buffer.reset();
+            self.stream.sync_read(&mut self.buffer, &self.timeout)?;
+            self.codec.feed(self.buffer.filled());
+        }
+    }
+
+    ///发送关闭帧，等待对端的关闭帧后关闭连接，期间收到的消息被丢弃
+    pub fn close(&mut self, code: CloseCode, reason: impl AsRef<str>) -> HlsResult<()> {
+        if self.codec.is_closed() { return Ok(()); }
+        if !self.codec.is_closing() { self.send(Message::Close(Some(CloseFrame::new(code, reason.as_ref()))))?; }
+        loop {
+            match self.recv() {
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => continue,
+                //对端直接关闭了连接
+                Err(HlsError::PeerClosedConnection) => {
+                    self.shutdown();
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.shutdown();
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        let mut stream = mem::replace(&mut self.stream, Stream::unconnection());
+        if stream.is_connected() { let _ = stream.sync_shutdown(); }
+    }
+}
@@ -48,7 +48,7 @@ impl Key {
         Ok(Key::ChaCha20Poly1305Write(LessSafeKey::new(unbound_key)))
     }
 
-    pub fn encrypt(&mut self, nonce: Nonce, aad: Aad<&[u8; 13]>, in_out: &mut [u8]) -> RlsResult<Tag> {
+    pub fn encrypt<A: AsRef<[u8]>>(&mut self, nonce: Nonce, aad: Aad<A>, in_out: &mut [u8]) -> RlsResult<Tag> {
         match self {
             Key::AesGcmWrite(w) => Ok(w.seal_in_place_separate_tag(nonce, aad, in_out)?),
             Key::ChaCha20Poly1305Write(w) => Ok(w.seal_in_place_separate_tag(nonce, aad, in_out)?),
@@ -56,7 +56,7 @@ impl Key {
         }
     }
 
-    pub fn decrypt(&mut self, nonce: Nonce, aad: Aad<&[u8; 13]>, in_out: &mut [u8]) -> RlsResult<usize> {
+    pub fn decrypt<A: AsRef<[u8]>>(&mut self, nonce: Nonce, aad: Aad<A>, in_out: &mut [u8]) -> RlsResult<usize> {
         let res = match self {
             Key::AesGcmRead(r) => r.open_in_place(nonce, aad, in_out)?,
             Key::ChaCha20Poly1305Read(r) => r.open_in_place(nonce, aad, in_out)?,
@@ -4,6 +4,7 @@ use aws_lc_rs::aead::{Aad, Nonce};
 use iv::Iv;
 use key::Key;
 use crate::extend::Aead;
+use crate::message::Message;
 
 pub mod iv;
 pub mod key;
@@ -71,6 +72,45 @@ impl Cipher {
         self.seq += 1;
         Ok(len)
     }
+
+    ///#### tls1.3
+    /// * nonce: iv ^ seq, 没有explicit
+    /// * aad: record头部(5byte)
+    /// * payload: 明文+内容类型(1byte)+tag(16byte)
+    pub fn encrypt13(&mut self, record: &mut RecordLayer) -> RlsResult<()> {
+        let aad = Aad::from(Cipher::build_aad13(record));
+        let nonce = Nonce::assume_unique_for_key(self.iv.as_array(self.seq));
+        let payload = match record.messages.get_mut(0) {
+            Some(Message::Payload(v)) => v,
+            _ => return Err(RlsError::PayloadNone)
+        };
+        let payload_len = payload.len();
+        let tag = self.key.encrypt(nonce, aad, &mut payload[..payload_len - 16])?;
+        payload[payload_len - 16..].copy_from_slice(tag.as_ref());
+        self.seq += 1;
+        Ok(())
+    }
+
+    ///返回值为内部明文长度(包含末尾的内容类型和填充)
+    pub fn decrypt13(&mut self, record: &mut RecordLayer) -> RlsResult<usize> {
+        let aad = Aad::from(Cipher::build_aad13(record));
+        let nonce = Nonce::assume_unique_for_key(self.iv.as_array(self.seq));
+        let payload = match record.messages.get_mut(0) {
+            Some(Message::Payload(v)) => v,
+            _ => return Err(RlsError::PayloadNone)
+        };
+        let len = self.key.decrypt(nonce, aad, &mut payload[0..])?;
+        self.seq += 1;
+        Ok(len)
+    }
+
+    fn build_aad13(layer: &RecordLayer) -> [u8; 5] {
+        let mut res = [0; 5];
+        res[0] = layer.context_type.as_u8();
+        res[1..3].copy_from_slice(&layer.version.as_bytes());
+        res[3..5].copy_from_slice(&layer.len.to_be_bytes());
+        res
+    }
 }
 
 
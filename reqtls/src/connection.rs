@@ -15,7 +15,17 @@ use crate::error::RlsResult;
 use std::fs::OpenOptions;
 use std::io::Write;
 use crate::extend::Aead;
+use crate::extend::Extension;
+use crate::extend::ExtensionKind;
+use crate::hkdf::Hkdf;
+use crate::message::client_hello::ClientHello;
+use crate::message::HandshakeType;
+use crate::message::certificate::Certificates;
+use crate::message::key_exchange::ServerKeyExchange;
+use crate::secret::key::PriKey;
+use crate::verify::Verifier;
 use crate::RlsError;
+use aws_lc_rs::constant_time;
 
 pub struct Connection {
     client_random: Bytes,
@@ -30,6 +40,20 @@ pub struct Connection {
     cipher_suite: CipherSuite,
     session_bytes: Vec<u8>,
     prf: Prf,
+    tls13: bool,
+    hkdf: Hkdf,
+    key_shares: Vec<(u16, PriKey)>,
+    client_secret: Vec<u8>,
+    server_secret: Vec<u8>,
+    handshake_buffer: Vec<u8>,
+    certificates: Vec<Vec<u8>>,
+    cert_request: Option<Vec<u8>>,
+    read_type: RecordType,
+    hello_retried: bool,
+    retry_hello: Option<Vec<u8>>,
+    key_update: Option<Vec<u8>>,
+    verifier: Option<Verifier>,
+    cert_verified: bool,
 }
 impl Connection {
     pub fn new(client_random: Vec<u8>) -> Connection {
@@ -46,17 +70,368 @@ impl Connection {
             cipher_suite: CipherSuite::new(0),
             session_bytes: vec![],
             prf: Prf::default(),
+            tls13: false,
+            hkdf: Hkdf::default(),
+            key_shares: vec![],
+            client_secret: vec![],
+            server_secret: vec![],
+            handshake_buffer: vec![],
+            certificates: vec![],
+            cert_request: None,
+            read_type: RecordType::ApplicationData,
+            hello_retried: false,
+            retry_hello: None,
+            key_update: None,
+            verifier: None,
+            cert_verified: false,
         }
     }
 
+    ///设置证书校验，None时不校验服务端证书
+    pub fn set_verifier(&mut self, verifier: Option<Verifier>) {
+        self.verifier = verifier;
+    }
+
+    ///为ClientHello中key_share的每个group生成密钥(tls1.3)，不支持的group(如GREASE)保持原样
+    pub fn set_key_shares(&mut self, client_hello: &mut ClientHello) -> RlsResult<()> {
+        self.key_shares.clear();
+        let key_share = match client_hello.key_share_mut() {
+            None => return Ok(()),
+            Some(v) => v
+        };
+        for entry in key_share.entries_mut() {
+            let group = entry.group().as_u16();
+            if let Some(key) = PriKey::from_group(group)? {
+                entry.set_exchange(key.pub_key());
+                self.key_shares.push((group, key));
+            }
+        }
+        Ok(())
+    }
+
     pub fn set_by_server_hello(&mut self, server_hello: ServerHello) -> RlsResult<()> {
+        if server_hello.is_retry_request() { return self.set_by_retry_request(server_hello); }
+        self.tls13 = server_hello.selected_version() == VersionKind::TLS_1_3 as u16;
+        let key_share = server_hello.key_share().map(|x| (x.group().as_u16(), x.exchange().as_bytes()));
         self.use_ems = server_hello.use_ems();
         self.alpn = server_hello.alpn();
         self.server_random = server_hello.random;
-        self.cipher_suite = server_hello.cipher_suite;
-        self.cipher_suite.init_aead_hasher()?;
+        if self.hello_retried {
+            //HelloRetryRequest时已初始化hasher，不能重置摘要
+            if server_hello.cipher_suite.as_u16() != self.cipher_suite.as_u16() { return Err("cipher suite changed after HelloRetryRequest".into()); }
+            self.hello_retried = false;
+        } else {
+            self.cipher_suite = server_hello.cipher_suite;
+            self.cipher_suite.init_aead_hasher()?;
+        }
         let hasher = self.cipher_suite.hasher().as_ref().ok_or(RlsError::HasherNone)?;
         self.prf = Prf::from_hasher(hasher);
+        self.hkdf = Hkdf::from_hasher(hasher);
+        if !self.tls13 { return Ok(()); }
+        let (group, server_pub_key) = key_share.ok_or("server key share none")?;
+        let pos = self.key_shares.iter().position(|(g, _)| *g == group).ok_or("unsupported key share group")?;
+        let (_, keypair) = self.key_shares.remove(pos);
+        self.key_shares.clear();
+        let share_secret = keypair.diffie_hellman(server_pub_key)?;
+        //ClientHello和ServerHello在hasher初始化前缓存在session_bytes中
+        self.update_session([])?;
+        self.make_handshake_cipher(&share_secret)
+    }
+
+    ///#### HelloRetryRequest
+    /// * 摘要中的ClientHello1替换为message_hash(254+len+Hash(ClientHello1))
+    /// * 第二个ClientHello只保留服务端选择的group，并回传cookie
+    fn set_by_retry_request(&mut self, server_hello: ServerHello) -> RlsResult<()> {
+        if self.hello_retried { return Err("duplicate HelloRetryRequest".into()); }
+        let group = server_hello.key_share().ok_or("retry key share none")?.group().as_u16();
+        let cookie = server_hello.extension(0x2c).map(|x| x.as_bytes());
+        let hello_len = u32::from_be_bytes([0, self.session_bytes[1], self.session_bytes[2], self.session_bytes[3]]) as usize + 4;
+        let first_hello = self.session_bytes.drain(..hello_len).collect::<Vec<_>>();
+        let retry_request = std::mem::take(&mut self.session_bytes);
+        self.cipher_suite = server_hello.cipher_suite;
+        self.cipher_suite.init_aead_hasher()?;
+        self.cipher_suite.update(&first_hello);
+        let first_hash = self.cipher_suite.session_hash()?;
+        self.cipher_suite.init_aead_hasher()?;
+        self.cipher_suite.update([254, 0, 0, first_hash.len() as u8]);
+        self.cipher_suite.update(&first_hash);
+        self.cipher_suite.update(&retry_request);
+
+        let mut client_hello = ClientHello::from_bytes(HandshakeType::ClientHello, &first_hello)?;
+        let keypair = PriKey::from_group(group)?.ok_or("unsupported key share group")?;
+        client_hello.key_share_mut().ok_or("client key share none")?.retain_group(group, keypair.pub_key());
+        self.key_shares = vec![(group, keypair)];
+        if let Some(cookie) = cookie {
+            client_hello.push_extension(Extension::from_bytes(&cookie)?.remove(0));
+        }
+        let hello = client_hello.as_bytes();
+        self.update_session(&hello)?;
+        let mut record = vec![RecordType::HandShake.as_u8(), 3, 3];
+        record.extend((hello.len() as u16).to_be_bytes());
+        record.extend(hello);
+        self.retry_hello = Some(record);
+        self.hello_retried = true;
+        self.tls13 = true;
+        Ok(())
+    }
+
+    ///HelloRetryRequest后需要重新发送的ClientHello
+    pub fn take_retry_hello(&mut self) -> Option<Vec<u8>> {
+        self.retry_hello.take()
+    }
+
+    ///对端请求更新密钥时需要发送的KeyUpdate记录，必须在之后的应用数据之前发送
+    pub fn take_key_update(&mut self) -> Option<Vec<u8>> {
+        self.key_update.take()
+    }
+
+    ///#### tls1.3密钥计划
+    /// * handshake_secret = HKDF-Extract(Derive-Secret(early_secret, "derived"), share_secret)
+    /// * master_secret = HKDF-Extract(Derive-Secret(handshake_secret, "derived"), 0)
+    fn make_handshake_cipher(&mut self, share_secret: &[u8]) -> RlsResult<()> {
+        let early_secret = self.hkdf.extract(&[], &[])?;
+        let derived = self.hkdf.derive_secret(&early_secret, "derived", &[])?;
+        let handshake_secret = self.hkdf.extract(&derived, share_secret)?;
+        let transcript_hash = self.session_hash()?;
+        self.client_secret = self.hkdf.derive_secret(&handshake_secret, "c hs traffic", &transcript_hash)?;
+        self.server_secret = self.hkdf.derive_secret(&handshake_secret, "s hs traffic", &transcript_hash)?;
+        let derived = self.hkdf.derive_secret(&handshake_secret, "derived", &[])?;
+        self.master_secret = self.hkdf.extract(&derived, &[])?;
+        self.set_write_secret()?;
+        self.set_read_secret()
+    }
+
+    fn set_write_secret(&mut self) -> RlsResult<()> {
+        let aead = *self.cipher_suite.aead().ok_or(RlsError::AeadNone)?;
+        let key = self.hkdf.expand_label(&self.client_secret, "key", &[], aead.key_len())?;
+        let iv = self.hkdf.expand_label(&self.client_secret, "iv", &[], 12)?;
+        self.write = Cipher::none();
+        self.write.set_key(Key::write(&key, &aead)?);
+        self.write.set_iv(Iv::new(&iv, vec![]));
+        Ok(())
+    }
+
+    fn set_read_secret(&mut self) -> RlsResult<()> {
+        let aead = *self.cipher_suite.aead().ok_or(RlsError::AeadNone)?;
+        let key = self.hkdf.expand_label(&self.server_secret, "key", &[], aead.key_len())?;
+        let iv = self.hkdf.expand_label(&self.server_secret, "iv", &[], 12)?;
+        self.read = Cipher::none();
+        self.read.set_key(Key::read(&key, &aead)?);
+        self.read.set_iv(Iv::new(&iv, vec![]));
+        Ok(())
+    }
+
+    ///#### tls1.3握手阶段处理ServerHello之后的记录
+    /// * 兼容模式的ChangeCipherSpec直接忽略
+    /// * EncryptedExtensions/Certificate/CertificateVerify/Finished可能分散在多个记录中
+    /// * 校验服务端Finished后返回客户端需要发送的记录(Finished)，并切换到应用流量密钥
+    pub fn read_handshake13(&mut self, buffer: &mut [u8]) -> RlsResult<Option<Vec<u8>>> {
+        if buffer.first() == Some(&RecordType::HandShake.as_u8()) { return self.read_retry_server_hello(buffer); }
+        let len = {
+            let mut record = RecordLayer::from_bytes(buffer, true)?;
+            match record.context_type {
+                RecordType::CipherSpec => return Ok(None),
+                RecordType::ApplicationData => {}
+                RecordType::Alert => {
+                    let payload = record.messages[0].payload().ok_or(RlsError::PayloadNone)?;
+                    return Err(Connection::alert_error(&payload[0..]));
+                }
+                RecordType::HandShake => return Err("unexpected plaintext handshake".into())
+            }
+            self.read_message13(&mut record)?
+        };
+        match self.read_type {
+            RecordType::HandShake => self.handshake_buffer.extend_from_slice(&buffer[5..5 + len]),
+            RecordType::Alert => return Err(Connection::alert_error(&buffer[5..5 + len])),
+            _ => return Err("unexpected record during handshake".into())
+        }
+        while self.handshake_buffer.len() >= 4 {
+            let msg_len = u32::from_be_bytes([0, self.handshake_buffer[1], self.handshake_buffer[2], self.handshake_buffer[3]]) as usize;
+            if self.handshake_buffer.len() < msg_len + 4 { break; }
+            let message = self.handshake_buffer.drain(..msg_len + 4).collect::<Vec<_>>();
+            if let Some(finished) = self.handle_handshake13(&message)? {
+                return Ok(Some(finished));
+            }
+        }
+        Ok(None)
+    }
+
+    ///HelloRetryRequest之后的ServerHello仍为明文
+    fn read_retry_server_hello(&mut self, buffer: &mut [u8]) -> RlsResult<Option<Vec<u8>>> {
+        if !self.hello_retried { return Err("unexpected plaintext handshake".into()); }
+        self.update_session(&buffer[5..])?;
+        let record = RecordLayer::from_bytes(buffer, false)?;
+        for message in record.messages {
+            if let Message::ServerHello(v) = message {
+                self.set_by_server_hello(v)?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn handle_handshake13(&mut self, message: &[u8]) -> RlsResult<Option<Vec<u8>>> {
+        match HandshakeType::from_byte(message[0]) {
+            Some(HandshakeType::EncryptedExtensions) => {
+                if message.len() < 6 { return Err(RlsError::MessageTooShort); }
+                let ext_len = u16::from_be_bytes([message[4], message[5]]) as usize;
+                if message.len() < 6 + ext_len { return Err(RlsError::MessageTooShort); }
+                let extensions = Extension::from_server_bytes(&message[6..6 + ext_len])?;
+                let extend = extensions.iter().find(|x| x.extension_type().as_u16() == ExtensionKind::ApplicationLayerProtocolNegotiation as u16);
+                if let Some(protocol) = extend.and_then(|x| x.application_layer_protocol_negotiation()) {
+                    self.alpn = protocol.values().first().cloned();
+                }
+            }
+            Some(HandshakeType::CertificateRequest) => {
+                let context_len = *message.get(4).ok_or(RlsError::MessageTooShort)? as usize;
+                self.cert_request = Some(message.get(5..5 + context_len).ok_or(RlsError::MessageTooShort)?.to_vec());
+            }
+            Some(HandshakeType::Certificate) => {
+                self.certificates = Connection::parse_certificates13(message)?;
+                if let Some(ref verifier) = self.verifier { verifier.verify_chain(&self.certificates)?; }
+            }
+            Some(HandshakeType::CertificateVerify) => self.verify_certificate13(message)?,
+            Some(HandshakeType::CipherSpec) => {
+                if self.verifier.is_some() && !self.cert_verified { return Err(RlsError::InvalidCertificate("server certificate verify none".to_string())); }
+                let transcript_hash = self.session_hash()?;
+                let verify_data = self.hkdf.finished_verify_data(&self.server_secret, &transcript_hash)?;
+                if constant_time::verify_slices_are_equal(&verify_data, &message[4..]).is_err() { return Err("server finished verify failed".into()); }
+                self.update_session(message)?;
+                return Ok(Some(self.make_finish_message13()?));
+            }
+            _ => return Err(format!("unexpected handshake message: {}", message[0]).into())
+        }
+        self.update_session(message)?;
+        Ok(None)
+    }
+
+    ///#### CertificateVerify: scheme(2) + signature(2+n)
+    /// * 签名内容: 64个空格 + "TLS 1.3, server CertificateVerify" + 0 + Hash(ClientHello..Certificate)
+    fn verify_certificate13(&mut self, message: &[u8]) -> RlsResult<()> {
+        let verifier = match self.verifier {
+            None => return Ok(()),
+            Some(ref v) => v
+        };
+        let header = message.get(4..8).ok_or(RlsError::MessageTooShort)?;
+        let scheme = u16::from_be_bytes([header[0], header[1]]);
+        let sig_len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let signature = message.get(8..8 + sig_len).ok_or(RlsError::MessageTooShort)?;
+        let mut content = vec![0x20; 64];
+        content.extend_from_slice(b"TLS 1.3, server CertificateVerify\0");
+        content.extend(self.session_hash()?);
+        let leaf = self.certificates.first().ok_or(RlsError::InvalidCertificate("server certificate none".to_string()))?;
+        verifier.verify_signature(leaf, scheme, &content, signature, true)?;
+        self.cert_verified = true;
+        Ok(())
+    }
+
+    ///certificate_request_context(1+n) + certificate_list(3+n)[cert_data(3+n) + extensions(2+n)]
+    fn parse_certificates13(message: &[u8]) -> RlsResult<Vec<Vec<u8>>> {
+        let context_len = *message.get(4).ok_or(RlsError::MessageTooShort)? as usize;
+        let mut index = 5 + context_len;
+        let list = message.get(index..index + 3).ok_or(RlsError::MessageTooShort)?;
+        let list_end = index + 3 + u32::from_be_bytes([0, list[0], list[1], list[2]]) as usize;
+        index += 3;
+        let mut res = vec![];
+        while index < list_end {
+            let len = message.get(index..index + 3).ok_or(RlsError::MessageTooShort)?;
+            let len = u32::from_be_bytes([0, len[0], len[1], len[2]]) as usize;
+            res.push(message.get(index + 3..index + 3 + len).ok_or(RlsError::MessageTooShort)?.to_vec());
+            index += 3 + len;
+            let ext = message.get(index..index + 2).ok_or(RlsError::MessageTooShort)?;
+            index += 2 + u16::from_be_bytes([ext[0], ext[1]]) as usize;
+        }
+        Ok(res)
+    }
+
+    fn make_finish_message13(&mut self) -> RlsResult<Vec<u8>> {
+        let server_finished_hash = self.session_hash()?;
+        let mut res = vec![];
+        if let Some(context) = self.cert_request.take() {
+            //未配置客户端证书，回复空的Certificate
+            let mut message = vec![HandshakeType::Certificate.as_u8(), 0, 0, 0, context.len() as u8];
+            message.extend(context);
+            message.extend([0, 0, 0]);
+            let len = (message.len() - 4) as u32;
+            message[1..4].copy_from_slice(&len.to_be_bytes()[1..]);
+            self.update_session(&message)?;
+            res.extend(self.make_handshake_record13(&message)?);
+        }
+        let verify_data = self.hkdf.finished_verify_data(&self.client_secret, &self.session_hash()?)?;
+        let mut message = vec![HandshakeType::CipherSpec.as_u8(), 0, 0, verify_data.len() as u8];
+        message.extend(verify_data);
+        self.update_session(&message)?;
+        res.extend(self.make_handshake_record13(&message)?);
+        self.client_secret = self.hkdf.derive_secret(&self.master_secret, "c ap traffic", &server_finished_hash)?;
+        self.server_secret = self.hkdf.derive_secret(&self.master_secret, "s ap traffic", &server_finished_hash)?;
+        self.set_write_secret()?;
+        self.set_read_secret()?;
+        Ok(res)
+    }
+
+    fn make_handshake_record13(&mut self, message: &[u8]) -> RlsResult<Vec<u8>> {
+        let mut buffer = vec![0; self.encrypted_payload_len(message.len()) + 5];
+        buffer[5..5 + message.len()].copy_from_slice(message);
+        let len = self.make_message(RecordType::HandShake, &mut buffer, message.len())?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+
+    fn read_message13(&mut self, layer: &mut RecordLayer) -> RlsResult<usize> {
+        let len = self.read.decrypt13(layer)?;
+        let payload = &layer.messages[0].payload().ok_or(RlsError::PayloadNone)?[..len];
+        //去掉末尾的填充0，最后一个非0字节为实际的内容类型
+        let pos = payload.iter().rposition(|x| *x != 0).ok_or("inner content type none")?;
+        self.read_type = RecordType::from_byte(payload[pos]).ok_or("LayerType Unknown")?;
+        Ok(pos)
+    }
+
+    ///#### tls1.3握手后的消息，一个记录中可能有多个消息，一个消息也可能分散在多个记录中
+    /// * KeyUpdate: 更新读取密钥，对端请求更新时(update_requested)生成自己的KeyUpdate并更新写入密钥
+    /// * NewSessionTicket等其他消息忽略
+    fn read_post_handshake13(&mut self, payload: &[u8]) -> RlsResult<()> {
+        self.handshake_buffer.extend_from_slice(payload);
+        while self.handshake_buffer.len() >= 4 {
+            let msg_len = u32::from_be_bytes([0, self.handshake_buffer[1], self.handshake_buffer[2], self.handshake_buffer[3]]) as usize;
+            if self.handshake_buffer.len() < msg_len + 4 { break; }
+            let message = self.handshake_buffer.drain(..msg_len + 4).collect::<Vec<_>>();
+            if message[0] != HandshakeType::KeyUpdate.as_u8() { continue; }
+            let requested = *message.get(4).ok_or(RlsError::MessageTooShort)? == 1;
+            self.server_secret = self.hkdf.expand_label(&self.server_secret, "traffic upd", &[], self.hkdf.hash_size())?;
+            self.set_read_secret()?;
+            //已有未发送的KeyUpdate时不再重复更新
+            if requested && self.key_update.is_none() {
+                let message = [HandshakeType::KeyUpdate.as_u8(), 0, 0, 1, 0];
+                self.key_update = Some(self.make_handshake_record13(&message)?);
+                self.client_secret = self.hkdf.expand_label(&self.client_secret, "traffic upd", &[], self.hkdf.hash_size())?;
+                self.set_write_secret()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn alert_error(payload: &[u8]) -> RlsError {
+        match payload {
+            [level, description, ..] => RlsError::Alert(*level, *description),
+            _ => "tls alert".into()
+        }
+    }
+
+    ///tls1.2的服务端证书链
+    pub fn set_by_certificate(&mut self, certificates: &Certificates) -> RlsResult<()> {
+        self.certificates = certificates.certificates().iter().map(|x| x.value().to_vec()).collect();
+        if let Some(ref verifier) = self.verifier { verifier.verify_chain(&self.certificates)?; }
+        Ok(())
+    }
+
+    ///tls1.2的ServerKeyExchange，签名内容为client_random + server_random + params
+    pub fn set_by_server_key_exchange(&mut self, exchange: &ServerKeyExchange) -> RlsResult<()> {
+        let param = exchange.hellman_param();
+        if let Some(ref verifier) = self.verifier {
+            let leaf = self.certificates.first().ok_or(RlsError::InvalidCertificate("server certificate none".to_string()))?;
+            let content = [self.client_random.as_ref(), self.server_random.as_ref(), &param.params_bytes()].concat();
+            verifier.verify_signature(leaf, param.signature_algorithm().as_u16(), &content, param.signature().as_ref(), false)?;
+        }
+        self.set_by_exchange_key(param.pub_key().clone(), *param.named_curve());
         Ok(())
     }
 
@@ -105,6 +480,7 @@ impl Connection {
     }
 
     pub fn make_message(&mut self, cty: RecordType, buffer: &mut [u8], payload_len: usize) -> RlsResult<usize> {
+        if self.tls13 { return self.make_message13(cty, buffer, payload_len); }
         let aead = self.cipher_suite.aead().ok_or(RlsError::AeadNone)?;
         let payload_len = aead.encrypted_payload_len(payload_len);
         buffer[0] = cty.as_u8();
@@ -121,8 +497,65 @@ impl Connection {
         Ok(payload_len + 5)
     }
 
+    ///tls1.3外层类型固定为ApplicationData, 实际类型放在明文末尾
+    fn make_message13(&mut self, cty: RecordType, buffer: &mut [u8], payload_len: usize) -> RlsResult<usize> {
+        buffer[5 + payload_len] = cty.as_u8();
+        let record_len = self.encrypted_payload_len(payload_len);
+        buffer[0] = RecordType::ApplicationData.as_u8();
+        buffer[1..3].copy_from_slice(&(VersionKind::TLS_1_2 as u16).to_be_bytes());
+        buffer[3..5].copy_from_slice(&(record_len as u16).to_be_bytes());
+        let mut layer = RecordLayer {
+            context_type: RecordType::ApplicationData,
+            version: Version::new(VersionKind::TLS_1_2 as u16),
+            len: record_len as u16,
+            messages: vec![Message::Payload(Payload::from_slice(&mut buffer[5..record_len + 5]))],
+        };
+        self.write.encrypt13(&mut layer)?;
+        Ok(record_len + 5)
+    }
+
+    ///解密后的实际类型通过read_type获取
     pub fn read_message<'a>(&mut self, layer: &'a mut RecordLayer<'a>) -> RlsResult<usize> {
-        self.read.decrypt(layer, self.cipher_suite.aead().ok_or(RlsError::AeadNone)?)
+        self.read_type = layer.context_type;
+        match self.tls13 {
+            true => {
+                let len = self.read_message13(layer)?;
+                if let RecordType::HandShake = self.read_type {
+                    let payload = layer.messages[0].payload().ok_or(RlsError::PayloadNone)?;
+                    self.read_post_handshake13(&payload[..len])?;
+                }
+                Ok(len)
+            }
+            false => self.read.decrypt(layer, self.cipher_suite.aead().ok_or(RlsError::AeadNone)?)
+        }
+    }
+
+    pub fn read_type(&self) -> RecordType {
+        self.read_type
+    }
+
+    pub fn is_tls13(&self) -> bool {
+        self.tls13
+    }
+
+    ///解密后明文在记录中的起始位置
+    pub fn payload_start(&self) -> usize {
+        match self.tls13 {
+            true => 5,
+            false => self.cipher_suite.aead().map(|x| x.payload_start()).unwrap_or(5)
+        }
+    }
+
+    pub fn encrypted_payload_len(&self, len: usize) -> usize {
+        match self.tls13 {
+            true => len + 1 + 16,
+            false => self.cipher_suite.aead().map(|x| x.encrypted_payload_len(len)).unwrap_or(len)
+        }
+    }
+
+    ///服务端证书链(DER), 第一个为叶子证书
+    pub fn certificates(&self) -> &Vec<Vec<u8>> {
+        &self.certificates
     }
 
     pub fn named_curve(&self) -> &NamedCurve {
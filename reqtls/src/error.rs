@@ -20,7 +20,10 @@ pub enum RlsError {
     AeadNone,
     InvalidCipherSuite,
     MessageTooShort,
-    StdError(Box<dyn Error>),
+    InvalidCertificate(String),
+    ///对端发送的alert(level, description)，close_notify为(1, 0)
+    Alert(u8, u8),
+    StdError(Box<dyn Error + Send + Sync>),
     Currently(String),
 }
 
@@ -36,6 +39,9 @@ impl Display for RlsError {
             RlsError::HasherNone => f.write_str("Hasher none"),
             RlsError::InvalidCipherSuite=>f.write_str("Invalid cipher suite"),
             RlsError::MessageTooShort=>f.write_str("Message too short"),
+            RlsError::InvalidCertificate(e) => f.write_fmt(format_args!("Invalid certificate: {}", e)),
+            RlsError::Alert(1, 0) => f.write_str("close notify"),
+            RlsError::Alert(level, description) => f.write_fmt(format_args!("tls alert: level={} description={}", level, description)),
             RlsError::StdError(e) => f.write_fmt(format_args!("{:?}", e)),
             RlsError::Currently(e) => f.write_str(e),
         }
@@ -108,12 +114,31 @@ impl From<p256::elliptic_curve::Error> for RlsError {
     }
 }
 
+impl From<webpki::Error> for RlsError {
+    fn from(value: webpki::Error) -> Self {
+        RlsError::InvalidCertificate(value.to_string())
+    }
+}
+
+impl From<pki_types::pem::Error> for RlsError {
+    fn from(value: pki_types::pem::Error) -> Self {
+        RlsError::InvalidCertificate(value.to_string())
+    }
+}
+
 impl From<RlsError> for io::Error {
     fn from(error: RlsError) -> Self {
-        io::Error::new(io::ErrorKind::Other, error.to_string())
+        io::Error::other(error)
     }
 }
 
-impl Error for RlsError {}
+impl Error for RlsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RlsError::StdError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 pub type RlsResult<T> = Result<T, RlsError>;
\ No newline at end of file
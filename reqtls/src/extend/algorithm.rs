@@ -63,6 +63,10 @@ impl SignatureAlgorithm {
     pub fn as_bytes(&self) -> [u8; 2] {
         (*self as u16).to_be_bytes()
     }
+
+    pub fn as_u16(&self) -> u16 {
+        *self as u16
+    }
 }
 
 #[derive(Debug)]
@@ -1,5 +1,7 @@
 use std::fmt::{Debug, Formatter};
 use super::super::bytes::Bytes;
+use crate::error::RlsResult;
+use crate::RlsError;
 
 pub struct KeyShareType(u16);
 impl KeyShareType {
@@ -10,6 +12,10 @@ impl KeyShareType {
     pub fn as_bytes(&self) -> [u8; 2] {
         self.0.to_be_bytes()
     }
+
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
 }
 
 impl Debug for KeyShareType {
@@ -84,6 +90,19 @@ impl KeyShareEntry {
         res
     }
 
+    pub fn group(&self) -> &KeyShareType {
+        &self.group
+    }
+
+    pub fn exchange(&self) -> &Bytes {
+        &self.exchange
+    }
+
+    pub fn set_exchange(&mut self, exchange: Vec<u8>) {
+        self.exchange_len = exchange.len() as u16;
+        self.exchange = Bytes::new(exchange);
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut res = self.group.as_bytes().to_vec();
         let vs = self.exchange.as_bytes();
@@ -97,6 +116,8 @@ impl KeyShareEntry {
 pub struct KeyShare {
     len: usize,
     entries: Vec<KeyShareEntry>,
+    ///ServerHello中只有一个entry, 没有长度前缀
+    server: bool,
 }
 
 impl KeyShare {
@@ -104,6 +125,7 @@ impl KeyShare {
         KeyShare {
             len: 0,
             entries: vec![],
+            server: false,
         }
     }
     pub fn from_bytes(bytes: &[u8]) -> KeyShare {
@@ -113,11 +135,48 @@ impl KeyShare {
         res
     }
 
+    ///ServerHello: group(2)+len(2)+exchange; HelloRetryRequest: group(2)
+    pub fn from_server_bytes(bytes: &[u8]) -> RlsResult<KeyShare> {
+        let mut res = KeyShare::new();
+        res.server = true;
+        if bytes.len() < 2 { return Err(RlsError::MessageTooShort); }
+        let mut entry = KeyShareEntry::new();
+        entry.group = KeyShareType::new(u16::from_be_bytes([bytes[0], bytes[1]]));
+        if bytes.len() >= 4 {
+            entry.exchange_len = u16::from_be_bytes([bytes[2], bytes[3]]);
+            if bytes.len() < 4 + entry.exchange_len as usize { return Err(RlsError::MessageTooShort); }
+            entry.exchange = Bytes::new(bytes[4..4 + entry.exchange_len as usize].to_vec());
+        }
+        res.entries.push(entry);
+        Ok(res)
+    }
+
+    pub fn entries(&self) -> &Vec<KeyShareEntry> {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut Vec<KeyShareEntry> {
+        &mut self.entries
+    }
+
+    ///HelloRetryRequest后只保留服务端选择的group
+    pub fn retain_group(&mut self, group: u16, exchange: Vec<u8>) {
+        let mut entry = KeyShareEntry::new();
+        entry.group = KeyShareType::new(group);
+        entry.set_exchange(exchange);
+        self.entries = vec![entry];
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut res = (self.len as u16).to_be_bytes().to_vec();
+        let mut res = vec![];
         for entry in &self.entries {
             res.extend(entry.as_bytes());
         }
+        if !self.server {
+            let mut bs = (res.len() as u16).to_be_bytes().to_vec();
+            bs.extend(res);
+            res = bs;
+        }
         res
     }
 }
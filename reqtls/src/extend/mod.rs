@@ -190,6 +190,15 @@ impl ExtensionValue {
         }
     }
 
+    ///ServerHello/EncryptedExtensions中key_share和supported_versions的格式与ClientHello不同
+    pub fn from_server_bytes(t: &ExtensionType, bytes: &[u8]) -> RlsResult<Self> {
+        match t.kind() {
+            Some(ExtensionKind::SupportedVersions) => Ok(ExtensionValue::SupportedVersions(Versions::from_server_bytes(bytes))),
+            Some(ExtensionKind::KeyShare) => Ok(ExtensionValue::KeyShare(KeyShare::from_server_bytes(bytes)?)),
+            _ => ExtensionValue::from_bytes(t, bytes)
+        }
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         match self {
             ExtensionValue::PskKeyExchangeMode(v) => v.as_bytes(),
@@ -240,6 +249,14 @@ impl Extension {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> RlsResult<Vec<Extension>> {
+        Extension::parse(bytes, false)
+    }
+
+    pub fn from_server_bytes(bytes: &[u8]) -> RlsResult<Vec<Extension>> {
+        Extension::parse(bytes, true)
+    }
+
+    fn parse(bytes: &[u8], server: bool) -> RlsResult<Vec<Extension>> {
         let mut res = vec![];
         let mut index = 0;
         while index < bytes.len() {
@@ -247,7 +264,12 @@ impl Extension {
             let mut v = Extension::new();
             v.type_ = ExtensionType(tv);
             v.len = u16::from_be_bytes([bytes[index + 2], bytes[index + 3]].try_into()?);
-            v.value = ExtensionValue::from_bytes(&v.type_, &bytes[index + 4..index + 4 + v.len as usize])?;
+            if index + 4 + v.len as usize > bytes.len() { return Err("extension body not enough".into()); }
+            let body = &bytes[index + 4..index + 4 + v.len as usize];
+            v.value = match server {
+                true => ExtensionValue::from_server_bytes(&v.type_, body)?,
+                false => ExtensionValue::from_bytes(&v.type_, body)?
+            };
             index += 4 + v.len as usize;
             res.push(v);
         }
@@ -284,6 +306,27 @@ impl Extension {
         }
     }
 
+    pub fn key_share(&self) -> Option<&KeyShare> {
+        match &self.value {
+            ExtensionValue::KeyShare(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn key_share_mut(&mut self) -> Option<&mut KeyShare> {
+        match self.value {
+            ExtensionValue::KeyShare(ref mut v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn supported_versions(&self) -> Option<&Versions> {
+        match &self.value {
+            ExtensionValue::SupportedVersions(v) => Some(v),
+            _ => None
+        }
+    }
+
     pub fn application_layer_protocol_negotiation(&self) -> Option<&ALPS> {
         match &self.value {
             ExtensionValue::ApplicationLayerProtocolNegotiation(v) => Some(v),
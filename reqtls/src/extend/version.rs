@@ -5,6 +5,8 @@ use super::super::version::Version;
 pub struct Versions {
     len: u8,
     versions: Vec<Version>,
+    ///ServerHello中只有选中的版本, 没有长度前缀
+    server: bool,
 }
 
 impl Versions {
@@ -12,6 +14,7 @@ impl Versions {
         Versions {
             len: 0,
             versions: vec![],
+            server: false,
         }
     }
 
@@ -26,7 +29,23 @@ impl Versions {
         res
     }
 
+    pub fn from_server_bytes(bytes: &[u8]) -> Self {
+        let mut res = Versions::new();
+        res.server = true;
+        if bytes.len() >= 2 {
+            res.versions.push(Version::new(u16::from_be_bytes([bytes[0], bytes[1]])));
+        }
+        res
+    }
+
+    pub fn selected(&self) -> Option<&Version> {
+        self.versions.first()
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
+        if self.server {
+            return self.versions.iter().map(|x| x.as_bytes()).collect::<Vec<_>>().concat();
+        }
         let mut res = vec![0];
         for version in &self.versions {
             res.extend(version.as_bytes());
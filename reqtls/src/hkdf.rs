@@ -0,0 +1,134 @@
+use super::cipher::suite::Hasher;
+use crate::error::RlsResult;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256, Sha384};
+
+enum HkdfKind {
+    Sha256,
+    Sha384,
+}
+
+impl HkdfKind {
+    fn hmac_sha(&self, secret: &[u8], data: &[&[u8]]) -> RlsResult<Vec<u8>> {
+        match self {
+            HkdfKind::Sha256 => {
+                let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret)?;
+                for datum in data {
+                    mac.update(datum);
+                }
+                Ok(mac.finalize().as_bytes().to_vec())
+            }
+            HkdfKind::Sha384 => {
+                let mut mac: Hmac<Sha384> = Hmac::new_from_slice(secret)?;
+                for datum in data {
+                    mac.update(datum);
+                }
+                Ok(mac.finalize().as_bytes().to_vec())
+            }
+        }
+    }
+
+    fn empty_hash(&self) -> Vec<u8> {
+        match self {
+            HkdfKind::Sha256 => Sha256::digest(b"").to_vec(),
+            HkdfKind::Sha384 => Sha384::digest(b"").to_vec(),
+        }
+    }
+
+    fn hash_size(&self) -> usize {
+        match self {
+            HkdfKind::Sha256 => 32,
+            HkdfKind::Sha384 => 48,
+        }
+    }
+}
+
+///#### tls1.3密钥派生(RFC 8446 7.1)
+/// * extract: HMAC(salt, ikm)
+/// * expand_label: HKDF-Expand(secret, HkdfLabel, len)
+pub struct Hkdf(HkdfKind);
+
+impl Hkdf {
+    pub fn default() -> Hkdf {
+        Hkdf(HkdfKind::Sha256)
+    }
+
+    pub fn from_hasher(hasher: &Hasher) -> Hkdf {
+        match hasher {
+            Hasher::Sha256(_) => Hkdf(HkdfKind::Sha256),
+            Hasher::Sha384(_) => Hkdf(HkdfKind::Sha384),
+        }
+    }
+
+    pub fn hash_size(&self) -> usize {
+        self.0.hash_size()
+    }
+
+    ///salt或ikm为空时按hash长度的0填充
+    pub fn extract(&self, salt: &[u8], ikm: &[u8]) -> RlsResult<Vec<u8>> {
+        let zeros = vec![0; self.hash_size()];
+        let salt = if salt.is_empty() { &zeros } else { salt };
+        let ikm = if ikm.is_empty() { &zeros } else { ikm };
+        self.0.hmac_sha(salt, &[ikm])
+    }
+
+    pub fn expand(&self, prk: &[u8], info: &[u8], out: &mut [u8]) -> RlsResult<()> {
+        let mut t = vec![];
+        for (i, chunk) in out.chunks_mut(self.hash_size()).enumerate() {
+            // T(i) = HMAC(prk, T(i-1) + info + i)
+            t = self.0.hmac_sha(prk, &[&t, info, &[i as u8 + 1]])?;
+            chunk.copy_from_slice(&t[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    pub fn expand_label(&self, secret: &[u8], label: &str, context: &[u8], len: usize) -> RlsResult<Vec<u8>> {
+        let label = [b"tls13 ", label.as_bytes()].concat();
+        let mut info = (len as u16).to_be_bytes().to_vec();
+        info.push(label.len() as u8);
+        info.extend(label);
+        info.push(context.len() as u8);
+        info.extend_from_slice(context);
+        let mut res = vec![0; len];
+        self.expand(secret, &info, &mut res)?;
+        Ok(res)
+    }
+
+    ///transcript_hash为空时使用空消息的hash
+    pub fn derive_secret(&self, secret: &[u8], label: &str, transcript_hash: &[u8]) -> RlsResult<Vec<u8>> {
+        let empty = self.0.empty_hash();
+        let context = if transcript_hash.is_empty() { &empty } else { transcript_hash };
+        self.expand_label(secret, label, context, self.hash_size())
+    }
+
+    pub fn finished_verify_data(&self, base_key: &[u8], transcript_hash: &[u8]) -> RlsResult<Vec<u8>> {
+        let finished_key = self.expand_label(base_key, "finished", &[], self.hash_size())?;
+        self.0.hmac_sha(&finished_key, &[transcript_hash])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::hkdf::Hkdf;
+
+    #[test]
+    fn test_hkdf() {
+        //RFC 8448 Simple 1-RTT Handshake
+        let hkdf = Hkdf::default();
+        let early_secret = hkdf.extract(&[], &[]).unwrap();
+        assert_eq!(hex::encode(&early_secret), "33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a");
+        let derived = hkdf.derive_secret(&early_secret, "derived", &[]).unwrap();
+        assert_eq!(hex::encode(&derived), "6f2615a108c702c5678f54fc9dbab69716c076189c48250cebeac3576c3611ba");
+        let share_secret = hex::decode("8bd4054fb55b9d63fdfbacf9f04b9f0d35e6d63f537563efd46272900f89492d").unwrap();
+        let handshake_secret = hkdf.extract(&derived, &share_secret).unwrap();
+        assert_eq!(hex::encode(&handshake_secret), "1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac");
+        let transcript = hex::decode("860c06edc07858ee8e78f0e7428c58edd6b43f2ca3e6e95f02ed063cf0e1cad8").unwrap();
+        let client_secret = hkdf.derive_secret(&handshake_secret, "c hs traffic", &transcript).unwrap();
+        assert_eq!(hex::encode(&client_secret), "b3eddb126e067f35a780b3abf45e2d8f3b1a950738f52e9600746a0e27a55a21");
+        let key = hkdf.expand_label(&client_secret, "key", &[], 16).unwrap();
+        assert_eq!(hex::encode(&key), "dbfaa693d1762c5b666af5d950258d01");
+        let iv = hkdf.expand_label(&client_secret, "iv", &[], 12).unwrap();
+        assert_eq!(hex::encode(&iv), "5bd3c71b836e0b76bb73265f");
+    }
+}
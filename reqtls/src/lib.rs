@@ -7,10 +7,12 @@ pub use secret::key::PriKey;
 pub use record::{RecordLayer, RecordType};
 pub use error::RlsError;
 pub use version::Version;
+pub use verify::{RootStore, Verifier};
 
 mod extend;
 mod message;
 mod prf;
+mod hkdf;
 mod cipher;
 mod connection;
 mod record;
@@ -19,4 +21,5 @@ mod bytes;
 mod fingerprint;
 mod secret;
 mod error;
+mod verify;
 pub mod rand;
@@ -35,6 +35,11 @@ impl Certificate {
         res.extend(self.value.as_bytes());
         res
     }
+
+    ///DER编码的证书
+    pub fn value(&self) -> &[u8] {
+        self.value.as_ref()
+    }
 }
 
 #[derive(Debug)]
@@ -80,6 +85,10 @@ impl Certificates {
     pub fn len(&self) -> u32 {
         self.len
     }
+
+    pub fn certificates(&self) -> &Vec<Certificate> {
+        &self.certificates
+    }
 }
 
 #[derive(Debug)]
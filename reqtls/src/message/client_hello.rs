@@ -1,5 +1,6 @@
 use crate::error::RlsResult;
 use crate::extend::alps::ALPS;
+use crate::extend::key_share::KeyShare;
 use super::super::bytes::Bytes;
 use super::super::cipher::suite::CipherSuite;
 use super::super::extend::Extension;
@@ -113,7 +114,6 @@ impl ClientHello {
             vec![]
         };
         let ja3_str = format!("{},{},{},{},{}", ver, suite.join("-"), ext.join("-"), group.join("-"), formats.join("-"));
-        println!("{}", ja3_str);
         hex::encode(md5::compute(ja3_str.as_bytes()).as_slice())
     }
 
@@ -148,6 +148,14 @@ impl ClientHello {
         self.extensions = extension;
     }
 
+    ///pre_shared_key必须是最后一个扩展
+    pub fn push_extension(&mut self, extension: Extension) {
+        match self.extensions.last() {
+            Some(v) if v.extension_type().as_u16() == 0x29 => self.extensions.insert(self.extensions.len() - 1, extension),
+            _ => self.extensions.push(extension)
+        }
+    }
+
     pub fn server_name(&self) -> Option<&str> {
         let extension = self.extensions.iter().find(|x| x.extension_type().as_u16() == ExtensionKind::ServerName as u16)?;
         Some(extension.server_name()?.value())
@@ -169,6 +177,11 @@ impl ClientHello {
         }
     }
 
+    pub fn key_share_mut(&mut self) -> Option<&mut KeyShare> {
+        let extension = self.extensions.iter_mut().find(|x| x.extension_type().as_u16() == ExtensionKind::KeyShare as u16)?;
+        extension.key_share_mut()
+    }
+
     pub fn remove_tls13(&mut self) {
         let extend = self.extensions.iter_mut().find(|x| x.extension_type().as_u16() == ExtensionKind::SupportedVersions as u16);
         if let Some(ext) = extend {
@@ -82,10 +82,7 @@ impl ServerHellmanParam {
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut res = vec![self.curve_type.as_u8()];
-        res.extend(self.named_curve.as_bytes());
-        res.push(self.pub_key.len() as u8);
-        res.extend(self.pub_key.as_bytes());
+        let mut res = self.params_bytes();
         res.extend(self.signature_algorithm.as_bytes());
         res.extend((self.signature.len() as u16).to_be_bytes());
         res.extend(self.signature.as_bytes());
@@ -99,6 +96,23 @@ impl ServerHellmanParam {
     pub fn named_curve(&self) -> &NamedCurve {
         &self.named_curve
     }
+
+    ///签名的参数部分: curve_type + named_curve + pub_key_len + pub_key
+    pub fn params_bytes(&self) -> Vec<u8> {
+        let mut res = vec![self.curve_type.as_u8()];
+        res.extend(self.named_curve.as_bytes());
+        res.push(self.pub_key.len() as u8);
+        res.extend(self.pub_key.as_bytes());
+        res
+    }
+
+    pub fn signature_algorithm(&self) -> &SignatureAlgorithm {
+        &self.signature_algorithm
+    }
+
+    pub fn signature(&self) -> &Bytes {
+        &self.signature
+    }
 }
 
 #[derive(Debug)]
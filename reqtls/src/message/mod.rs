@@ -136,6 +136,9 @@ impl<'a> Message<'a> {
                 HandshakeType::NewSessionTicket => Ok(Message::NewSessionTicket(SessionTicket::from_bytes(handshake_type, &bytes)?)),
                 HandshakeType::CertificateStatus => Ok(Message::CertificateStatus(CertificateStatus::from_bytes(handshake_type, &bytes))),
                 HandshakeType::CipherSpec => Ok(Message::CipherSpec),
+                //tls1.3的握手消息由Connection直接处理
+                HandshakeType::EncryptedExtensions | HandshakeType::CertificateRequest |
+                HandshakeType::CertificateVerify | HandshakeType::KeyUpdate => Ok(Message::Payload(Payload(bytes))),
             }
         } else {
             Ok(Message::Payload(Payload(bytes)))
@@ -240,12 +243,17 @@ pub enum HandshakeType {
     ClientHello = 0x1,
     ServerHello = 0x2,
     NewSessionTicket = 0x4,
+    EncryptedExtensions = 0x8,
     Certificate = 0xb,
     ServerKeyExchange = 0xc,
+    CertificateRequest = 0xd,
     ServerHelloDone = 0xe,
+    CertificateVerify = 0xf,
     ClientKeyExchange = 0x10,
+    ///Finished
     CipherSpec = 0x14,
     CertificateStatus = 0x16,
+    KeyUpdate = 0x18,
 }
 
 impl HandshakeType {
@@ -254,12 +262,16 @@ impl HandshakeType {
             0x1 => Some(HandshakeType::ClientHello),
             0x2 => Some(HandshakeType::ServerHello),
             0x4 => Some(HandshakeType::NewSessionTicket),
+            0x8 => Some(HandshakeType::EncryptedExtensions),
             0xb => Some(HandshakeType::Certificate),
             0xc => Some(HandshakeType::ServerKeyExchange),
+            0xd => Some(HandshakeType::CertificateRequest),
             0xe => Some(HandshakeType::ServerHelloDone),
+            0xf => Some(HandshakeType::CertificateVerify),
             0x10 => Some(HandshakeType::ClientKeyExchange),
             0x14 => Some(HandshakeType::CipherSpec),
             0x16 => Some(HandshakeType::CertificateStatus),
+            0x18 => Some(HandshakeType::KeyUpdate),
             _ => None
         }
     }
@@ -6,6 +6,12 @@ use super::super::version::Version;
 use super::super::bytes::Bytes;
 use super::super::extend::alps::ALPN;
 use super::super::extend::ExtensionKind;
+use super::super::extend::key_share::KeyShareEntry;
+
+const HELLO_RETRY_REQUEST: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
 
 #[derive(Debug)]
 pub struct ServerHello {
@@ -50,7 +56,7 @@ impl ServerHello {
         res.cipher_suite = CipherSuite::new(v);
         res.compress_method = bytes[index + 2];
         res.extend_len = u16::from_be_bytes([bytes[index + 3], bytes[index + 4]].try_into()?);
-        res.extensions = Extension::from_bytes(&bytes[index + 5..index + 5 + res.extend_len as usize])?;
+        res.extensions = Extension::from_server_bytes(&bytes[index + 5..index + 5 + res.extend_len as usize])?;
         Ok(res)
     }
 
@@ -65,6 +71,29 @@ impl ServerHello {
         Some(alpn)
     }
 
+    ///tls1.3通过supported_versions扩展协商版本
+    pub fn selected_version(&self) -> u16 {
+        let extend = self.extensions.iter().find(|x| x.extension_type().as_u16() == ExtensionKind::SupportedVersions as u16);
+        match extend.and_then(|x| x.supported_versions()).and_then(|x| x.selected()) {
+            Some(v) => v.as_u16(),
+            None => self.version.as_u16()
+        }
+    }
+
+    pub fn key_share(&self) -> Option<&KeyShareEntry> {
+        let extend = self.extensions.iter().find(|x| x.extension_type().as_u16() == ExtensionKind::KeyShare as u16)?;
+        extend.key_share()?.entries().first()
+    }
+
+    pub fn extension(&self, kind: u16) -> Option<&Extension> {
+        self.extensions.iter().find(|x| x.extension_type().as_u16() == kind)
+    }
+
+    ///HelloRetryRequest的random为固定值SHA-256("HelloRetryRequest")
+    pub fn is_retry_request(&self) -> bool {
+        self.random.as_ref() == &HELLO_RETRY_REQUEST[..]
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut res = vec![self.handshake_type.as_u8(), 0, 0, 0];
         // res.extend_from_slice(&(self.len as u32).to_be_bytes()[1..]);
@@ -1,6 +1,7 @@
 use super::super::message::key_exchange::NamedCurve;
 use crate::error::RlsResult;
 use crate::rand::CryptRand;
+use aws_lc_rs::kem::{AlgorithmId, Ciphertext, DecapsulationKey, ML_KEM_768};
 use p256::elliptic_curve::sec1::ToEncodedPoint;
 
 #[allow(non_camel_case_types)]
@@ -8,6 +9,8 @@ pub enum PriKey {
     x25519(x25519_dalek::EphemeralSecret),
     Secp256r1(p256::ecdh::EphemeralSecret),
     Secp384r1(p384::ecdh::EphemeralSecret),
+    ///ML-KEM-768 + x25519混合密钥
+    X25519MLKEM768(DecapsulationKey<AlgorithmId>, x25519_dalek::EphemeralSecret),
 }
 
 impl PriKey {
@@ -30,6 +33,22 @@ impl PriKey {
             }
         }
     }
+    ///tls1.3的key_share按group生成密钥，不支持的group返回None
+    pub fn from_group(group: u16) -> RlsResult<Option<PriKey>> {
+        match group {
+            0x1d => Ok(Some(PriKey::new(&NamedCurve::x25519)?)),
+            0x17 => Ok(Some(PriKey::new(&NamedCurve::Secp256r1)?)),
+            0x18 => Ok(Some(PriKey::new(&NamedCurve::Secp384r1)?)),
+            0x11ec => {
+                let mlkem = DecapsulationKey::generate(&ML_KEM_768)?;
+                let mut rng = CryptRand::new();
+                let x25519 = x25519_dalek::EphemeralSecret::random_from_rng(&mut rng);
+                Ok(Some(PriKey::X25519MLKEM768(mlkem, x25519)))
+            }
+            _ => Ok(None)
+        }
+    }
+
     pub fn diffie_hellman(self, pub_key: impl AsRef<[u8]>) -> RlsResult<Vec<u8>> {
         match self {
             PriKey::x25519(v) => {
@@ -48,6 +67,15 @@ impl PriKey {
                 let share_secret = v.diffie_hellman(&pub_key);
                 Ok(share_secret.raw_secret_bytes().to_vec())
             }
+            PriKey::X25519MLKEM768(mlkem, x25519) => {
+                //服务端: ciphertext(1088) + x25519公钥(32)
+                let pub_key = pub_key.as_ref();
+                if pub_key.len() != 1120 { return Err("invalid X25519MLKEM768 key share".into()); }
+                let mlkem_secret = mlkem.decapsulate(Ciphertext::from(&pub_key[..1088]))?;
+                let x25519_pub: [u8; 32] = pub_key[1088..].try_into()?;
+                let x25519_secret = x25519.diffie_hellman(&x25519_dalek::PublicKey::from(x25519_pub));
+                Ok([mlkem_secret.as_ref(), x25519_secret.as_bytes()].concat())
+            }
         }
     }
 
@@ -65,6 +93,12 @@ impl PriKey {
                 let pub_key = v.public_key().to_encoded_point(false);
                 pub_key.as_bytes().to_vec()
             }
+            PriKey::X25519MLKEM768(mlkem, x25519) => {
+                //客户端: encapsulation key(1184) + x25519公钥(32)
+                let mut res = mlkem.encapsulation_key().and_then(|x| x.key_bytes()).map(|x| x.as_ref().to_vec()).unwrap_or_default();
+                res.extend(x25519_dalek::PublicKey::from(x25519).to_bytes());
+                res
+            }
         }
     }
 }
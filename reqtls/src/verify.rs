@@ -0,0 +1,214 @@
+use crate::error::{RlsError, RlsResult};
+use pki_types::pem::PemObject;
+use pki_types::{CertificateDer, ServerName, SignatureVerificationAlgorithm, TrustAnchor, UnixTime};
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Formatter};
+use webpki::aws_lc_rs as algs;
+use webpki::{EndEntityCert, KeyUsage};
+
+///证书链签名支持的算法
+static SUPPORTED_SIG_ALGS: &[&dyn SignatureVerificationAlgorithm] = &[
+    algs::ECDSA_P256_SHA256,
+    algs::ECDSA_P256_SHA384,
+    algs::ECDSA_P384_SHA256,
+    algs::ECDSA_P384_SHA384,
+    algs::ECDSA_P521_SHA256,
+    algs::ECDSA_P521_SHA384,
+    algs::ECDSA_P521_SHA512,
+    algs::ED25519,
+    algs::RSA_PKCS1_2048_8192_SHA256,
+    algs::RSA_PKCS1_2048_8192_SHA384,
+    algs::RSA_PKCS1_2048_8192_SHA512,
+    algs::RSA_PKCS1_3072_8192_SHA384,
+    algs::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    algs::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    algs::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+];
+
+///#### 受信任的根证书
+/// * 默认使用webpki-roots(Mozilla)的根证书
+/// * 自签名证书可以通过add_der/add_pem添加
+#[derive(Clone)]
+pub struct RootStore {
+    roots: Vec<TrustAnchor<'static>>,
+    //根证书集合的标识，每添加一个证书更新为sha256(id + der)
+    id: [u8; 32],
+}
+
+impl RootStore {
+    pub fn empty() -> RootStore {
+        RootStore { roots: vec![], id: [0; 32] }
+    }
+
+    pub fn webpki() -> RootStore {
+        RootStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(), id: Sha256::digest(b"webpki-roots").into() }
+    }
+
+    pub fn add_der(&mut self, der: impl AsRef<[u8]>) -> RlsResult<()> {
+        let der = CertificateDer::from(der.as_ref());
+        self.roots.push(webpki::anchor_from_trusted_cert(&der)?.to_owned());
+        self.id = Sha256::new().chain_update(self.id).chain_update(der.as_ref()).finalize().into();
+        Ok(())
+    }
+
+    ///根证书集合的标识，添加了相同顺序的相同证书时一致，可用于区分使用不同根证书校验的连接
+    pub fn id(&self) -> String {
+        hex::encode(self.id)
+    }
+
+    ///添加pem中所有的CERTIFICATE，返回添加的数量
+    pub fn add_pem(&mut self, pem: impl AsRef<[u8]>) -> RlsResult<usize> {
+        let mut count = 0;
+        for der in CertificateDer::pem_slice_iter(pem.as_ref()) {
+            self.add_der(der?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+impl Debug for RootStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RootStore").field("roots", &self.roots.len()).finish()
+    }
+}
+
+///#### 服务端证书校验
+/// * 证书链: 从叶子证书经中间证书构建到根证书，并校验签名、有效期、用途(serverAuth)
+/// * 域名: 叶子证书的SAN需要匹配SNI
+/// * 握手签名: tls1.2的ServerKeyExchange和tls1.3的CertificateVerify
+pub struct Verifier {
+    server_name: String,
+    roots: RootStore,
+}
+
+impl Verifier {
+    pub fn new(server_name: impl ToString, roots: RootStore) -> Verifier {
+        Verifier {
+            server_name: server_name.to_string(),
+            roots,
+        }
+    }
+
+    ///chain[0]为叶子证书，其余为中间证书
+    pub fn verify_chain(&self, chain: &[Vec<u8>]) -> RlsResult<()> {
+        let (leaf, intermediates) = chain.split_first().ok_or(RlsError::InvalidCertificate("server certificate none".to_string()))?;
+        let leaf = CertificateDer::from(leaf.as_slice());
+        let cert = EndEntityCert::try_from(&leaf)?;
+        let intermediates = intermediates.iter().map(|x| CertificateDer::from(x.as_slice())).collect::<Vec<_>>();
+        cert.verify_for_usage(SUPPORTED_SIG_ALGS, &self.roots.roots, &intermediates, UnixTime::now(), KeyUsage::server_auth(), None, None)?;
+        let server_name = ServerName::try_from(self.server_name.as_str()).map_err(|e| RlsError::InvalidCertificate(e.to_string()))?;
+        cert.verify_is_valid_for_subject_name(&server_name)?;
+        Ok(())
+    }
+
+    ///#### 使用叶子证书的公钥校验握手签名，scheme为SignatureScheme(RFC 8446 4.2.3)
+    /// * tls1.2的ServerKeyExchange: 允许rsa_pkcs1，ecdsa的scheme不限制曲线
+    /// * tls1.3的CertificateVerify: 不允许rsa_pkcs1，ecdsa的scheme限定曲线(RFC 8446 4.4.3)
+    pub fn verify_signature(&self, leaf: &[u8], scheme: u16, message: &[u8], signature: &[u8], tls13: bool) -> RlsResult<()> {
+        let leaf = CertificateDer::from(leaf);
+        let cert = EndEntityCert::try_from(&leaf)?;
+        let algs: &[&dyn SignatureVerificationAlgorithm] = match (scheme, tls13) {
+            (0x0403, true) => &[algs::ECDSA_P256_SHA256],
+            (0x0503, true) => &[algs::ECDSA_P384_SHA384],
+            (0x0403, false) => &[algs::ECDSA_P256_SHA256, algs::ECDSA_P384_SHA256, algs::ECDSA_P521_SHA256],
+            (0x0503, false) => &[algs::ECDSA_P384_SHA384, algs::ECDSA_P256_SHA384, algs::ECDSA_P521_SHA384],
+            (0x0603, _) => &[algs::ECDSA_P521_SHA512],
+            (0x0807, _) => &[algs::ED25519],
+            (0x0401, false) => &[algs::RSA_PKCS1_2048_8192_SHA256],
+            (0x0501, false) => &[algs::RSA_PKCS1_2048_8192_SHA384],
+            (0x0601, false) => &[algs::RSA_PKCS1_2048_8192_SHA512],
+            (0x0804, _) => &[algs::RSA_PSS_2048_8192_SHA256_LEGACY_KEY],
+            (0x0805, _) => &[algs::RSA_PSS_2048_8192_SHA384_LEGACY_KEY],
+            (0x0806, _) => &[algs::RSA_PSS_2048_8192_SHA512_LEGACY_KEY],
+            _ => return Err(RlsError::InvalidCertificate(format!("unsupported signature scheme: {:#06x}", scheme)))
+        };
+        let mut error = None;
+        for alg in algs {
+            match cert.verify_signature(*alg, message, signature) {
+                Ok(_) => return Ok(()),
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.map(RlsError::from).unwrap_or(RlsError::InvalidCertificate("signature verify failed".to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RootStore, Verifier};
+    use pki_types::pem::PemObject;
+    use pki_types::CertificateDer;
+
+    const MESSAGE: &[u8] = b"reqtls signature test";
+
+    fn der(pem: &[u8]) -> Vec<u8> {
+        CertificateDer::from_pem_slice(pem).unwrap().to_vec()
+    }
+
+    fn verifier(server_name: &str) -> Verifier {
+        let mut roots = RootStore::empty();
+        assert_eq!(roots.add_pem(include_bytes!("../testdata/root.pem")).unwrap(), 1);
+        Verifier::new(server_name, roots)
+    }
+
+    #[test]
+    fn test_root_store_id() {
+        let pem = include_bytes!("../testdata/root.pem");
+        let (mut a, mut b) = (RootStore::empty(), RootStore::empty());
+        assert_eq!(a.id(), b.id());
+        a.add_pem(pem).unwrap();
+        assert_ne!(a.id(), b.id());
+        b.add_pem(pem).unwrap();
+        assert_eq!(a.id(), b.id());
+        assert_ne!(RootStore::webpki().id(), a.id());
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let chain = [der(include_bytes!("../testdata/leaf.pem"))];
+        assert!(verifier("localhost").verify_chain(&chain).is_ok());
+        //域名不匹配
+        assert!(verifier("example.com").verify_chain(&chain).is_err());
+        //根证书不受信任
+        assert!(Verifier::new("localhost", RootStore::webpki()).verify_chain(&chain).is_err());
+        //已过期
+        let expired = der(include_bytes!("../testdata/expired.pem"));
+        assert!(verifier("localhost").verify_chain(&[expired]).is_err());
+        assert!(verifier("localhost").verify_chain(&[]).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let verifier = verifier("localhost");
+        let ecdsa = der(include_bytes!("../testdata/leaf.pem"));
+        let sha256 = include_bytes!("../testdata/ecdsa_sha256.sig");
+        let sha384 = include_bytes!("../testdata/ecdsa_sha384.sig");
+        assert!(verifier.verify_signature(&ecdsa, 0x0403, MESSAGE, sha256, true).is_ok());
+        assert!(verifier.verify_signature(&ecdsa, 0x0403, b"reqtls", sha256, true).is_err());
+        let mut bad = sha256.to_vec();
+        let last = bad.len() - 1;
+        bad[last] ^= 1;
+        assert!(verifier.verify_signature(&ecdsa, 0x0403, MESSAGE, &bad, false).is_err());
+        //p256的证书使用ecdsa_secp384r1_sha384，tls1.2允许，tls1.3不允许
+        assert!(verifier.verify_signature(&ecdsa, 0x0503, MESSAGE, sha384, false).is_ok());
+        assert!(verifier.verify_signature(&ecdsa, 0x0503, MESSAGE, sha384, true).is_err());
+
+        let rsa = der(include_bytes!("../testdata/rsa.pem"));
+        let pkcs1 = include_bytes!("../testdata/rsa_pkcs1_sha256.sig");
+        let pss = include_bytes!("../testdata/rsa_pss_sha256.sig");
+        assert!(verifier.verify_signature(&rsa, 0x0401, MESSAGE, pkcs1, false).is_ok());
+        assert!(verifier.verify_signature(&rsa, 0x0401, MESSAGE, pkcs1, true).is_err());
+        assert!(verifier.verify_signature(&rsa, 0x0804, MESSAGE, pss, true).is_ok());
+        assert!(verifier.verify_signature(&rsa, 0x0804, MESSAGE, pkcs1, false).is_err());
+        assert!(verifier.verify_signature(&rsa, 0x0201, MESSAGE, pkcs1, false).is_err());
+    }
+}